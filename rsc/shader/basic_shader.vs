@@ -3,8 +3,7 @@
 in vec3 iPosition;
 
 uniform mat4 uModel;
-uniform mat4 uView;
-uniform mat4 uProjection;
+#include "camera.glsl"
 
 out vec3 FragPosition;
 
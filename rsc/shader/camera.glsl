@@ -0,0 +1,6 @@
+// camera of the frame, shared by the shaders
+layout(std140) uniform Camera {
+    mat4 uView;
+    mat4 uProjection;
+    vec4 uViewPosition; // w is unused
+};
@@ -0,0 +1,39 @@
+#version 140
+
+uniform mat4 uModel;
+
+#include "camera.glsl"
+
+in vec3 iPosition;
+in vec3 iNormal;
+in vec3 iColor;
+in vec2 iTexcoord;
+in float iGroup;
+
+// the inputs of the debug views, which each use some of them
+out vec3 WorldPosition;
+out vec3 ViewPosition;
+out vec3 Normal;
+out vec3 ViewNormal;
+out vec3 Color;
+out vec2 Texcoord;
+flat out float Group;
+
+void main()
+{
+    WorldPosition = vec3(uModel * vec4(iPosition, 1.0));
+    ViewPosition = vec3(uView * vec4(WorldPosition, 1.0));
+    Normal = mat3(transpose(inverse(uModel))) * iNormal;
+    ViewNormal = mat3(uView) * Normal;
+
+    // per-vertex color, white when the model has none
+#ifdef HAS_VERTEX_COLOR
+    Color = iColor;
+#else
+    Color = vec3(1.0);
+#endif
+    Texcoord = iTexcoord;
+    Group = iGroup;
+
+    gl_Position = uProjection * vec4(ViewPosition, 1.0);
+}
@@ -0,0 +1,12 @@
+#version 140
+
+in vec3 ViewPosition;
+
+// distances from the camera from uNear to uFar are shown from black to white
+uniform float uNear; // @range(0, 10) @default(0)
+uniform float uFar;  // @range(0.1, 100) @default(10)
+
+void main() {
+    float depth = (-ViewPosition.z - uNear) / max(uFar - uNear, 0.0001);
+    gl_FragColor = vec4(vec3(clamp(depth, 0.0, 1.0)), 1.0);
+}
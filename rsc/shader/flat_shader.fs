@@ -0,0 +1,15 @@
+#version 140
+
+in vec3 FragPosition;
+in vec3 Color;
+
+// edited in the viewer
+uniform vec3 uTint; // @default(1, 1, 1)
+
+#include "lighting.glsl"
+
+void main() {
+    // the normal of the face, from how the position changes across the pixel
+    vec3 normal = cross(dFdx(FragPosition), dFdy(FragPosition));
+    gl_FragColor = vec4(lambert(normal, uTint * Color), 1.0);
+}
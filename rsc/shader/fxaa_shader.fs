@@ -0,0 +1,44 @@
+#version 140
+
+in vec2 TexCoord;
+
+// the resolved scene, bound by the viewer
+uniform sampler2D uScene;   // @hidden
+uniform vec2 uTexelSize;    // @hidden
+// copy the scene as it is when false
+uniform bool uFxaa;         // @hidden
+
+const float REDUCE_MIN = 1.0 / 128.0;
+const float REDUCE_MUL = 1.0 / 8.0;
+const float SPAN_MAX = 8.0;
+const vec3 LUMA = vec3(0.299, 0.587, 0.114);
+
+void main() {
+    vec4 center = texture(uScene, TexCoord);
+    if (!uFxaa) {
+        gl_FragColor = center;
+        return;
+    }
+    float lumaNW = dot(texture(uScene, TexCoord + vec2(-1.0, -1.0) * uTexelSize).rgb, LUMA);
+    float lumaNE = dot(texture(uScene, TexCoord + vec2(1.0, -1.0) * uTexelSize).rgb, LUMA);
+    float lumaSW = dot(texture(uScene, TexCoord + vec2(-1.0, 1.0) * uTexelSize).rgb, LUMA);
+    float lumaSE = dot(texture(uScene, TexCoord + vec2(1.0, 1.0) * uTexelSize).rgb, LUMA);
+    float lumaM = dot(center.rgb, LUMA);
+    float lumaMin = min(lumaM, min(min(lumaNW, lumaNE), min(lumaSW, lumaSE)));
+    float lumaMax = max(lumaM, max(max(lumaNW, lumaNE), max(lumaSW, lumaSE)));
+
+    // blur along the edge, which runs across the steepest change of luma
+    vec2 dir = vec2(-((lumaNW + lumaNE) - (lumaSW + lumaSE)), (lumaNW + lumaSW) - (lumaNE + lumaSE));
+    float dirReduce = max((lumaNW + lumaNE + lumaSW + lumaSE) * 0.25 * REDUCE_MUL, REDUCE_MIN);
+    float rcpDirMin = 1.0 / (min(abs(dir.x), abs(dir.y)) + dirReduce);
+    dir = clamp(dir * rcpDirMin, vec2(-SPAN_MAX), vec2(SPAN_MAX)) * uTexelSize;
+
+    vec3 rgbA = 0.5 * (texture(uScene, TexCoord + dir * (1.0 / 3.0 - 0.5)).rgb
+        + texture(uScene, TexCoord + dir * (2.0 / 3.0 - 0.5)).rgb);
+    vec3 rgbB = rgbA * 0.5 + 0.25 * (texture(uScene, TexCoord - dir * 0.5).rgb
+        + texture(uScene, TexCoord + dir * 0.5).rgb);
+    // the wider blur is dropped when it reaches past the contrast of the neighbours
+    float lumaB = dot(rgbB, LUMA);
+    vec3 rgb = (lumaB < lumaMin || lumaB > lumaMax) ? rgbA : rgbB;
+    gl_FragColor = vec4(rgb, center.a);
+}
@@ -0,0 +1,12 @@
+#version 140
+
+// a triangle which covers the screen, in clip coordinates
+in vec2 iPosition;
+
+out vec2 TexCoord;
+
+void main()
+{
+    TexCoord = iPosition * 0.5 + 0.5;
+    gl_Position = vec4(iPosition, 0.0, 1.0);
+}
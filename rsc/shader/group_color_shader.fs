@@ -0,0 +1,16 @@
+#version 140
+
+in vec3 Normal;
+flat in float Group;
+
+#include "lighting.glsl"
+
+// a color made up from the group index, the same in every frame
+vec3 group_color(float group) {
+    vec3 random = fract(sin(vec3(group + 1.0) * vec3(12.9898, 78.233, 37.719)) * 43758.5453);
+    return 0.3 + 0.7 * random;
+}
+
+void main() {
+    gl_FragColor = vec4(lambert(Normal, group_color(Group)), 1.0);
+}
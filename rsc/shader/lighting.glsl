@@ -0,0 +1,14 @@
+// lighting shared by the shaders
+
+// a light far away, the same for every frame of the viewer
+layout(std140) uniform Lighting {
+    vec4 uLightPosition;
+    vec4 uLightColor;   // a is the share of ambient light
+};
+
+// diffuse light with an ambient part
+vec3 lambert(vec3 normal, vec3 color) {
+    float lum = max(dot(normalize(normal), normalize(uLightPosition.xyz)), 0.0);
+    float ambient = uLightColor.a;
+    return (ambient + (1.0 - ambient) * lum) * uLightColor.rgb * color;
+}
@@ -0,0 +1,7 @@
+#version 140
+
+in vec3 Color;
+
+void main() {
+    gl_FragColor = vec4(Color, 1.0);
+}
@@ -0,0 +1,16 @@
+#version 140
+
+uniform mat4 uModel;
+
+#include "camera.glsl"
+
+in vec3 iPosition;
+in vec3 iColor;
+
+out vec3 Color;
+
+void main()
+{
+    Color = iColor;
+    gl_Position = uProjection * uView * uModel * vec4(iPosition, 1.0);
+}
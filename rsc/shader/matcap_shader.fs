@@ -0,0 +1,13 @@
+#version 140
+
+in vec3 ViewNormal;
+in vec3 Color;
+
+// the lit sphere image, bound by the viewer
+uniform sampler2D uMatcap; // @hidden
+
+void main() {
+    // the normal facing the camera picks the middle of the sphere
+    vec2 uv = normalize(ViewNormal).xy * 0.5 + 0.5;
+    gl_FragColor = vec4(texture(uMatcap, uv).rgb * Color, 1.0);
+}
@@ -1,10 +1,21 @@
 #version 140
 
 in vec3 Normal;
-const vec3 LIGHT = vec3(5.0, 5.0, 5.0);
+in vec3 Color;
+
+// edited in the viewer
+uniform vec3 uTint;         // @default(1, 1, 1)
+uniform float uBrightness;  // @range(0, 2) @default(1)
+uniform bool uUnlit;        // draw the colors without lighting
+
+#include "lighting.glsl"
 
 void main() {
-    float lum = max(dot(normalize(Normal), normalize(LIGHT)), 0.0);
-    vec3 color = (0.3 + 0.7 * lum) * vec3(1.0, 1.0, 1.0);
-    gl_FragColor = vec4(color, 1.0);
+    vec3 color = uTint * Color;
+    // points of a point cloud have no normal and are not lit
+    if (uUnlit || length(Normal) == 0.0) {
+        gl_FragColor = vec4(uBrightness * color, 1.0);
+        return;
+    }
+    gl_FragColor = vec4(uBrightness * lambert(Normal, color), 1.0);
 }
\ No newline at end of file
@@ -1,14 +1,16 @@
 #version 140
 
 uniform mat4 uModel;
-uniform mat4 uView;
-uniform mat4 uProjection;
+
+#include "camera.glsl"
 
 in vec3 iPosition;
 in vec3 iNormal;
+in vec3 iColor;
 
 out vec3 FragPosition;
 out vec3 Normal;
+out vec3 Color;
 
 void main()
 {
@@ -20,6 +22,13 @@ void main()
     // Normal = iNormal;
     Normal = mat3(transpose(inverse(uModel))) * iNormal;
     
+    // per-vertex color, white when the model has none
+#ifdef HAS_VERTEX_COLOR
+    Color = iColor;
+#else
+    Color = vec3(1.0);
+#endif
+    
     // gl position
     gl_Position = uProjection * uView * vec4(FragPosition, 1.0);
 }
\ No newline at end of file
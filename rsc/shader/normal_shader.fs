@@ -0,0 +1,12 @@
+#version 140
+
+in vec3 Normal;
+
+void main() {
+    // points of a point cloud have no normal and are grey
+    if (length(Normal) == 0.0) {
+        gl_FragColor = vec4(0.5, 0.5, 0.5, 1.0);
+        return;
+    }
+    gl_FragColor = vec4(normalize(Normal) * 0.5 + 0.5, 1.0);
+}
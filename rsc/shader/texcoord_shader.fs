@@ -0,0 +1,8 @@
+#version 140
+
+in vec2 Texcoord;
+
+void main() {
+    // texcoords outside of 0..1 repeat
+    gl_FragColor = vec4(fract(Texcoord), 0.0, 1.0);
+}
@@ -0,0 +1,14 @@
+#version 140
+
+in vec2 Texcoord;
+
+// cells along each side of the 0..1 texture square
+uniform float uCheckerCount; // @range(1, 64) @default(8)
+
+void main() {
+    vec2 cell = floor(Texcoord * uCheckerCount);
+    float dark = mod(cell.x + cell.y, 2.0);
+    // the cells get redder with u and greener with v, so that flipped or rotated UVs show
+    vec3 color = vec3(0.5 + 0.5 * fract(Texcoord), 0.5);
+    gl_FragColor = vec4(mix(color, color * 0.4, dark), 1.0);
+}
@@ -0,0 +1,10 @@
+#version 140
+
+in vec3 ViewPosition;
+
+// positions from -1 / uScale to 1 / uScale are shown from black to full color
+uniform float uScale; // @range(0.01, 10) @default(1)
+
+void main() {
+    gl_FragColor = vec4(clamp(ViewPosition * uScale * 0.5 + 0.5, 0.0, 1.0), 1.0);
+}
@@ -0,0 +1,19 @@
+#version 140
+
+// 0 on the edges of the triangle, and above 1 along edges which are hidden
+in vec3 Barycentric;
+
+uniform vec3 uWireColor;
+uniform float uWireWidth;   // in pixels
+
+void main() {
+    // the coverage of the nearest edge, smoothed over a pixel by how fast the
+    // coordinates change across the screen
+    vec3 width = fwidth(Barycentric);
+    vec3 coverage = smoothstep(width * (uWireWidth - 0.5), width * (uWireWidth + 0.5), Barycentric);
+    float alpha = 1.0 - min(min(coverage.x, coverage.y), coverage.z);
+    if (alpha <= 0.0) {
+        discard;
+    }
+    gl_FragColor = vec4(uWireColor, alpha);
+}
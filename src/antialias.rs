@@ -0,0 +1,165 @@
+use gl::types::GLint;
+
+use crate::framebuffer::Framebuffer;
+use crate::shader::ShaderVariants;
+use crate::vertex::{Vertex, VertexLayout};
+
+type Vector2 = cgmath::Vector2<f32>;
+
+// sample counts of MSAA which can be chosen, 0 for none
+pub const SAMPLE_COUNTS: [u32; 5] = [0, 2, 4, 8, 16];
+
+// the most samples of SAMPLE_COUNTS which are no more than asked for and than the driver has
+pub fn supported_samples(samples: u32, max_samples: u32) -> u32 {
+    SAMPLE_COUNTS
+        .iter()
+        .copied()
+        .filter(|&count| count <= samples.min(max_samples))
+        .max()
+        .unwrap_or(0)
+}
+
+pub fn sample_label(samples: u32) -> String {
+    if samples == 0 {
+        "Off".to_string()
+    } else {
+        format!("{}x", samples)
+    }
+}
+
+// the most samples a multisampled framebuffer can have
+pub fn max_samples() -> u32 {
+    let mut samples: GLint = 0;
+    unsafe {
+        gl::GetIntegerv(gl::MAX_SAMPLES, &mut samples);
+    }
+    samples.max(0) as u32
+}
+
+// samples of the bound framebuffer, which are those of the window for the default one
+pub fn framebuffer_samples() -> u32 {
+    let mut samples: GLint = 0;
+    unsafe {
+        gl::GetIntegerv(gl::SAMPLES, &mut samples);
+    }
+    samples.max(0) as u32
+}
+
+// the scene drawn offscreen, multisampled or not. it is resolved into a texture which the
+// FXAA pass reads
+pub struct SceneTarget {
+    multisample: Option<Framebuffer>,
+    resolved: Framebuffer,
+}
+
+impl SceneTarget {
+    pub fn new(width: u32, height: u32, samples: u32) -> Result<SceneTarget, String> {
+        let multisample = if samples > 0 {
+            Some(Framebuffer::multisample(width, height, samples)?)
+        } else {
+            None
+        };
+        Ok(SceneTarget {
+            multisample,
+            resolved: Framebuffer::with_texture(width, height)?,
+        })
+    }
+
+    pub fn samples(&self) -> u32 {
+        self.multisample
+            .as_ref()
+            .map_or(0, |framebuffer| framebuffer.samples)
+    }
+
+    // whether the target can be drawn into as it is, or has to be made again
+    pub fn matches(&self, width: u32, height: u32, samples: u32) -> bool {
+        self.resolved.width == width && self.resolved.height == height && self.samples() == samples
+    }
+
+    // draw into the target, with the viewport set to its size
+    pub fn bind(&self) {
+        self.multisample.as_ref().unwrap_or(&self.resolved).bind();
+    }
+
+    // the scene with its samples resolved, which can be read back or filtered
+    pub fn resolve(&self) -> &Framebuffer {
+        if let Some(multisample) = self.multisample.as_ref() {
+            multisample.resolve_into(&self.resolved);
+        }
+        &self.resolved
+    }
+}
+
+// a fullscreen pass which copies a scene into the bound framebuffer, smoothing its edges
+// with FXAA when enabled
+pub struct Fxaa {
+    pub shaders: ShaderVariants,
+    triangle: Vertex,
+}
+
+#[allow(clippy::missing_safety_doc)]
+impl Fxaa {
+    pub fn new() -> Fxaa {
+        // a single triangle is larger than the screen, which clips it to a rectangle
+        let corners: [f32; 6] = [-1.0, -1.0, 3.0, -1.0, -1.0, 3.0];
+        Fxaa {
+            shaders: ShaderVariants::new("rsc/shader/fxaa_shader.vs", "rsc/shader/fxaa_shader.fs"),
+            triangle: Vertex::from_slice(&corners, &VertexLayout::floats(&[2]), gl::STATIC_DRAW),
+        }
+    }
+
+    pub fn is_built(&self) -> bool {
+        self.shaders.get(&[]).is_some_and(|shader| shader.id != 0)
+    }
+
+    // draw the color texture of `scene` over the viewport of the bound framebuffer. the
+    // depth test, blending and culling are left disabled
+    pub unsafe fn apply(&self, scene: &Framebuffer, enabled: bool) {
+        let shader = match self.shaders.get(&[]).filter(|shader| shader.id != 0) {
+            Some(shader) => shader,
+            None => return,
+        };
+        gl::Disable(gl::DEPTH_TEST);
+        gl::Disable(gl::BLEND);
+        gl::Disable(gl::CULL_FACE);
+        gl::PolygonMode(gl::FRONT_AND_BACK, gl::FILL);
+        gl::ActiveTexture(gl::TEXTURE0);
+        gl::BindTexture(gl::TEXTURE_2D, scene.color_texture());
+        shader.use_program();
+        shader.set_uniform("uScene", 0);
+        shader.set_uniform(
+            "uTexelSize",
+            Vector2::new(1.0 / scene.width as f32, 1.0 / scene.height as f32),
+        );
+        shader.set_uniform("uFxaa", enabled);
+        self.triangle.draw_arrays(gl::TRIANGLES);
+        gl::BindTexture(gl::TEXTURE_2D, 0);
+    }
+}
+
+impl Default for Fxaa {
+    fn default() -> Fxaa {
+        Fxaa::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ok_supported_samples() {
+        assert_eq!(4, supported_samples(4, 16));
+        assert_eq!(8, supported_samples(16, 8));
+        // drivers may report a count which is not one of the choices
+        assert_eq!(4, supported_samples(16, 6));
+        assert_eq!(0, supported_samples(0, 16));
+        assert_eq!(0, supported_samples(4, 0));
+    }
+
+    #[test]
+    fn test_ok_sample_label() {
+        assert_eq!("Off", sample_label(0));
+        assert_eq!("8x", sample_label(8));
+    }
+}
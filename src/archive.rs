@@ -0,0 +1,206 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, Read, Seek};
+use std::path::{Path, PathBuf};
+
+use flate2::read::GzDecoder;
+use zip::result::ZipError;
+use zip::ZipArchive;
+
+use crate::loader::LoaderRegistry;
+use crate::model::Model;
+
+const GZIP_MAGIC: &[u8] = b"\x1f\x8b";
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+
+pub fn is_gzip(head: &[u8]) -> bool {
+    head.starts_with(GZIP_MAGIC)
+}
+
+pub fn is_zip(head: &[u8]) -> bool {
+    head.starts_with(ZIP_MAGIC)
+}
+
+// true if the file starts like a zip archive. unreadable files are not archives
+pub fn is_zip_file(path: &Path) -> bool {
+    let mut head = [0; 4];
+    File::open(path)
+        .and_then(|mut file| file.read_exact(&mut head))
+        .is_ok()
+        && is_zip(&head)
+}
+
+pub fn gunzip(data: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut out = Vec::new();
+    GzDecoder::new(data).read_to_end(&mut out)?;
+    Ok(out)
+}
+
+// "model.obj.gz" is named "model.obj" after decompression, so that its extension tells the format
+pub fn strip_gz(path: &Path) -> PathBuf {
+    match path.extension() {
+        Some(ext) if ext.eq_ignore_ascii_case("gz") => path.with_extension(""),
+        _ => path.to_path_buf(),
+    }
+}
+
+// path of an entry referred to as `name` by the entry in `dir`. zip entries always use "/"
+fn entry_path(dir: &str, name: &str) -> String {
+    let mut parts: Vec<&str> = dir.split('/').filter(|s| !s.is_empty()).collect();
+    for part in name.split(['/', '\\']) {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            _ => parts.push(part),
+        }
+    }
+    parts.join("/")
+}
+
+// a zip file with models and the material libraries and textures they refer to
+pub struct ModelArchive<R: Read + Seek> {
+    zip: ZipArchive<R>,
+    // file entries in archive order
+    names: Vec<String>,
+}
+
+impl ModelArchive<File> {
+    pub fn open(path: &Path) -> Result<ModelArchive<File>, Box<dyn Error>> {
+        let file = File::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        ModelArchive::new(file)
+    }
+}
+
+impl<R: Read + Seek> ModelArchive<R> {
+    pub fn new(reader: R) -> Result<ModelArchive<R>, Box<dyn Error>> {
+        let mut zip = ZipArchive::new(reader)?;
+        let mut names = Vec::with_capacity(zip.len());
+        for i in 0..zip.len() {
+            let entry = zip.by_index(i)?;
+            if entry.is_file() {
+                names.push(entry.name().to_string());
+            }
+        }
+        Ok(ModelArchive { zip, names })
+    }
+
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    // entries which one of the loaders reads, also when they are gzip compressed
+    pub fn models(&self, registry: &LoaderRegistry) -> Vec<String> {
+        self.names
+            .iter()
+            .filter(|name| {
+                registry
+                    .find_by_extension(&strip_gz(Path::new(name)))
+                    .is_some()
+            })
+            .cloned()
+            .collect()
+    }
+
+    // data of an entry. a missing entry is an io::ErrorKind::NotFound error like a missing file
+    pub fn read(&mut self, name: &str) -> io::Result<Vec<u8>> {
+        let mut entry = self.zip.by_name(name).map_err(|e| match e {
+            ZipError::Io(e) => e,
+            ZipError::FileNotFound => {
+                io::Error::new(io::ErrorKind::NotFound, format!("{}: not in archive", name))
+            }
+            e => io::Error::new(io::ErrorKind::InvalidData, e.to_string()),
+        })?;
+        let mut data = Vec::with_capacity(entry.size() as usize);
+        entry.read_to_end(&mut data)?;
+        Ok(data)
+    }
+
+    // load a model entry. the files it refers to are looked up relative to it inside the
+    // archive, and its textures are kept as embedded textures since they are not on disk
+    pub fn load(&mut self, registry: &LoaderRegistry, name: &str) -> Result<Model, Box<dyn Error>> {
+        let data = self.read(name)?;
+        let dir = match name.rfind('/') {
+            Some(i) => name[..i].to_string(),
+            None => String::new(),
+        };
+        let mut resolver = |file: &str| self.read(&entry_path(&dir, file));
+        let mut model = registry.load_from_bytes(Path::new(name), &data, &mut resolver)?;
+        model.embedded_textures = model.read_textures(&mut resolver)?;
+        Ok(model)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::{Cursor, Write};
+    use zip::write::FileOptions;
+    use zip::ZipWriter;
+
+    const OBJ: &str = "mtllib ./scene.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n";
+
+    fn test_zip() -> Vec<u8> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        let entries: [(&str, &[u8]); 4] = [
+            ("models/a.obj", OBJ.as_bytes()),
+            (
+                "models/scene.mtl",
+                b"newmtl red\nKd 1 0 0\nmap_Kd tex/red.png\n",
+            ),
+            ("models/tex/red.png", b"png"),
+            ("models/b.ply", b"ply\nformat ascii 1.0\nend_header\n"),
+        ];
+        for (name, data) in entries.iter() {
+            zip.start_file(*name, FileOptions::default()).unwrap();
+            zip.write_all(data).unwrap();
+        }
+        zip.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn test_ok_entry_path() {
+        assert_eq!("a/b.mtl", entry_path("a", "b.mtl"));
+        assert_eq!("b.mtl", entry_path("", "./b.mtl"));
+        assert_eq!("c/t.png", entry_path("a/b", "../../c\\t.png"));
+    }
+
+    #[test]
+    fn test_ok_model_archive() {
+        let data = test_zip();
+        assert!(is_zip(&data));
+        let registry = LoaderRegistry::new();
+        let mut archive = ModelArchive::new(Cursor::new(data)).unwrap();
+        assert_eq!(4, archive.names().len());
+        assert_eq!(
+            vec!["models/a.obj", "models/b.ply"],
+            archive.models(&registry)
+        );
+
+        let model = archive.load(&registry, "models/a.obj").unwrap();
+        assert_eq!(vec![3], model.polygon_sizes);
+        assert_eq!("red", model.materials[0].name);
+        assert_eq!(
+            vec![("tex/red.png".to_string(), b"png".to_vec())],
+            model.embedded_textures
+        );
+        assert!(archive.load(&registry, "models/c.obj").is_err());
+    }
+
+    #[test]
+    fn test_ok_gunzip() {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(OBJ.as_bytes()).unwrap();
+        let data = encoder.finish().unwrap();
+        assert!(is_gzip(&data));
+        assert_eq!(OBJ.as_bytes(), &gunzip(&data).unwrap()[..]);
+        assert!(gunzip(b"\x1f\x8bbroken").is_err());
+        assert_eq!(
+            PathBuf::from("dir/model.obj"),
+            strip_gz(Path::new("dir/model.obj.GZ"))
+        );
+    }
+}
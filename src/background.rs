@@ -0,0 +1,134 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+use indicatif::{ProgressBar, ProgressStyle};
+
+use crate::cache::{self, CacheMode, PreparedModel};
+use crate::loader::LoaderRegistry;
+use crate::progress::Progress;
+
+// a model loaded on its own thread, through the cache unless it is bypassed. the result
+// is taken on the thread which owns the OpenGL context, which uploads it
+pub struct BackgroundLoad {
+    path: PathBuf,
+    progress: Arc<Progress>,
+    handle: Option<JoinHandle<Result<PreparedModel, String>>>,
+    // the same progress in the terminal
+    bar: ProgressBar,
+}
+
+impl BackgroundLoad {
+    pub fn start(
+        registry: &Arc<LoaderRegistry>,
+        path: &Path,
+        cache_dir: &Path,
+        cache_mode: CacheMode,
+    ) -> BackgroundLoad {
+        let progress = Arc::new(Progress::new());
+        let handle = {
+            let registry = Arc::clone(registry);
+            let progress = Arc::clone(&progress);
+            let path = path.to_path_buf();
+            let cache_dir = cache_dir.to_path_buf();
+            thread::spawn(move || {
+                cache::load(&registry, &path, &cache_dir, cache_mode, &progress)
+                    .map_err(|e| e.to_string())
+            })
+        };
+        let bar = ProgressBar::new(0);
+        bar.set_style(
+            ProgressStyle::default_bar()
+                .template("{bar:40} {bytes}/{total_bytes} [{elapsed_precise}] {wide_msg}")
+                .progress_chars("##-"),
+        );
+        BackgroundLoad {
+            path: path.to_path_buf(),
+            progress,
+            handle: Some(handle),
+            bar,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn progress(&self) -> &Progress {
+        &self.progress
+    }
+
+    pub fn cancel(&self) {
+        self.progress.cancel();
+    }
+
+    // update the terminal progress and take the result once the thread has finished.
+    // None while it is still loading
+    pub fn poll(&mut self) -> Option<Result<PreparedModel, String>> {
+        self.bar.set_length(self.progress.total_bytes());
+        self.bar.set_position(self.progress.bytes_read());
+        self.bar
+            .set_message(format!("{} faces", self.progress.faces()));
+        if !self.handle.as_ref()?.is_finished() {
+            return None;
+        }
+        self.bar.finish_and_clear();
+        let result = self
+            .handle
+            .take()?
+            .join()
+            .unwrap_or_else(|_| Err("loading thread panicked".to_string()));
+        Some(result)
+    }
+}
+
+// a load which is replaced or abandoned stops in the background
+impl Drop for BackgroundLoad {
+    fn drop(&mut self) {
+        if self.handle.is_some() {
+            self.progress.cancel();
+            self.bar.finish_and_clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::progress::CANCELLED;
+    use std::fs;
+    use std::time::Duration;
+
+    fn wait(load: &mut BackgroundLoad) -> Result<PreparedModel, String> {
+        loop {
+            if let Some(result) = load.poll() {
+                return result;
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn test_ok_background_load() {
+        let path = std::env::temp_dir().join("obj_viewer_test_background_load.obj");
+        fs::write(&path, "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\nf 3 2 1\n").unwrap();
+        let registry = Arc::new(LoaderRegistry::new());
+        let cache_dir = std::env::temp_dir();
+
+        let mut load = BackgroundLoad::start(&registry, &path, &cache_dir, CacheMode::Bypass);
+        let prepared = wait(&mut load).unwrap();
+        assert_eq!(vec![3, 3], prepared.model.polygon_sizes);
+        assert_eq!(2, load.progress().faces());
+        assert_eq!(1.0, load.progress().fraction());
+        assert!(load.poll().is_none());
+
+        // a load cancelled before it has read anything gives the cancel error
+        let mut load = BackgroundLoad::start(&registry, &path, &cache_dir, CacheMode::Bypass);
+        load.cancel();
+        match wait(&mut load) {
+            Ok(_) => assert!(load.progress().is_cancelled()),
+            Err(e) => assert_eq!(CANCELLED, e),
+        }
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,62 @@
+use std::error::Error;
+use std::fs;
+use std::time::{Duration, Instant};
+
+use crate::cli::BenchOptions;
+use crate::model::Model;
+
+// the fastest of `runs` loads, with the model of the last one
+fn fastest<F>(runs: u32, load: F) -> Result<(Duration, Model), Box<dyn Error>>
+where
+    F: Fn(&mut Model) -> Result<(), Box<dyn Error>>,
+{
+    let mut best = Duration::MAX;
+    let mut model = Model::new();
+    for _ in 0..runs {
+        model = Model::new();
+        let start = Instant::now();
+        load(&mut model)?;
+        best = best.min(start.elapsed());
+    }
+    Ok((best, model))
+}
+
+fn report(name: &str, time: Duration, megabytes: f64) {
+    let seconds = time.as_secs_f64();
+    println!(
+        "{:<12}{:>10.3} s{:>10.1} MB/s",
+        name,
+        seconds,
+        megabytes / seconds.max(f64::EPSILON)
+    );
+}
+
+// compare the line by line loader with the parallel one on the same OBJ file
+pub fn run(options: &BenchOptions) -> Result<(), Box<dyn Error>> {
+    let path = options.path.to_str().ok_or("path is not valid UTF-8")?;
+    let megabytes = fs::metadata(path)?.len() as f64 / 1_000_000.0;
+    println!(
+        "{}: {:.1} MB, {} runs, {} threads",
+        path,
+        megabytes,
+        options.runs,
+        rayon::current_num_threads()
+    );
+
+    let (sequential_time, sequential) = fastest(options.runs, |model| model.load(path))?;
+    report("sequential", sequential_time, megabytes);
+    let (parallel_time, parallel) = fastest(options.runs, |model| model.load_parallel(path))?;
+    report("parallel", parallel_time, megabytes);
+    println!(
+        "speedup: {:.2}x",
+        sequential_time.as_secs_f64() / parallel_time.as_secs_f64().max(f64::EPSILON)
+    );
+
+    if sequential.vertex != parallel.vertex
+        || sequential.corners != parallel.corners
+        || sequential.groups != parallel.groups
+    {
+        return Err("the parallel loader gave a different model".into());
+    }
+    Ok(())
+}
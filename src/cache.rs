@@ -0,0 +1,703 @@
+use std::convert::TryInto;
+use std::env;
+use std::error::Error;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use memmap2::Mmap;
+
+use crate::loader::{LoaderRegistry, LOADER_VERSION};
+use crate::material::Material;
+use crate::model::{Corner, Group, Model};
+use crate::progress::Progress;
+use crate::watcher;
+
+const CACHE_MAGIC: &[u8; 8] = b"OBJVCACH";
+// bump when the layout of the cache file changes
+const CACHE_VERSION: u32 = 2;
+// u32 written for an index which is None
+const NO_INDEX: u32 = u32::MAX;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CacheMode {
+    // read the cache if it is up to date, and write it otherwise
+    Use,
+    // neither read nor write the cache
+    Bypass,
+    // parse the model and write the cache even if it is up to date
+    Rebuild,
+}
+
+// a model with its vertex buffer as uploaded for rendering (position, normal and color)
+pub struct PreparedModel {
+    pub model: Model,
+    pub vertex_buffer: Vec<f32>,
+    pub bounds: Option<([f32; 3], [f32; 3])>,
+    pub from_cache: bool,
+}
+
+impl PreparedModel {
+    pub fn new(mut model: Model) -> PreparedModel {
+        model.create_vertex_normal();
+        PreparedModel {
+            vertex_buffer: model.create_concat_vertex(),
+            bounds: model.bounding_box(),
+            model,
+            from_cache: false,
+        }
+    }
+}
+
+// what a cache was made from. any difference makes it stale
+#[derive(Debug, PartialEq)]
+pub struct CacheKey {
+    pub path: String,
+    pub size: u64,
+    pub modified_secs: u64,
+    pub modified_nanos: u32,
+    pub loader_version: u32,
+}
+
+impl CacheKey {
+    pub fn of(path: &Path) -> Result<CacheKey, Box<dyn Error>> {
+        let metadata = fs::metadata(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let modified = metadata.modified()?.duration_since(UNIX_EPOCH)?;
+        Ok(CacheKey {
+            path: fs::canonicalize(path)?.to_string_lossy().into_owned(),
+            size: metadata.len(),
+            modified_secs: modified.as_secs(),
+            modified_nanos: modified.subsec_nanos(),
+            loader_version: LOADER_VERSION,
+        })
+    }
+}
+
+// size and modification time of a file, None while it does not exist
+type Stamp = Option<(u64, u64, u32)>;
+
+fn stamp(path: &Path) -> Stamp {
+    let metadata = fs::metadata(path).ok()?;
+    let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    Some((metadata.len(), modified.as_secs(), modified.subsec_nanos()))
+}
+
+// the material libraries and textures of the model, whose contents are cached with it
+fn dependencies(key: &CacheKey, model: &Model) -> Vec<PathBuf> {
+    let mut files = watcher::model_files(Path::new(&key.path), model);
+    files.remove(0);
+    files
+}
+
+// $XDG_CACHE_HOME/obj_viewer, ~/.cache/obj_viewer, or a directory in the temp directory
+pub fn default_cache_dir() -> PathBuf {
+    let base = match (env::var_os("XDG_CACHE_HOME"), env::var_os("HOME")) {
+        (Some(dir), _) => PathBuf::from(dir),
+        (None, Some(home)) => Path::new(&home).join(".cache"),
+        (None, None) => env::temp_dir(),
+    };
+    base.join("obj_viewer")
+}
+
+// FNV-1a, which unlike the hasher of the standard library is the same in every build
+fn hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, b| {
+        (hash ^ *b as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+pub fn cache_file(cache_dir: &Path, key: &CacheKey) -> PathBuf {
+    cache_dir.join(format!("{:016x}.cache", hash(key.path.as_bytes())))
+}
+
+// little endian values appended to a buffer
+struct Encoder {
+    buf: Vec<u8>,
+}
+
+impl Encoder {
+    fn u32(&mut self, value: u32) {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u64(&mut self, value: u64) {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn f32(&mut self, value: f32) {
+        self.buf.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn len(&mut self, len: usize) {
+        self.u64(len as u64);
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.len(bytes.len());
+        self.buf.extend_from_slice(bytes);
+    }
+
+    fn string(&mut self, s: &str) {
+        self.bytes(s.as_bytes());
+    }
+
+    fn f32s(&mut self, values: &[f32]) {
+        self.len(values.len());
+        for value in values {
+            self.f32(*value);
+        }
+    }
+
+    fn u32s(&mut self, values: &[u32]) {
+        self.len(values.len());
+        for value in values {
+            self.u32(*value);
+        }
+    }
+
+    fn option<T>(&mut self, value: &Option<T>, write: fn(&mut Encoder, &T)) {
+        match value {
+            Some(value) => {
+                self.buf.push(1);
+                write(self, value);
+            }
+            None => self.buf.push(0),
+        }
+    }
+}
+
+// reads what Encoder wrote. every read fails on truncated data instead of panicking
+struct Decoder<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Decoder<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Box<dyn Error>> {
+        if self.data.len() < len {
+            return Err("cache file is truncated".into());
+        }
+        let (head, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> Result<u8, Box<dyn Error>> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, Box<dyn Error>> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into()?))
+    }
+
+    fn u64(&mut self) -> Result<u64, Box<dyn Error>> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into()?))
+    }
+
+    fn f32(&mut self) -> Result<f32, Box<dyn Error>> {
+        Ok(f32::from_le_bytes(self.take(4)?.try_into()?))
+    }
+
+    fn len(&mut self) -> Result<usize, Box<dyn Error>> {
+        Ok(self.u64()? as usize)
+    }
+
+    fn bytes(&mut self) -> Result<&'a [u8], Box<dyn Error>> {
+        let len = self.len()?;
+        self.take(len)
+    }
+
+    fn string(&mut self) -> Result<String, Box<dyn Error>> {
+        Ok(String::from_utf8(self.bytes()?.to_vec())?)
+    }
+
+    // bytes of a length-prefixed array of values of `size` bytes
+    fn array(&mut self, size: usize) -> Result<&'a [u8], Box<dyn Error>> {
+        let len = self.len()?;
+        let bytes = len.checked_mul(size).ok_or("cache file is invalid")?;
+        self.take(bytes)
+    }
+
+    fn f32s(&mut self) -> Result<Vec<f32>, Box<dyn Error>> {
+        Ok(self
+            .array(4)?
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect())
+    }
+
+    fn u32s(&mut self) -> Result<Vec<u32>, Box<dyn Error>> {
+        Ok(self
+            .array(4)?
+            .chunks_exact(4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect())
+    }
+
+    fn option<T>(
+        &mut self,
+        read: fn(&mut Decoder<'a>) -> Result<T, Box<dyn Error>>,
+    ) -> Result<Option<T>, Box<dyn Error>> {
+        match self.u8()? {
+            0 => Ok(None),
+            1 => Ok(Some(read(self)?)),
+            _ => Err("cache file is invalid".into()),
+        }
+    }
+}
+
+fn write_color(encoder: &mut Encoder, color: &[f32; 3]) {
+    color.iter().for_each(|c| encoder.f32(*c));
+}
+
+fn read_color(decoder: &mut Decoder) -> Result<[f32; 3], Box<dyn Error>> {
+    Ok([decoder.f32()?, decoder.f32()?, decoder.f32()?])
+}
+
+fn write_material(encoder: &mut Encoder, material: &Material) {
+    encoder.string(&material.name);
+    for color in [
+        &material.ambient,
+        &material.diffuse,
+        &material.specular,
+        &material.emissive,
+    ]
+    .iter()
+    {
+        encoder.option(color, write_color);
+    }
+    for value in [
+        &material.shininess,
+        &material.optical_density,
+        &material.dissolve,
+        &material.metallic,
+        &material.roughness,
+    ]
+    .iter()
+    {
+        encoder.option(value, |e, v| e.f32(*v));
+    }
+    encoder.option(&material.illumination, |e, v| e.u32(*v));
+    for map in material.maps().iter() {
+        encoder.option(map, |e, s| e.string(s));
+    }
+    encoder.len(material.map_options.len());
+    for (key, options) in material.map_options.iter() {
+        encoder.string(key);
+        encoder.string(options);
+    }
+}
+
+fn read_material(decoder: &mut Decoder) -> Result<Material, Box<dyn Error>> {
+    let mut material = Material::new(&decoder.string()?);
+    material.ambient = decoder.option(read_color)?;
+    material.diffuse = decoder.option(read_color)?;
+    material.specular = decoder.option(read_color)?;
+    material.emissive = decoder.option(read_color)?;
+    material.shininess = decoder.option(Decoder::f32)?;
+    material.optical_density = decoder.option(Decoder::f32)?;
+    material.dissolve = decoder.option(Decoder::f32)?;
+    material.metallic = decoder.option(Decoder::f32)?;
+    material.roughness = decoder.option(Decoder::f32)?;
+    material.illumination = decoder.option(Decoder::u32)?;
+    for map in material.maps_mut().iter_mut() {
+        **map = decoder.option(Decoder::string)?;
+    }
+    for _ in 0..decoder.len()? {
+        let key = decoder.string()?;
+        material.map_options.push((key, decoder.string()?));
+    }
+    Ok(material)
+}
+
+fn write_key(encoder: &mut Encoder, key: &CacheKey) {
+    encoder.string(&key.path);
+    encoder.u64(key.size);
+    encoder.u64(key.modified_secs);
+    encoder.u32(key.modified_nanos);
+    encoder.u32(key.loader_version);
+}
+
+fn write_stamp(encoder: &mut Encoder, stamp: &(u64, u64, u32)) {
+    encoder.u64(stamp.0);
+    encoder.u64(stamp.1);
+    encoder.u32(stamp.2);
+}
+
+fn read_stamp(decoder: &mut Decoder) -> Result<(u64, u64, u32), Box<dyn Error>> {
+    Ok((decoder.u64()?, decoder.u64()?, decoder.u32()?))
+}
+
+fn read_key(decoder: &mut Decoder) -> Result<CacheKey, Box<dyn Error>> {
+    Ok(CacheKey {
+        path: decoder.string()?,
+        size: decoder.u64()?,
+        modified_secs: decoder.u64()?,
+        modified_nanos: decoder.u32()?,
+        loader_version: decoder.u32()?,
+    })
+}
+
+pub fn encode(key: &CacheKey, prepared: &PreparedModel) -> Vec<u8> {
+    let model = &prepared.model;
+    let mut e = Encoder { buf: Vec::new() };
+    e.buf.extend_from_slice(CACHE_MAGIC);
+    e.u32(CACHE_VERSION);
+    write_key(&mut e, key);
+    let files = dependencies(key, model);
+    e.len(files.len());
+    for file in files.iter() {
+        e.string(&file.to_string_lossy());
+        e.option(&stamp(file), write_stamp);
+    }
+
+    // what the renderer uploads
+    e.f32s(&prepared.vertex_buffer);
+    e.u32s(&model.indices.vertex_indices);
+    e.option(&prepared.bounds, |e, (min, max)| {
+        write_color(e, min);
+        write_color(e, max);
+    });
+
+    // the rest of the model
+    e.f32s(&model.vertex);
+    e.f32s(&model.vertex_tex);
+    e.f32s(&model.vertex_normal);
+    e.f32s(&model.vertex_normal_tmp);
+    e.f32s(&model.vertex_color);
+    e.len(model.vertex_properties.len());
+    for (name, values) in model.vertex_properties.iter() {
+        e.string(name);
+        e.f32s(values);
+    }
+    e.u32s(&model.indices.texture_indices);
+    e.u32s(&model.indices.normal_indices);
+    e.len(model.corners.len());
+    for corner in model.corners.iter() {
+        e.u32(corner.vertex);
+        e.u32(corner.texture.unwrap_or(NO_INDEX));
+        e.u32(corner.normal.unwrap_or(NO_INDEX));
+    }
+    e.u32s(&model.polygon_sizes);
+    e.len(model.groups.len());
+    for group in model.groups.iter() {
+        e.string(&group.name);
+        e.string(&group.object);
+        e.option(&group.material, |e, s| e.string(s));
+        e.u32(group.smoothing_group);
+        for value in [
+            group.face_start,
+            group.face_count,
+            group.index_start,
+            group.index_count,
+        ]
+        .iter()
+        {
+            e.len(*value);
+        }
+    }
+    e.len(model.material_libs.len());
+    model.material_libs.iter().for_each(|lib| e.string(lib));
+    e.len(model.materials.len());
+    model
+        .materials
+        .iter()
+        .for_each(|material| write_material(&mut e, material));
+    e.len(model.embedded_textures.len());
+    for (name, bytes) in model.embedded_textures.iter() {
+        e.string(name);
+        e.bytes(bytes);
+    }
+    e.buf
+}
+
+fn optional_index(index: u32) -> Option<u32> {
+    if index == NO_INDEX {
+        None
+    } else {
+        Some(index)
+    }
+}
+
+// None if the data was made by another version or from another file, or if a file the
+// model refers to has changed since
+pub fn decode(data: &[u8], key: &CacheKey) -> Result<Option<PreparedModel>, Box<dyn Error>> {
+    let mut d = Decoder { data };
+    if d.take(CACHE_MAGIC.len())? != CACHE_MAGIC {
+        return Err("not a model cache file".into());
+    }
+    if d.u32()? != CACHE_VERSION || read_key(&mut d)? != *key {
+        return Ok(None);
+    }
+    for _ in 0..d.len()? {
+        let file = d.string()?;
+        if d.option(read_stamp)? != stamp(Path::new(&file)) {
+            return Ok(None);
+        }
+    }
+
+    let vertex_buffer = d.f32s()?;
+    let mut model = Model::new();
+    model.indices.vertex_indices = d.u32s()?;
+    let bounds = d.option(|d| Ok((read_color(d)?, read_color(d)?)))?;
+
+    model.vertex = d.f32s()?;
+    model.vertex_tex = d.f32s()?;
+    model.vertex_normal = d.f32s()?;
+    model.vertex_normal_tmp = d.f32s()?;
+    model.vertex_color = d.f32s()?;
+    for _ in 0..d.len()? {
+        let name = d.string()?;
+        model.vertex_properties.push((name, d.f32s()?));
+    }
+    model.indices.texture_indices = d.u32s()?;
+    model.indices.normal_indices = d.u32s()?;
+    let corner_num = d.len()?;
+    let corner_bytes = d.take(corner_num.checked_mul(12).ok_or("cache file is invalid")?)?;
+    model.corners = corner_bytes
+        .chunks_exact(12)
+        .map(|b| {
+            let index = |i: usize| u32::from_le_bytes([b[i], b[i + 1], b[i + 2], b[i + 3]]);
+            Corner {
+                vertex: index(0),
+                texture: optional_index(index(4)),
+                normal: optional_index(index(8)),
+            }
+        })
+        .collect();
+    model.polygon_sizes = d.u32s()?;
+    for _ in 0..d.len()? {
+        model.groups.push(Group {
+            name: d.string()?,
+            object: d.string()?,
+            material: d.option(Decoder::string)?,
+            smoothing_group: d.u32()?,
+            face_start: d.len()?,
+            face_count: d.len()?,
+            index_start: d.len()?,
+            index_count: d.len()?,
+        });
+    }
+    for _ in 0..d.len()? {
+        model.material_libs.push(d.string()?);
+    }
+    for _ in 0..d.len()? {
+        model.materials.push(read_material(&mut d)?);
+    }
+    for _ in 0..d.len()? {
+        let name = d.string()?;
+        model.embedded_textures.push((name, d.bytes()?.to_vec()));
+    }
+    if !d.data.is_empty() {
+        return Err("cache file has trailing data".into());
+    }
+    Ok(Some(PreparedModel {
+        model,
+        vertex_buffer,
+        bounds,
+        from_cache: true,
+    }))
+}
+
+// the cached model, or None if there is no cache or it is stale
+pub fn read_cache(path: &Path, key: &CacheKey) -> Result<Option<PreparedModel>, Box<dyn Error>> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(_) => return Ok(None),
+    };
+    // caches are replaced by renaming, so a mapped file is never written to
+    let map = unsafe { Mmap::map(&file)? };
+    decode(&map, key)
+}
+
+// written to a temporary file first, so that a cache is either complete or missing
+pub fn write_cache(
+    path: &Path,
+    key: &CacheKey,
+    prepared: &PreparedModel,
+) -> Result<(), Box<dyn Error>> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let tmp_path = path.with_extension(format!("tmp{}", std::process::id()));
+    fs::write(&tmp_path, encode(key, prepared))?;
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+// load a model file through its cache in cache_dir. a cache which cannot be read or
+// written is reported and loading goes on without it
+pub fn load(
+    registry: &LoaderRegistry,
+    path: &Path,
+    cache_dir: &Path,
+    mode: CacheMode,
+    progress: &Progress,
+) -> Result<PreparedModel, Box<dyn Error>> {
+    if mode == CacheMode::Bypass {
+        return Ok(PreparedModel::new(
+            registry.load_with_progress(path, progress)?,
+        ));
+    }
+    let key = CacheKey::of(path)?;
+    let cache_path = cache_file(cache_dir, &key);
+    if mode == CacheMode::Use {
+        match read_cache(&cache_path, &key) {
+            Ok(Some(prepared)) => {
+                progress.set_total_bytes(key.size);
+                progress.add_bytes(key.size);
+                progress.add_faces(prepared.model.polygon_sizes.len() as u64);
+                return Ok(prepared);
+            }
+            Ok(None) => {}
+            Err(e) => println!("Cache error: {}: {}", cache_path.display(), e),
+        }
+    }
+    let prepared = PreparedModel::new(registry.load_with_progress(path, progress)?);
+    if let Err(e) = write_cache(&cache_path, &key, &prepared) {
+        println!("Cache error: {}: {}", cache_path.display(), e);
+    }
+    Ok(prepared)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_key() -> CacheKey {
+        CacheKey {
+            path: "/models/box.obj".to_string(),
+            size: 100,
+            modified_secs: 1_600_000_000,
+            modified_nanos: 5,
+            loader_version: LOADER_VERSION,
+        }
+    }
+
+    #[test]
+    fn test_ok_encode() {
+        let mut model = Model::new();
+        model
+            .load_from_str(
+                "mtllib a.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0\ng side\nusemtl red\nf 1/1 2/1 3/1\nf 1 3 2\n",
+                &mut |name| match name {
+                    "a.mtl" => Ok(b"newmtl red\nKd 1 0 0\nNs 10\nmap_Kd -s 2 2 red.png\n".to_vec()),
+                    _ => Err(std::io::ErrorKind::NotFound.into()),
+                },
+            )
+            .unwrap();
+        model.vertex_color = vec![1.0; 9];
+        model.vertex_properties = vec![("quality".to_string(), vec![0.5, 0.25, 1.0])];
+        model.embedded_textures = vec![("red.png".to_string(), b"png".to_vec())];
+        let prepared = PreparedModel::new(model);
+
+        let key = test_key();
+        let data = encode(&key, &prepared);
+        let decoded = decode(&data, &key).unwrap().unwrap();
+        assert!(decoded.from_cache);
+        assert_eq!(prepared.vertex_buffer, decoded.vertex_buffer);
+        assert_eq!(prepared.bounds, decoded.bounds);
+        let (a, b) = (&prepared.model, &decoded.model);
+        assert_eq!(a.vertex, b.vertex);
+        assert_eq!(a.vertex_tex, b.vertex_tex);
+        assert_eq!(a.vertex_normal, b.vertex_normal);
+        assert_eq!(a.vertex_color, b.vertex_color);
+        assert_eq!(a.vertex_properties, b.vertex_properties);
+        assert_eq!(a.indices.vertex_indices, b.indices.vertex_indices);
+        assert_eq!(a.indices.texture_indices, b.indices.texture_indices);
+        assert_eq!(a.corners, b.corners);
+        assert_eq!(a.polygon_sizes, b.polygon_sizes);
+        assert_eq!(a.groups, b.groups);
+        assert_eq!(a.material_libs, b.material_libs);
+        assert_eq!(a.materials, b.materials);
+        assert_eq!(a.embedded_textures, b.embedded_textures);
+
+        // another file or loader version makes the cache stale
+        let mut other = test_key();
+        other.modified_nanos += 1;
+        assert!(decode(&data, &other).unwrap().is_none());
+        other = test_key();
+        other.loader_version += 1;
+        assert!(decode(&data, &other).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_ng_decode() {
+        let key = test_key();
+        let data = encode(&key, &PreparedModel::new(Model::new()));
+        assert!(decode(b"OBJVCAC", &key).is_err());
+        assert!(decode(b"NOTCACHE\x01\x00\x00\x00", &key).is_err());
+        assert!(decode(&data[..data.len() - 1], &key).is_err());
+        let mut longer = data.clone();
+        longer.push(0);
+        assert!(decode(&longer, &key).is_err());
+    }
+
+    #[test]
+    fn test_ok_load() {
+        let dir = env::temp_dir().join("obj_viewer_test_cache_load");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("tri.obj");
+        fs::write(&path, "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n").unwrap();
+        let cache_dir = dir.join("cache");
+        let registry = LoaderRegistry::new();
+        let open = |mode| load(&registry, &path, &cache_dir, mode, &Progress::new()).unwrap();
+
+        assert!(!open(CacheMode::Bypass).from_cache);
+        assert!(!cache_dir.exists());
+        assert!(!open(CacheMode::Use).from_cache);
+        let cached = open(CacheMode::Use);
+        assert!(cached.from_cache);
+        assert_eq!(vec![3], cached.model.polygon_sizes);
+        assert!(!open(CacheMode::Rebuild).from_cache);
+
+        // a changed file is parsed again
+        fs::write(&path, "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\nf 3 2 1\n").unwrap();
+        let changed = open(CacheMode::Use);
+        assert!(!changed.from_cache);
+        assert_eq!(vec![3, 3], changed.model.polygon_sizes);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_ok_load_changed_material() {
+        let dir = env::temp_dir().join("obj_viewer_test_cache_material");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("tri.obj");
+        fs::write(
+            &path,
+            "mtllib tri.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\nusemtl red\nf 1 2 3\n",
+        )
+        .unwrap();
+        fs::write(dir.join("tri.mtl"), "newmtl red\nKd 1 0 0\n").unwrap();
+        let cache_dir = dir.join("cache");
+        let registry = LoaderRegistry::new();
+        let open = || {
+            load(
+                &registry,
+                &path,
+                &cache_dir,
+                CacheMode::Use,
+                &Progress::new(),
+            )
+            .unwrap()
+        };
+
+        assert!(!open().from_cache);
+        assert!(open().from_cache);
+        // the model file stays the same, and the material comes from the new library
+        fs::write(dir.join("tri.mtl"), "newmtl red\nKd 0.5 0 0\n").unwrap();
+        let changed = open();
+        assert!(!changed.from_cache);
+        assert_eq!(Some([0.5, 0.0, 0.0]), changed.model.materials[0].diffuse);
+        assert!(open().from_cache);
+        // so does a texture which appears
+        fs::write(dir.join("tri.mtl"), "newmtl red\nmap_Kd red.png\n").unwrap();
+        assert!(!open().from_cache);
+        assert!(open().from_cache);
+        fs::write(dir.join("red.png"), b"png").unwrap();
+        assert!(!open().from_cache);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
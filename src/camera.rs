@@ -7,10 +7,15 @@ type Matrix4 = cgmath::Matrix4<f32>;
 
 use cgmath::perspective;
 
+const FOV_Y_DEG: f32 = 45.0;
+
 pub struct CameraState {
     aspect_ratio: f32,
     pub position: (f32, f32, f32),
+    pub target: (f32, f32, f32),
     direction: (f32, f32, f32),
+    znear: f32,
+    zfar: f32,
 }
 
 impl CameraState {
@@ -18,7 +23,10 @@ impl CameraState {
         CameraState {
             aspect_ratio: window_size_wh.0 as f32 / window_size_wh.1 as f32,
             position: (5.0, -5.0, 5.0),
+            target: (0.0, 1.0, 0.0),
             direction: (0.0, 1.0, 0.0),
+            znear: 0.1,
+            zfar: 1024.0,
         }
     }
 
@@ -30,11 +38,58 @@ impl CameraState {
         self.direction = dir;
     }
 
+    pub fn set_aspect_ratio(&mut self, window_size_wh: (u32, u32)) {
+        self.aspect_ratio = window_size_wh.0 as f32 / window_size_wh.1.max(1) as f32;
+    }
+
+    // place the camera so that the bounding box (min, max) fills the view.
+    // yaw rotates around the y axis and pitch tilts up from the xz plane, both in degrees.
+    pub fn frame_bounds(&mut self, min: [f32; 3], max: [f32; 3], yaw_deg: f32, pitch_deg: f32) {
+        let center = [
+            (min[0] + max[0]) * 0.5,
+            (min[1] + max[1]) * 0.5,
+            (min[2] + max[2]) * 0.5,
+        ];
+        let size = [max[0] - min[0], max[1] - min[1], max[2] - min[2]];
+        let radius =
+            (0.5 * (size[0] * size[0] + size[1] * size[1] + size[2] * size[2]).sqrt()).max(1.0e-3);
+
+        // distance at which the bounding sphere fits into the narrower field of view
+        let half_fov_y = (FOV_Y_DEG * 0.5).to_radians();
+        let half_fov_x = (half_fov_y.tan() * self.aspect_ratio).atan();
+        let distance = radius / half_fov_y.min(half_fov_x).sin();
+
+        let (yaw, pitch) = (yaw_deg.to_radians(), pitch_deg.to_radians());
+        let dir = (
+            pitch.cos() * yaw.sin(),
+            pitch.sin(),
+            pitch.cos() * yaw.cos(),
+        );
+        self.target = (center[0], center[1], center[2]);
+        self.position = (
+            center[0] + dir.0 * distance,
+            center[1] + dir.1 * distance,
+            center[2] + dir.2 * distance,
+        );
+        self.znear = (distance - radius).max(distance * 1.0e-3);
+        self.zfar = distance + radius;
+    }
+
+    pub fn get_view(&self) -> Matrix4 {
+        Matrix4::look_at_rh(
+            Point3::new(self.position.0, self.position.1, self.position.2),
+            Point3::new(self.target.0, self.target.1, self.target.2),
+            Vector3::new(0.0, 1.0, 0.0),
+        )
+    }
+
     pub fn get_perspective(&self) -> Matrix4 {
-        let zfar = 1024.0;
-        let znear = 0.1;
-        let projection_matrix: Matrix4 =
-            perspective(cgmath::Deg(45.0f32), self.aspect_ratio, znear, zfar);
+        let projection_matrix: Matrix4 = perspective(
+            cgmath::Deg(FOV_Y_DEG),
+            self.aspect_ratio,
+            self.znear,
+            self.zfar,
+        );
         projection_matrix
     }
 }
@@ -0,0 +1,458 @@
+use std::path::PathBuf;
+
+use crate::antialias::SAMPLE_COUNTS;
+use crate::cache::CacheMode;
+use crate::obj_writer::{IndexStyle, ObjWriteOptions};
+use crate::stats::Check;
+
+pub const USAGE: &str = "\
+usage:
+  obj_viewer [view] <model.obj|.stl|.ply|.gltf|.glb|.off|.xyz|.gz|.zip> [options]
+      --no-cache           neither read nor write the cache of parsed models
+      --rebuild-cache      parse the model and rewrite its cache
+      --msaa <samples>     multisampling: 0, 2, 4, 8 or 16 samples (default: 4)
+      --fxaa               smooth edges with an FXAA pass
+  obj_viewer thumbnail <directory> [options]
+      --size <pixels>      thumbnail width and height (default: 256)
+      --yaw <degrees>      rotation around the y axis (default: 45)
+      --pitch <degrees>    elevation above the xz plane (default: 30)
+      --output <dir>       write thumbnails into <dir> instead of next to each model
+      --msaa <samples>     multisampling: 0, 2, 4, 8 or 16 samples (default: 4)
+      --fxaa               smooth edges with an FXAA pass
+  obj_viewer info <model.obj> [--json]
+  obj_viewer validate <model.obj> [--json] [--fail-on <checks>]
+      --json               print the report as JSON
+      --fail-on <checks>   comma separated checks that make validation fail
+                           (default: degenerate-faces,out-of-range-indices)
+                           checks: unreferenced-vertices, degenerate-faces,
+                                   out-of-range-indices, missing-normals
+  obj_viewer convert <input> <output> [options]
+      --precision <digits>         significant digits (default: as many as the values need)
+      --index-style <style>        absolute or relative OBJ indices (default: absolute)
+      --ascii                      write ASCII instead of binary STL or PLY
+      --big-endian                 write big endian instead of little endian binary PLY
+      --embed-textures             put textures into .gltf/.glb instead of referencing them
+  obj_viewer bench <model.obj> [--runs <n>]
+      --runs <n>           loads per loader, the fastest one is reported (default: 3)";
+
+pub struct ViewOptions {
+    pub path: PathBuf,
+    pub cache: CacheMode,
+    // samples of MSAA, 0 for none
+    pub samples: u32,
+    pub fxaa: bool,
+}
+
+pub struct ThumbnailOptions {
+    pub root: PathBuf,
+    pub size: u32,
+    pub yaw: f32,
+    pub pitch: f32,
+    pub output_dir: Option<PathBuf>,
+    pub samples: u32,
+    pub fxaa: bool,
+}
+
+impl ThumbnailOptions {
+    pub fn new(root: PathBuf) -> ThumbnailOptions {
+        ThumbnailOptions {
+            root,
+            size: 256,
+            yaw: 45.0,
+            pitch: 30.0,
+            output_dir: None,
+            samples: 4,
+            fxaa: false,
+        }
+    }
+}
+
+pub struct ReportOptions {
+    pub path: PathBuf,
+    pub json: bool,
+    // checks to validate, None for the info subcommand
+    pub fail_on: Option<Vec<Check>>,
+}
+
+pub struct ConvertOptions {
+    pub input: PathBuf,
+    pub output: PathBuf,
+    pub obj: ObjWriteOptions,
+    // STL and PLY are written as binary unless ascii is set
+    pub ascii: bool,
+    pub big_endian: bool,
+    pub embed_textures: bool,
+}
+
+pub struct BenchOptions {
+    pub path: PathBuf,
+    pub runs: u32,
+}
+
+pub enum Command {
+    View(ViewOptions),
+    Thumbnail(ThumbnailOptions),
+    Report(ReportOptions),
+    Convert(ConvertOptions),
+    Bench(BenchOptions),
+    Help,
+}
+
+// value following an option such as "--size 128"
+fn option_value<T: std::str::FromStr>(
+    args: &mut dyn Iterator<Item = String>,
+    name: &str,
+) -> Result<T, String> {
+    let value = args
+        .next()
+        .ok_or_else(|| format!("missing value for {}", name))?;
+    value
+        .parse::<T>()
+        .map_err(|_| format!("invalid value for {}: {}", name, value))
+}
+
+// sample count of "--msaa", which is one of SAMPLE_COUNTS
+fn samples_value(args: &mut dyn Iterator<Item = String>, name: &str) -> Result<u32, String> {
+    let samples = option_value(args, name)?;
+    if !SAMPLE_COUNTS.contains(&samples) {
+        return Err(format!("{} must be 0, 2, 4, 8 or 16", name));
+    }
+    Ok(samples)
+}
+
+fn parse_view(
+    path: Option<String>,
+    args: &mut dyn Iterator<Item = String>,
+) -> Result<Command, String> {
+    let mut path = path.map(PathBuf::from);
+    let mut cache = CacheMode::Use;
+    let mut samples = 4;
+    let mut fxaa = false;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--no-cache" => cache = CacheMode::Bypass,
+            "--rebuild-cache" => cache = CacheMode::Rebuild,
+            "--msaa" => samples = samples_value(args, &arg)?,
+            "--fxaa" => fxaa = true,
+            _ if arg.starts_with("--") => return Err(format!("unknown option: {}", arg)),
+            _ if path.is_none() => path = Some(PathBuf::from(arg)),
+            _ => return Err(format!("unexpected argument: {}", arg)),
+        }
+    }
+    Ok(Command::View(ViewOptions {
+        path: path.ok_or("missing model path")?,
+        cache,
+        samples,
+        fxaa,
+    }))
+}
+
+fn parse_thumbnail(args: &mut dyn Iterator<Item = String>) -> Result<Command, String> {
+    let mut root = None;
+    let mut options = ThumbnailOptions::new(PathBuf::new());
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--size" => options.size = option_value(args, &arg)?,
+            "--yaw" => options.yaw = option_value(args, &arg)?,
+            "--pitch" => options.pitch = option_value(args, &arg)?,
+            "--output" => options.output_dir = Some(option_value(args, &arg)?),
+            "--msaa" => options.samples = samples_value(args, &arg)?,
+            "--fxaa" => options.fxaa = true,
+            _ if arg.starts_with("--") => return Err(format!("unknown option: {}", arg)),
+            _ if root.is_none() => root = Some(PathBuf::from(arg)),
+            _ => return Err(format!("unexpected argument: {}", arg)),
+        }
+    }
+    if options.size == 0 {
+        return Err("--size must be greater than 0".to_string());
+    }
+    if options.pitch.abs() >= 90.0 {
+        return Err("--pitch must be between -90 and 90 degrees".to_string());
+    }
+    options.root = root.ok_or("missing directory")?;
+    Ok(Command::Thumbnail(options))
+}
+
+fn parse_checks(list: &str) -> Result<Vec<Check>, String> {
+    list.split(',')
+        .filter(|name| !name.is_empty())
+        .map(|name| Check::from_name(name).ok_or(format!("unknown check: {}", name)))
+        .collect()
+}
+
+fn parse_report(args: &mut dyn Iterator<Item = String>, validate: bool) -> Result<Command, String> {
+    let mut path = None;
+    let mut json = false;
+    let mut fail_on = if validate {
+        Some(Check::DEFAULT.to_vec())
+    } else {
+        None
+    };
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--json" => json = true,
+            "--fail-on" if validate => {
+                let list: String = option_value(args, &arg)?;
+                fail_on = Some(parse_checks(&list)?);
+            }
+            _ if arg.starts_with("--") => return Err(format!("unknown option: {}", arg)),
+            _ if path.is_none() => path = Some(PathBuf::from(arg)),
+            _ => return Err(format!("unexpected argument: {}", arg)),
+        }
+    }
+    Ok(Command::Report(ReportOptions {
+        path: path.ok_or("missing model path")?,
+        json,
+        fail_on,
+    }))
+}
+
+fn parse_convert(args: &mut dyn Iterator<Item = String>) -> Result<Command, String> {
+    let mut paths = Vec::new();
+    let mut obj = ObjWriteOptions::new();
+    let mut ascii = false;
+    let mut big_endian = false;
+    let mut embed_textures = false;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--ascii" => ascii = true,
+            "--big-endian" => big_endian = true,
+            "--embed-textures" => embed_textures = true,
+            "--precision" => obj.precision = Some(option_value(args, &arg)?),
+            "--index-style" => {
+                let style: String = option_value(args, &arg)?;
+                obj.index_style = match style.as_str() {
+                    "absolute" => IndexStyle::Absolute,
+                    "relative" => IndexStyle::Relative,
+                    _ => return Err(format!("invalid value for {}: {}", arg, style)),
+                };
+            }
+            _ if arg.starts_with("--") => return Err(format!("unknown option: {}", arg)),
+            _ if paths.len() < 2 => paths.push(PathBuf::from(arg)),
+            _ => return Err(format!("unexpected argument: {}", arg)),
+        }
+    }
+    if obj.precision == Some(0) {
+        return Err("--precision must be greater than 0".to_string());
+    }
+    let output = paths.pop().ok_or("missing input and output paths")?;
+    let input = paths.pop().ok_or("missing output path")?;
+    Ok(Command::Convert(ConvertOptions {
+        input,
+        output,
+        obj,
+        ascii,
+        big_endian,
+        embed_textures,
+    }))
+}
+
+fn parse_bench(args: &mut dyn Iterator<Item = String>) -> Result<Command, String> {
+    let mut path = None;
+    let mut runs = 3;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--runs" => runs = option_value(args, &arg)?,
+            _ if arg.starts_with("--") => return Err(format!("unknown option: {}", arg)),
+            _ if path.is_none() => path = Some(PathBuf::from(arg)),
+            _ => return Err(format!("unexpected argument: {}", arg)),
+        }
+    }
+    if runs == 0 {
+        return Err("--runs must be greater than 0".to_string());
+    }
+    Ok(Command::Bench(BenchOptions {
+        path: path.ok_or("missing model path")?,
+        runs,
+    }))
+}
+
+// parse the command line arguments without the program name
+pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Command, String> {
+    let mut args = args.into_iter();
+    let first = match args.next() {
+        Some(arg) => arg,
+        None => return Ok(Command::Help),
+    };
+    match first.as_str() {
+        "-h" | "--help" | "help" => Ok(Command::Help),
+        "thumbnail" => parse_thumbnail(&mut args),
+        "info" => parse_report(&mut args, false),
+        "validate" => parse_report(&mut args, true),
+        "convert" => parse_convert(&mut args),
+        "bench" => parse_bench(&mut args),
+        "view" => parse_view(None, &mut args),
+        _ if first.starts_with("--") => Err(format!("unknown option: {}", first)),
+        _ => parse_view(Some(first), &mut args),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(s: &str) -> Vec<String> {
+        s.split_whitespace().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_ok_parse_view() {
+        match parse(args("teapot.obj")) {
+            Ok(Command::View(options)) => {
+                assert_eq!(PathBuf::from("teapot.obj"), options.path);
+                assert!(matches!(options.cache, CacheMode::Use));
+                assert_eq!(4, options.samples);
+                assert!(!options.fxaa);
+            }
+            _ => panic!("expected view command"),
+        }
+        match parse(args("view teapot.obj --rebuild-cache")) {
+            Ok(Command::View(options)) => {
+                assert_eq!(PathBuf::from("teapot.obj"), options.path);
+                assert!(matches!(options.cache, CacheMode::Rebuild));
+            }
+            _ => panic!("expected view command"),
+        }
+        match parse(args("--no-cache teapot.obj")) {
+            Ok(_) => panic!("options come after the path"),
+            Err(e) => assert_eq!("unknown option: --no-cache", e),
+        }
+        assert!(matches!(
+            parse(args("view --no-cache teapot.obj")),
+            Ok(Command::View(ViewOptions {
+                cache: CacheMode::Bypass,
+                ..
+            }))
+        ));
+        match parse(args("teapot.obj --msaa 0 --fxaa")) {
+            Ok(Command::View(options)) => {
+                assert_eq!(0, options.samples);
+                assert!(options.fxaa);
+            }
+            _ => panic!("expected view command"),
+        }
+        assert!(parse(args("teapot.obj --msaa 3")).is_err());
+        assert!(parse(args("teapot.obj --msaa")).is_err());
+        assert!(parse(args("view")).is_err());
+        assert!(parse(args("teapot.obj --fast")).is_err());
+        assert!(matches!(parse(args("")), Ok(Command::Help)));
+    }
+
+    #[test]
+    fn test_ok_parse_thumbnail() {
+        let command = parse(args(
+            "thumbnail assets --size 128 --yaw -30 --pitch 10 --output thumbs --msaa 8 --fxaa",
+        ));
+        match command {
+            Ok(Command::Thumbnail(options)) => {
+                assert_eq!(PathBuf::from("assets"), options.root);
+                assert_eq!(128, options.size);
+                assert_eq!(-30.0, options.yaw);
+                assert_eq!(10.0, options.pitch);
+                assert_eq!(Some(PathBuf::from("thumbs")), options.output_dir);
+                assert_eq!(8, options.samples);
+                assert!(options.fxaa);
+            }
+            _ => panic!("expected thumbnail command"),
+        }
+    }
+
+    #[test]
+    fn test_ng_parse_thumbnail() {
+        assert!(parse(args("thumbnail")).is_err());
+        assert!(parse(args("thumbnail assets --size")).is_err());
+        assert!(parse(args("thumbnail assets --size big")).is_err());
+        assert!(parse(args("thumbnail assets --size 0")).is_err());
+        assert!(parse(args("thumbnail assets --pitch 90")).is_err());
+        assert!(parse(args("thumbnail assets --msaa 32")).is_err());
+        assert!(parse(args("thumbnail assets --unknown")).is_err());
+        assert!(parse(args("thumbnail assets other")).is_err());
+    }
+
+    #[test]
+    fn test_ok_parse_report() {
+        match parse(args("info teapot.obj --json")) {
+            Ok(Command::Report(options)) => {
+                assert_eq!(PathBuf::from("teapot.obj"), options.path);
+                assert!(options.json);
+                assert!(options.fail_on.is_none());
+            }
+            _ => panic!("expected report command"),
+        }
+        match parse(args("validate teapot.obj")) {
+            Ok(Command::Report(options)) => {
+                assert!(!options.json);
+                assert_eq!(Some(Check::DEFAULT.to_vec()), options.fail_on);
+            }
+            _ => panic!("expected report command"),
+        }
+        match parse(args(
+            "validate teapot.obj --fail-on missing-normals,unreferenced-vertices",
+        )) {
+            Ok(Command::Report(options)) => {
+                let expected = vec![Check::MissingNormals, Check::UnreferencedVertices];
+                assert_eq!(Some(expected), options.fail_on);
+            }
+            _ => panic!("expected report command"),
+        }
+    }
+
+    #[test]
+    fn test_ok_parse_convert() {
+        match parse(args(
+            "convert in.obj out.obj --precision 3 --index-style relative",
+        )) {
+            Ok(Command::Convert(options)) => {
+                assert_eq!(PathBuf::from("in.obj"), options.input);
+                assert_eq!(PathBuf::from("out.obj"), options.output);
+                assert_eq!(Some(3), options.obj.precision);
+                assert_eq!(IndexStyle::Relative, options.obj.index_style);
+                assert!(!options.ascii);
+                assert!(!options.big_endian);
+            }
+            _ => panic!("expected convert command"),
+        }
+        match parse(args("convert in.obj out.stl --ascii")) {
+            Ok(Command::Convert(options)) => assert!(options.ascii),
+            _ => panic!("expected convert command"),
+        }
+        match parse(args("convert in.obj out.ply --big-endian")) {
+            Ok(Command::Convert(options)) => assert!(options.big_endian),
+            _ => panic!("expected convert command"),
+        }
+        match parse(args("convert in.obj out.glb --embed-textures")) {
+            Ok(Command::Convert(options)) => assert!(options.embed_textures),
+            _ => panic!("expected convert command"),
+        }
+    }
+
+    #[test]
+    fn test_ng_parse_convert() {
+        assert!(parse(args("convert")).is_err());
+        assert!(parse(args("convert in.obj")).is_err());
+        assert!(parse(args("convert in.obj out.obj other.obj")).is_err());
+        assert!(parse(args("convert in.obj out.obj --index-style sideways")).is_err());
+        assert!(parse(args("convert in.obj out.obj --precision 0")).is_err());
+    }
+
+    #[test]
+    fn test_ok_parse_bench() {
+        match parse(args("bench scan.obj --runs 5")) {
+            Ok(Command::Bench(options)) => {
+                assert_eq!(PathBuf::from("scan.obj"), options.path);
+                assert_eq!(5, options.runs);
+            }
+            _ => panic!("expected bench command"),
+        }
+        assert!(parse(args("bench")).is_err());
+        assert!(parse(args("bench scan.obj --runs 0")).is_err());
+    }
+
+    #[test]
+    fn test_ng_parse_report() {
+        assert!(parse(args("info")).is_err());
+        assert!(parse(args("info teapot.obj --fail-on missing-normals")).is_err());
+        assert!(parse(args("validate teapot.obj --fail-on unknown")).is_err());
+        assert!(parse(args("validate teapot.obj --fail-on")).is_err());
+    }
+}
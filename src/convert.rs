@@ -0,0 +1,66 @@
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+use crate::cli::ConvertOptions;
+use crate::gltf::{self, GltfWriteOptions};
+use crate::loader::LoaderRegistry;
+use crate::model::Model;
+use crate::obj_writer::{self, ObjWriteOptions};
+use crate::ply;
+use crate::stl;
+
+fn extension(path: &Path) -> String {
+    path.extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
+// the directory the texture paths of the input are relative to
+fn input_dir(options: &ConvertOptions) -> PathBuf {
+    options
+        .input
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_default()
+}
+
+pub fn save_model(
+    path: &Path,
+    model: &Model,
+    options: &ConvertOptions,
+) -> Result<(), Box<dyn Error>> {
+    match extension(path).as_str() {
+        "obj" => {
+            let obj_options = ObjWriteOptions {
+                texture_dir: input_dir(options),
+                ..options.obj.clone()
+            };
+            obj_writer::save_obj(path, model, &obj_options)
+        }
+        "stl" => stl::save_stl(path, model, options.ascii),
+        "ply" => {
+            let format = if options.ascii {
+                ply::Format::Ascii
+            } else if options.big_endian {
+                ply::Format::BinaryBigEndian
+            } else {
+                ply::Format::BinaryLittleEndian
+            };
+            ply::save_ply(path, model, format)
+        }
+        "gltf" | "glb" => {
+            let gltf_options = GltfWriteOptions {
+                embed_textures: options.embed_textures,
+                texture_dir: input_dir(options),
+            };
+            gltf::save_gltf(path, model, &gltf_options)
+        }
+        ext => Err(format!("unsupported output format: {}", ext).into()),
+    }
+}
+
+// convert options.input to the format given by the extension of options.output
+pub fn run(options: &ConvertOptions) -> Result<(), Box<dyn Error>> {
+    let model = LoaderRegistry::new().load(&options.input)?;
+    save_model(&options.output, &model, options)
+}
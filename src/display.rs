@@ -1,10 +1,37 @@
+use crate::shading::ShadingMode;
+use crate::wireframe::WireframeMode;
+
 pub struct DisplayState {
     pub window_width: u32,
     pub window_height: u32,
     pub is_enabled_depth_test: bool,
     pub is_enabled_blend: bool,
-    pub is_enabled_wireframe: bool,
+    // samples of MSAA, 0 for none
+    pub msaa_samples: u32,
+    // smooth the edges of the scene with a post-process pass
+    pub is_enabled_fxaa: bool,
+    pub wireframe_mode: WireframeMode,
+    // the edges of the polygons as loaded rather than of the triangles they are split into
+    pub is_enabled_polygon_edges: bool,
+    pub wire_color: [f32; 3],
+    // in pixels
+    pub wire_width: f32,
     pub is_enabled_culling: bool,
+    // draw the vertices as points instead of the faces
+    pub is_enabled_points: bool,
+    pub point_size: f32,
+    // lines drawn over the model
+    pub is_enabled_axes: bool,
+    pub is_enabled_grid: bool,
+    pub grid_cells: i32,
+    pub is_enabled_bounding_box: bool,
+    pub is_enabled_vertex_normals: bool,
+    pub is_enabled_face_normals: bool,
+    // length of the normal lines as a share of the model size
+    pub normal_length: f32,
+    pub shading_mode: ShadingMode,
+    // index of the image of the matcap mode
+    pub matcap: usize,
 }
 
 impl DisplayState {
@@ -14,8 +41,24 @@ impl DisplayState {
             window_height: window_size_wh.1,
             is_enabled_depth_test: false,
             is_enabled_blend: false,
-            is_enabled_wireframe: false,
+            msaa_samples: 0,
+            is_enabled_fxaa: false,
+            wireframe_mode: WireframeMode::default(),
+            is_enabled_polygon_edges: true,
+            wire_color: [0.1, 0.1, 0.1],
+            wire_width: 1.0,
             is_enabled_culling: false,
+            is_enabled_points: false,
+            point_size: 3.0,
+            is_enabled_axes: true,
+            is_enabled_grid: false,
+            grid_cells: 10,
+            is_enabled_bounding_box: false,
+            is_enabled_vertex_normals: false,
+            is_enabled_face_normals: false,
+            normal_length: 0.02,
+            shading_mode: ShadingMode::default(),
+            matcap: 0,
         }
     }
     pub fn set_window_size(&mut self,window_size:&[f32]){
@@ -36,7 +79,14 @@ impl DisplayState {
             gl::Disable(gl::BLEND);
         }
 
-        if self.is_enabled_wireframe {
+        // a multisampled window is drawn without its samples when MSAA is off
+        if self.msaa_samples > 0 {
+            gl::Enable(gl::MULTISAMPLE);
+        } else {
+            gl::Disable(gl::MULTISAMPLE);
+        }
+
+        if self.wireframe_mode == WireframeMode::Lines {
             gl::PolygonMode(gl::FRONT_AND_BACK, gl::LINE);
         } else {
             gl::PolygonMode(gl::FRONT_AND_BACK, gl::FILL);
@@ -47,5 +97,7 @@ impl DisplayState {
         } else {
             gl::Disable(gl::CULL_FACE);
         }
+
+        gl::PointSize(self.point_size);
     }
 }
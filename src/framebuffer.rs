@@ -0,0 +1,204 @@
+use gl::types::{GLint, GLsizei, GLuint};
+use std::os::raw::c_void;
+use std::ptr;
+
+// offscreen render target with a RGBA8 color buffer and a depth buffer. the color buffer is a
+// texture when a later pass reads it, and a multisampled renderbuffer for MSAA
+pub struct Framebuffer {
+    fbo: GLuint,
+    color_rbo: GLuint,
+    color_texture: GLuint,
+    depth_rbo: GLuint,
+    pub width: u32,
+    pub height: u32,
+    // 0 without multisampling
+    pub samples: u32,
+}
+
+impl Framebuffer {
+    pub fn new(width: u32, height: u32) -> Result<Framebuffer, String> {
+        Framebuffer::create(width, height, 0, false)
+    }
+
+    // a framebuffer which is resolved into another one before it is read
+    pub fn multisample(width: u32, height: u32, samples: u32) -> Result<Framebuffer, String> {
+        Framebuffer::create(width, height, samples, false)
+    }
+
+    // a framebuffer whose color buffer is a texture, for a pass which samples it
+    pub fn with_texture(width: u32, height: u32) -> Result<Framebuffer, String> {
+        Framebuffer::create(width, height, 0, true)
+    }
+
+    fn create(width: u32, height: u32, samples: u32, texture: bool) -> Result<Framebuffer, String> {
+        let mut fbo = 0;
+        let mut renderbuffers = [0; 2];
+        let mut color_texture = 0;
+        unsafe {
+            gl::GenFramebuffers(1, &mut fbo);
+            gl::GenRenderbuffers(2, renderbuffers.as_mut_ptr());
+            let [color_rbo, depth_rbo] = renderbuffers;
+            // a renderbuffer with 0 samples is the same as one made without multisampling
+            let storage = |format| {
+                gl::RenderbufferStorageMultisample(
+                    gl::RENDERBUFFER,
+                    samples as GLsizei,
+                    format,
+                    width as GLsizei,
+                    height as GLsizei,
+                )
+            };
+
+            gl::BindFramebuffer(gl::FRAMEBUFFER, fbo);
+            if texture {
+                gl::GenTextures(1, &mut color_texture);
+                gl::BindTexture(gl::TEXTURE_2D, color_texture);
+                gl::TexImage2D(
+                    gl::TEXTURE_2D,
+                    0,
+                    gl::RGBA8 as GLint,
+                    width as GLsizei,
+                    height as GLsizei,
+                    0,
+                    gl::RGBA,
+                    gl::UNSIGNED_BYTE,
+                    ptr::null(),
+                );
+                gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as GLint);
+                gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as GLint);
+                gl::TexParameteri(
+                    gl::TEXTURE_2D,
+                    gl::TEXTURE_WRAP_S,
+                    gl::CLAMP_TO_EDGE as GLint,
+                );
+                gl::TexParameteri(
+                    gl::TEXTURE_2D,
+                    gl::TEXTURE_WRAP_T,
+                    gl::CLAMP_TO_EDGE as GLint,
+                );
+                gl::BindTexture(gl::TEXTURE_2D, 0);
+                gl::FramebufferTexture2D(
+                    gl::FRAMEBUFFER,
+                    gl::COLOR_ATTACHMENT0,
+                    gl::TEXTURE_2D,
+                    color_texture,
+                    0,
+                );
+            } else {
+                gl::BindRenderbuffer(gl::RENDERBUFFER, color_rbo);
+                storage(gl::RGBA8);
+                gl::FramebufferRenderbuffer(
+                    gl::FRAMEBUFFER,
+                    gl::COLOR_ATTACHMENT0,
+                    gl::RENDERBUFFER,
+                    color_rbo,
+                );
+            }
+            gl::BindRenderbuffer(gl::RENDERBUFFER, depth_rbo);
+            storage(gl::DEPTH_COMPONENT24);
+            gl::FramebufferRenderbuffer(
+                gl::FRAMEBUFFER,
+                gl::DEPTH_ATTACHMENT,
+                gl::RENDERBUFFER,
+                depth_rbo,
+            );
+            let status = gl::CheckFramebufferStatus(gl::FRAMEBUFFER);
+            gl::BindRenderbuffer(gl::RENDERBUFFER, 0);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+
+            let framebuffer = Framebuffer {
+                fbo,
+                color_rbo,
+                color_texture,
+                depth_rbo,
+                width,
+                height,
+                samples,
+            };
+            if status != gl::FRAMEBUFFER_COMPLETE {
+                return Err(format!("framebuffer is incomplete: status=0x{:x}", status));
+            }
+            Ok(framebuffer)
+        }
+    }
+
+    pub fn bind(&self) {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.fbo);
+            gl::Viewport(0, 0, self.width as GLsizei, self.height as GLsizei);
+        }
+    }
+
+    pub fn unbind(&self) {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+    }
+
+    // the color texture of a framebuffer made with_texture, 0 otherwise
+    pub fn color_texture(&self) -> GLuint {
+        self.color_texture
+    }
+
+    // copy the color buffer into a framebuffer of the same size, which resolves the samples
+    // of a multisampled one
+    pub fn resolve_into(&self, target: &Framebuffer) {
+        unsafe {
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, self.fbo);
+            gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, target.fbo);
+            gl::BlitFramebuffer(
+                0,
+                0,
+                self.width as GLint,
+                self.height as GLint,
+                0,
+                0,
+                target.width as GLint,
+                target.height as GLint,
+                gl::COLOR_BUFFER_BIT,
+                gl::NEAREST,
+            );
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+    }
+
+    // read back the color buffer as RGBA8 rows, top row first
+    pub fn read_pixels(&self) -> Vec<u8> {
+        let row_len = self.width as usize * 4;
+        let mut pixels = vec![0u8; row_len * self.height as usize];
+        unsafe {
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, self.fbo);
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl::ReadPixels(
+                0,
+                0,
+                self.width as GLsizei,
+                self.height as GLsizei,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                pixels.as_mut_ptr() as *mut c_void,
+            );
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, 0);
+        }
+
+        // OpenGL returns the bottom row first
+        let mut flipped = Vec::with_capacity(pixels.len());
+        for row in pixels.chunks_exact(row_len).rev() {
+            flipped.extend_from_slice(row);
+        }
+        flipped
+    }
+}
+
+impl Drop for Framebuffer {
+    fn drop(&mut self) {
+        let renderbuffers = [self.color_rbo, self.depth_rbo];
+        unsafe {
+            if self.color_texture != 0 {
+                gl::DeleteTextures(1, &self.color_texture);
+            }
+            gl::DeleteRenderbuffers(2, renderbuffers.as_ptr());
+            gl::DeleteFramebuffers(1, &self.fbo);
+        }
+    }
+}
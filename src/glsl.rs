@@ -0,0 +1,333 @@
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::shader::{LogMessage, ShaderError};
+
+// a shader source with its includes resolved and the defines of a variant added
+pub struct Source {
+    pub code: String,
+    // files read, the shader itself first
+    pub files: Vec<PathBuf>,
+    // the file and line each line of the code comes from, None for the added defines
+    lines: Vec<Option<(usize, u32)>>,
+}
+
+impl Source {
+    // file and line of a line of the code, both counted from 1 like the compiler does
+    pub fn locate(&self, line: u32) -> Option<(&Path, u32)> {
+        let (file, line) = (*self.lines.get((line as usize).checked_sub(1)?)?)?;
+        Some((&self.files[file], line))
+    }
+
+    // point the messages of the compiler to the files the lines come from
+    pub fn map_messages(&self, messages: &mut [LogMessage]) {
+        for message in messages.iter_mut() {
+            if let Some((file, line)) = message.line.and_then(|line| self.locate(line)) {
+                message.file = file.display().to_string();
+                message.line = Some(line);
+            }
+        }
+    }
+
+    fn push(&mut self, text: &str, place: Option<(usize, u32)>) {
+        self.code.push_str(text);
+        self.code.push('\n');
+        self.lines.push(place);
+    }
+}
+
+// names of the vertex inputs in the order they are declared
+pub fn vertex_inputs(code: &str) -> Vec<String> {
+    code.lines()
+        .map(|line| line.trim())
+        .filter(|line| line.starts_with("in ") || line.starts_with("attribute "))
+        .filter_map(|line| {
+            let declaration = line[..line.find(';')?].trim_end();
+            let name = declaration.rsplit(char::is_whitespace).next()?;
+            Some(name.to_string())
+        })
+        .collect()
+}
+
+// annotations in the comment after a uniform declaration, like
+// uniform float uBrightness; // @range(0, 2) @default(1)
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Annotation {
+    pub range: Option<(f32, f32)>,
+    // empty without a default
+    pub default: Vec<f32>,
+    // not shown in the uniform editor
+    pub hidden: bool,
+}
+
+// arguments of "@name(a, b, ...)" in a comment. true and false are 1 and 0
+fn annotation_args(comment: &str, name: &str) -> Option<Vec<f32>> {
+    let start = comment.find(&format!("@{}(", name))? + name.len() + 2;
+    let end = start + comment[start..].find(')')?;
+    comment[start..end]
+        .split(',')
+        .map(|arg| match arg.trim() {
+            "true" => Some(1.0),
+            "false" => Some(0.0),
+            arg => arg.parse().ok(),
+        })
+        .collect()
+}
+
+impl Annotation {
+    pub fn parse(comment: &str) -> Annotation {
+        let range = match annotation_args(comment, "range").as_deref() {
+            Some(&[min, max]) => Some((min, max)),
+            _ => None,
+        };
+        Annotation {
+            range,
+            default: annotation_args(comment, "default").unwrap_or_default(),
+            hidden: comment.contains("@hidden"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct UniformDeclaration {
+    pub name: String,
+    // GLSL type, such as "vec3"
+    pub type_: String,
+    pub annotation: Annotation,
+}
+
+// uniforms declared one per line outside of blocks, in the order of the code. arrays are left
+// out
+pub fn uniform_declarations(code: &str) -> Vec<UniformDeclaration> {
+    let mut declarations = Vec::new();
+    for line in code.lines().map(|line| line.trim()) {
+        let (declaration, comment) = match line.find("//") {
+            Some(i) => (&line[..i], &line[i + 2..]),
+            None => (line, ""),
+        };
+        let declaration = match declaration
+            .strip_prefix("uniform ")
+            .and_then(|declaration| declaration.trim_end().strip_suffix(';'))
+        {
+            Some(declaration) if !declaration.contains([',', '[']) => declaration,
+            _ => continue,
+        };
+        let words: Vec<&str> = declaration.split_whitespace().collect();
+        if words.len() < 2 {
+            continue;
+        }
+        declarations.push(UniformDeclaration {
+            name: words[words.len() - 1].to_string(),
+            type_: words[words.len() - 2].to_string(),
+            annotation: Annotation::parse(comment),
+        });
+    }
+    declarations
+}
+
+fn include_name(text: &str) -> Option<&str> {
+    let name = text.strip_prefix("#include")?.trim();
+    name.strip_prefix('"')?.strip_suffix('"')
+}
+
+struct Preprocessor<'a> {
+    read: &'a mut dyn FnMut(&Path) -> io::Result<String>,
+    source: Source,
+    // files being included, to find cycles
+    stack: Vec<PathBuf>,
+}
+
+impl<'a> Preprocessor<'a> {
+    fn error(&self, file: usize, line: u32, message: String) -> ShaderError {
+        ShaderError {
+            messages: vec![LogMessage {
+                file: self.source.files[file].display().to_string(),
+                line: Some(line),
+                message,
+            }],
+        }
+    }
+
+    // add a file. the defines follow the #version line of the shader, which has to stay first
+    fn add(&mut self, path: &Path, defines: Option<&[String]>) -> Result<(), ShaderError> {
+        let file = self.source.files.len();
+        self.source.files.push(path.to_path_buf());
+        self.stack.push(path.to_path_buf());
+        let text = (self.read)(path)
+            .map_err(|e| ShaderError::new(&path.display().to_string(), &e.to_string()))?;
+        let has_version = text
+            .lines()
+            .any(|line| line.trim_start().starts_with("#version"));
+        let mut defines = defines;
+        if !has_version {
+            self.add_defines(defines.take());
+        }
+        for (i, text) in text.lines().enumerate() {
+            let line = i as u32 + 1;
+            let trimmed = text.trim_start();
+            if trimmed.starts_with("#include") {
+                let name = include_name(trimmed)
+                    .ok_or_else(|| self.error(file, line, "include is invalid".to_string()))?;
+                let included = path.parent().unwrap_or_else(|| Path::new("")).join(name);
+                if self.stack.contains(&included) {
+                    return Err(self.error(file, line, format!("include cycle: {}", name)));
+                }
+                // a file is included once, like with #pragma once
+                if !self.source.files.contains(&included) {
+                    self.add(&included, None).map_err(|mut e| {
+                        // a missing file is reported at its #include
+                        if e.messages[0].line.is_none() {
+                            let missing = &e.messages[0];
+                            let message = format!("{}: {}", missing.file, missing.message);
+                            e = self.error(file, line, message);
+                        }
+                        e
+                    })?;
+                }
+                continue;
+            }
+            self.source.push(text, Some((file, line)));
+            if trimmed.starts_with("#version") {
+                self.add_defines(defines.take());
+            }
+        }
+        self.stack.pop();
+        Ok(())
+    }
+
+    fn add_defines(&mut self, defines: Option<&[String]>) {
+        for define in defines.unwrap_or_default() {
+            self.source.push(&format!("#define {}", define), None);
+        }
+    }
+}
+
+// resolve the #include "file" directives of a shader, relative to the file which includes
+// them, and add a #define for each of `defines`
+pub fn preprocess(
+    path: &Path,
+    defines: &[String],
+    read: &mut dyn FnMut(&Path) -> io::Result<String>,
+) -> Result<Source, ShaderError> {
+    let mut preprocessor = Preprocessor {
+        read,
+        source: Source {
+            code: String::new(),
+            files: Vec::new(),
+            lines: Vec::new(),
+        },
+        stack: Vec::new(),
+    };
+    preprocessor.add(path, Some(defines))?;
+    Ok(preprocessor.source)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn files(path: &Path) -> io::Result<String> {
+        let text = match path.to_str().unwrap() {
+            "shader/a.fs" => "#version 140\n#include \"lib/light.glsl\"\nvoid main() {}\n",
+            "shader/lib/light.glsl" => "#include \"common.glsl\"\nvec3 light();\n",
+            "shader/lib/common.glsl" => "const float PI = 3.14;\n",
+            "shader/cycle.glsl" => "#include \"cycle.glsl\"\n",
+            "shader/broken.fs" => "void main() {}\n#include \"missing.glsl\"\n",
+            _ => return Err(io::Error::new(io::ErrorKind::NotFound, "not found")),
+        };
+        Ok(text.to_string())
+    }
+
+    #[test]
+    fn test_ok_preprocess() {
+        let defines = vec!["HAS_VERTEX_COLOR".to_string()];
+        let source = preprocess(Path::new("shader/a.fs"), &defines, &mut files).unwrap();
+        assert_eq!(
+            "#version 140\n#define HAS_VERTEX_COLOR\nconst float PI = 3.14;\nvec3 light();\nvoid main() {}\n",
+            source.code
+        );
+        assert_eq!(None, source.locate(2));
+        assert_eq!(
+            Some((Path::new("shader/lib/common.glsl"), 1)),
+            source.locate(3)
+        );
+        assert_eq!(Some((Path::new("shader/a.fs"), 3)), source.locate(5));
+        assert_eq!(None, source.locate(6));
+
+        let mut messages = vec![LogMessage {
+            file: "shader/a.fs".to_string(),
+            line: Some(4),
+            message: "error".to_string(),
+        }];
+        source.map_messages(&mut messages);
+        assert_eq!("shader/lib/light.glsl:2: error", messages[0].to_string());
+    }
+
+    #[test]
+    fn test_ng_preprocess() {
+        let error = preprocess(Path::new("shader/cycle.glsl"), &[], &mut files)
+            .err()
+            .unwrap();
+        assert_eq!(
+            "shader/cycle.glsl:1: include cycle: cycle.glsl",
+            error.to_string()
+        );
+        let error = preprocess(Path::new("shader/broken.fs"), &[], &mut files)
+            .err()
+            .unwrap();
+        assert_eq!(
+            "shader/broken.fs:2: shader/missing.glsl: not found",
+            error.to_string()
+        );
+    }
+
+    #[test]
+    fn test_ok_preprocess_shaders() {
+        let mut read = |path: &Path| std::fs::read_to_string(path);
+        let source = preprocess(Path::new("rsc/shader/mono_shader.fs"), &[], &mut read).unwrap();
+        assert_eq!(
+            vec![
+                PathBuf::from("rsc/shader/mono_shader.fs"),
+                PathBuf::from("rsc/shader/lighting.glsl"),
+            ],
+            source.files
+        );
+        assert!(source.code.contains("vec3 lambert("));
+    }
+
+    #[test]
+    fn test_ok_uniform_declarations() {
+        let code = "uniform mat4 uModel;\n\
+                    uniform float uBrightness; // @range(0, 2) @default(1)\n\
+                    uniform highp vec3 uTint;  // @default(1, 0.5, 0)\n\
+                    uniform bool uUnlit; // @default(true) @hidden\n\
+                    uniform float uWeights[4];\n\
+                    layout(std140) uniform Camera {\n";
+        let declarations = uniform_declarations(code);
+        assert_eq!(4, declarations.len());
+        assert_eq!("uModel", declarations[0].name);
+        assert_eq!(Annotation::default(), declarations[0].annotation);
+        assert_eq!(
+            Annotation {
+                range: Some((0.0, 2.0)),
+                default: vec![1.0],
+                hidden: false,
+            },
+            declarations[1].annotation
+        );
+        assert_eq!(
+            ("vec3", "uTint"),
+            (&declarations[2].type_[..], &declarations[2].name[..])
+        );
+        assert_eq!(vec![1.0, 0.5, 0.0], declarations[2].annotation.default);
+        assert!(declarations[3].annotation.hidden);
+        assert_eq!(vec![1.0], declarations[3].annotation.default);
+    }
+
+    #[test]
+    fn test_ok_vertex_inputs() {
+        let code =
+            "in vec3 iPosition;\n  in  vec3 iNormal ;\nout vec3 Color;\nattribute vec2 iUv;\n";
+        assert_eq!(vec!["iPosition", "iNormal", "iUv"], vertex_inputs(code));
+    }
+}
@@ -0,0 +1,1625 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use cgmath::{InnerSpace, Matrix, Matrix3, Matrix4, Quaternion, SquareMatrix, Vector3, Vector4};
+use serde_json::{json, Map, Value};
+
+use crate::material::{self, Material};
+use crate::model::{dir_resolver, Corner, GroupState, Model, Resolver};
+
+const GLB_MAGIC: u32 = 0x4654_6C67;
+const GLB_VERSION: u32 = 2;
+const CHUNK_JSON: u32 = 0x4E4F_534A;
+const CHUNK_BIN: u32 = 0x004E_4942;
+
+// accessor component types
+const BYTE: u32 = 5120;
+const UNSIGNED_BYTE: u32 = 5121;
+const SHORT: u32 = 5122;
+const UNSIGNED_SHORT: u32 = 5123;
+const UNSIGNED_INT: u32 = 5125;
+const FLOAT: u32 = 5126;
+
+// buffer view targets
+const ARRAY_BUFFER: u32 = 34962;
+const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+
+// values of an accessor without a buffer view, which are zeros that take no space in the file
+const MAX_ZERO_VALUES: usize = 1 << 24;
+
+// primitive modes
+const POINTS: u32 = 0;
+const TRIANGLES: u32 = 4;
+const TRIANGLE_STRIP: u32 = 5;
+const TRIANGLE_FAN: u32 = 6;
+
+pub struct GltfWriteOptions {
+    // put the texture images into the file instead of referencing them. referenced textures
+    // point at the same files from the directory of the output file
+    pub embed_textures: bool,
+    // directory the texture paths of the materials are relative to
+    pub texture_dir: PathBuf,
+}
+
+impl GltfWriteOptions {
+    pub fn new() -> GltfWriteOptions {
+        GltfWriteOptions {
+            embed_textures: false,
+            texture_dir: PathBuf::new(),
+        }
+    }
+}
+
+impl Default for GltfWriteOptions {
+    fn default() -> GltfWriteOptions {
+        GltfWriteOptions::new()
+    }
+}
+
+// binary buffer with its buffer views and accessors
+struct BufferBuilder {
+    bin: Vec<u8>,
+    views: Vec<Value>,
+    accessors: Vec<Value>,
+}
+
+impl BufferBuilder {
+    fn new() -> BufferBuilder {
+        BufferBuilder {
+            bin: Vec::new(),
+            views: Vec::new(),
+            accessors: Vec::new(),
+        }
+    }
+
+    fn add_view(&mut self, bytes: &[u8], target: Option<u32>) -> usize {
+        // accessors need their data aligned to the component size
+        while !self.bin.len().is_multiple_of(4) {
+            self.bin.push(0);
+        }
+        let mut view = json!({
+            "buffer": 0,
+            "byteOffset": self.bin.len(),
+            "byteLength": bytes.len(),
+        });
+        if let Some(target) = target {
+            view["target"] = json!(target);
+        }
+        self.bin.extend_from_slice(bytes);
+        self.views.push(view);
+        self.views.len() - 1
+    }
+
+    // float vector attribute with its min and max, type_ is "VEC2", "VEC3", ...
+    fn add_floats(&mut self, values: &[f32], components: usize, type_: &str) -> usize {
+        let mut min = vec![f32::INFINITY; components];
+        let mut max = vec![f32::NEG_INFINITY; components];
+        for value in values.chunks_exact(components) {
+            for (k, v) in value.iter().enumerate() {
+                min[k] = min[k].min(*v);
+                max[k] = max[k].max(*v);
+            }
+        }
+        let bytes: Vec<u8> = values
+            .iter()
+            .flat_map(|v| v.to_le_bytes().to_vec())
+            .collect();
+        let view = self.add_view(&bytes, Some(ARRAY_BUFFER));
+        self.accessors.push(json!({
+            "bufferView": view,
+            "componentType": FLOAT,
+            "count": values.len() / components,
+            "type": type_,
+            "min": min,
+            "max": max,
+        }));
+        self.accessors.len() - 1
+    }
+
+    // indices are stored as unsigned short when they fit
+    fn add_indices(&mut self, indices: &[u32]) -> usize {
+        let max = indices.iter().copied().max().unwrap_or(0);
+        let min = indices.iter().copied().min().unwrap_or(0);
+        let (component_type, bytes): (u32, Vec<u8>) = if max < u16::MAX as u32 {
+            let bytes = indices
+                .iter()
+                .flat_map(|i| (*i as u16).to_le_bytes().to_vec())
+                .collect();
+            (UNSIGNED_SHORT, bytes)
+        } else {
+            let bytes = indices
+                .iter()
+                .flat_map(|i| i.to_le_bytes().to_vec())
+                .collect();
+            (UNSIGNED_INT, bytes)
+        };
+        let view = self.add_view(&bytes, Some(ELEMENT_ARRAY_BUFFER));
+        self.accessors.push(json!({
+            "bufferView": view,
+            "componentType": component_type,
+            "count": indices.len(),
+            "type": "SCALAR",
+            "min": [min],
+            "max": [max],
+        }));
+        self.accessors.len() - 1
+    }
+}
+
+// vertices of one primitive. OBJ corners with the same position but another texture
+// coordinate or normal become separate vertices
+struct PrimitiveBuilder {
+    keys: HashMap<(u32, Option<u32>, Option<u32>), u32>,
+    positions: Vec<f32>,
+    normals: Vec<f32>,
+    tex_coords: Vec<f32>,
+    colors: Vec<f32>,
+    indices: Vec<u32>,
+}
+
+impl PrimitiveBuilder {
+    fn new() -> PrimitiveBuilder {
+        PrimitiveBuilder {
+            keys: HashMap::new(),
+            positions: Vec::new(),
+            normals: Vec::new(),
+            tex_coords: Vec::new(),
+            colors: Vec::new(),
+            indices: Vec::new(),
+        }
+    }
+
+    fn add_corner(&mut self, model: &Model, smooth_normals: &[f32], corner: &Corner) {
+        let key = (corner.vertex, corner.texture, corner.normal);
+        let next = self.keys.len() as u32;
+        let index = *self.keys.entry(key).or_insert(next);
+        if index == next {
+            let v = corner.vertex as usize * 3;
+            self.positions.extend_from_slice(&model.vertex[v..v + 3]);
+            match corner.normal {
+                Some(n) => {
+                    let n = n as usize * 3;
+                    self.normals
+                        .extend_from_slice(&model.vertex_normal_tmp[n..n + 3]);
+                }
+                None => {
+                    let normal = smooth_normals.get(v..v + 3).unwrap_or(&[0.0, 0.0, 1.0]);
+                    self.normals.extend_from_slice(normal);
+                }
+            }
+            match corner.texture {
+                // glTF puts the origin of the texture at the top left, OBJ at the bottom left
+                Some(t) => {
+                    let t = t as usize * 2;
+                    let uv = &model.vertex_tex[t..t + 2];
+                    self.tex_coords.extend_from_slice(&[uv[0], 1.0 - uv[1]]);
+                }
+                None => self.tex_coords.extend_from_slice(&[0.0, 0.0]),
+            }
+            if model.vertex_color.len() == model.vertex.len() {
+                self.colors.extend_from_slice(&model.vertex_color[v..v + 3]);
+            }
+        }
+        self.indices.push(index);
+    }
+
+    fn to_json(&self, buffer: &mut BufferBuilder, material: Option<usize>, mode: u32) -> Value {
+        let mut attributes = Map::new();
+        attributes.insert(
+            "POSITION".to_string(),
+            json!(buffer.add_floats(&self.positions, 3, "VEC3")),
+        );
+        if mode == TRIANGLES {
+            attributes.insert(
+                "NORMAL".to_string(),
+                json!(buffer.add_floats(&self.normals, 3, "VEC3")),
+            );
+        }
+        if self.keys.keys().any(|(_, texture, _)| texture.is_some()) {
+            attributes.insert(
+                "TEXCOORD_0".to_string(),
+                json!(buffer.add_floats(&self.tex_coords, 2, "VEC2")),
+            );
+        }
+        if !self.colors.is_empty() {
+            attributes.insert(
+                "COLOR_0".to_string(),
+                json!(buffer.add_floats(&self.colors, 3, "VEC3")),
+            );
+        }
+        let mut primitive = json!({ "attributes": attributes, "mode": mode });
+        if mode == TRIANGLES {
+            primitive["indices"] = json!(buffer.add_indices(&self.indices));
+        }
+        if let Some(material) = material {
+            primitive["material"] = json!(material);
+        }
+        primitive
+    }
+}
+
+fn mime_type(path: &str) -> Option<&'static str> {
+    let ext = Path::new(path)
+        .extension()?
+        .to_string_lossy()
+        .to_lowercase();
+    match ext.as_str() {
+        "png" => Some("image/png"),
+        "jpg" | "jpeg" => Some("image/jpeg"),
+        _ => None,
+    }
+}
+
+// relative URI of a texture path, with reserved characters percent-encoded
+fn path_to_uri(path: &str) -> String {
+    let mut uri = String::new();
+    for b in path.replace('\\', "/").bytes() {
+        if b.is_ascii_alphanumeric() || b"-._~/".contains(&b) {
+            uri.push(b as char);
+        } else {
+            uri.push_str(&format!("%{:02X}", b));
+        }
+    }
+    uri
+}
+
+// without Pm and Pr, materials are dielectric and the roughness comes from Ns
+fn material_to_json(material: &Material, texture: Option<usize>) -> Value {
+    let diffuse = material.diffuse.unwrap_or([1.0, 1.0, 1.0]);
+    let alpha = material.dissolve.unwrap_or(1.0).clamp(0.0, 1.0);
+    let roughness = match (material.roughness, material.shininess) {
+        (Some(roughness), _) => roughness,
+        (None, Some(ns)) => (2.0 / (ns.max(0.0) + 2.0)).sqrt(),
+        (None, None) => 1.0,
+    };
+    let mut pbr = json!({
+        "baseColorFactor": [diffuse[0], diffuse[1], diffuse[2], alpha],
+        "metallicFactor": material.metallic.unwrap_or(0.0),
+        "roughnessFactor": roughness,
+    });
+    if let Some(texture) = texture {
+        pbr["baseColorTexture"] = json!({ "index": texture });
+    }
+    let mut value = json!({
+        "name": material.name,
+        "pbrMetallicRoughness": pbr,
+        // OBJ faces are shown from both sides
+        "doubleSided": true,
+    });
+    if let Some(e) = material.emissive {
+        let e: Vec<f32> = e.iter().map(|x| x.clamp(0.0, 1.0)).collect();
+        value["emissiveFactor"] = json!(e);
+    }
+    if alpha < 1.0 {
+        value["alphaMode"] = json!("BLEND");
+    }
+    value
+}
+
+// build the glTF document and its binary buffer. with binary set, embedded images go into the
+// buffer as for GLB, otherwise into data URIs
+// referenced textures are rebased onto out_dir, or keep their paths without it
+fn build(
+    model: &Model,
+    options: &GltfWriteOptions,
+    binary: bool,
+    out_dir: Option<&Path>,
+) -> Result<(Value, Vec<u8>), Box<dyn Error>> {
+    let vertex_num = model.vertex.len() / 3;
+    for corner in model.corners.iter() {
+        if corner.vertex as usize >= vertex_num
+            || matches!(corner.texture, Some(t) if t as usize * 2 >= model.vertex_tex.len())
+            || matches!(corner.normal, Some(n) if n as usize * 3 >= model.vertex_normal_tmp.len())
+        {
+            return Err("polygon index is out of range".into());
+        }
+    }
+    let mut buffer = BufferBuilder::new();
+
+    // images and textures, shared by the materials using the same file
+    let mut images: Vec<Value> = Vec::new();
+    let mut image_indices: HashMap<&str, usize> = HashMap::new();
+    let mut materials: Vec<Value> = Vec::new();
+    for material in model.materials.iter() {
+        let mut texture = None;
+        if let Some(map) = material.diffuse_map.as_deref() {
+            // images which came embedded in the source file have no file to refer to
+            let embedded = model
+                .embedded_textures
+                .iter()
+                .find(|(name, _)| name == map)
+                .map(|(_, bytes)| bytes);
+            if !image_indices.contains_key(map) {
+                let image = if options.embed_textures || embedded.is_some() {
+                    let mime = mime_type(map)
+                        .ok_or_else(|| format!("only PNG and JPEG can be embedded: {}", map))?;
+                    let bytes = match embedded {
+                        Some(bytes) => bytes.clone(),
+                        None => fs::read(options.texture_dir.join(map))
+                            .map_err(|e| format!("{}: {}", map, e))?,
+                    };
+                    if binary {
+                        let view = buffer.add_view(&bytes, None);
+                        json!({ "bufferView": view, "mimeType": mime })
+                    } else {
+                        let uri = format!("data:{};base64,{}", mime, base64::encode(&bytes));
+                        json!({ "uri": uri })
+                    }
+                } else {
+                    let path = match out_dir {
+                        Some(out_dir) => material::rebase_map(map, &options.texture_dir, out_dir),
+                        None => map.to_string(),
+                    };
+                    json!({ "uri": path_to_uri(&path) })
+                };
+                images.push(image);
+                image_indices.insert(map, images.len() - 1);
+            }
+            texture = Some(image_indices[map]);
+        }
+        materials.push(material_to_json(material, texture));
+    }
+    let textures: Vec<Value> = (0..images.len())
+        .map(|i| json!({ "sampler": 0, "source": i }))
+        .collect();
+    let samplers = if textures.is_empty() {
+        Vec::new()
+    } else {
+        vec![json!({ "magFilter": 9729, "minFilter": 9987, "wrapS": 10497, "wrapT": 10497 })]
+    };
+
+    // a mesh for every group name in an object, with a primitive for each material
+    let needs_smooth = model.corners.iter().any(|c| c.normal.is_none());
+    let smooth_normals = if needs_smooth {
+        model.smooth_normals()
+    } else {
+        Vec::new()
+    };
+    let mut corner_starts = Vec::with_capacity(model.polygon_sizes.len() + 1);
+    corner_starts.push(0);
+    for size in model.polygon_sizes.iter() {
+        corner_starts.push(corner_starts.last().unwrap() + *size as usize);
+    }
+    let mut mesh_keys: Vec<(&str, &str)> = Vec::new();
+    let mut mesh_primitives: Vec<Vec<(Option<usize>, PrimitiveBuilder)>> = Vec::new();
+    for group in model.groups.iter() {
+        let key = (group.object.as_str(), group.name.as_str());
+        let mesh = match mesh_keys.iter().position(|k| *k == key) {
+            Some(mesh) => mesh,
+            None => {
+                mesh_keys.push(key);
+                mesh_primitives.push(Vec::new());
+                mesh_keys.len() - 1
+            }
+        };
+        let material = group
+            .material
+            .as_ref()
+            .and_then(|name| model.materials.iter().position(|m| &m.name == name));
+        let primitives = &mut mesh_primitives[mesh];
+        let primitive = match primitives.iter().position(|(m, _)| *m == material) {
+            Some(i) => i,
+            None => {
+                primitives.push((material, PrimitiveBuilder::new()));
+                primitives.len() - 1
+            }
+        };
+        let builder = &mut primitives[primitive].1;
+        for face in group.face_start..group.face_start + group.face_count {
+            let polygon = &model.corners[corner_starts[face]..corner_starts[face + 1]];
+            for i in 1..polygon.len().saturating_sub(1) {
+                for corner in [&polygon[0], &polygon[i], &polygon[i + 1]].iter() {
+                    builder.add_corner(model, &smooth_normals, corner);
+                }
+            }
+        }
+    }
+
+    // meshes with the object they belong to
+    let mut meshes: Vec<(&str, Value)> = Vec::new();
+    for (key, primitives) in mesh_keys.iter().zip(mesh_primitives.iter()) {
+        let primitives: Vec<Value> = primitives
+            .iter()
+            .filter(|(_, builder)| !builder.indices.is_empty())
+            .map(|(material, builder)| builder.to_json(&mut buffer, *material, TRIANGLES))
+            .collect();
+        if !primitives.is_empty() {
+            meshes.push((key.0, json!({ "name": key.1, "primitives": primitives })));
+        }
+    }
+    // a point cloud has vertices but no faces
+    if model.polygon_sizes.is_empty() && vertex_num > 0 {
+        let mut builder = PrimitiveBuilder::new();
+        for v in 0..vertex_num as u32 {
+            let corner = Corner {
+                vertex: v,
+                texture: None,
+                normal: None,
+            };
+            builder.add_corner(model, &[], &corner);
+        }
+        let primitive = builder.to_json(&mut buffer, None, POINTS);
+        meshes.push(("", json!({ "name": "points", "primitives": [primitive] })));
+    }
+
+    // a node per mesh, under a parent node for each named object
+    let mut nodes: Vec<Value> = Vec::new();
+    let mut scene_nodes: Vec<usize> = Vec::new();
+    let mut object_nodes: HashMap<String, usize> = HashMap::new();
+    for (i, (object, mesh)) in meshes.iter().enumerate() {
+        nodes.push(json!({ "name": mesh["name"], "mesh": i }));
+        let node = nodes.len() - 1;
+        if object.is_empty() {
+            scene_nodes.push(node);
+            continue;
+        }
+        let parent = match object_nodes.get(*object) {
+            Some(parent) => *parent,
+            None => {
+                nodes.push(json!({ "name": object, "children": [] }));
+                scene_nodes.push(nodes.len() - 1);
+                object_nodes.insert(object.to_string(), nodes.len() - 1);
+                nodes.len() - 1
+            }
+        };
+        nodes[parent]["children"]
+            .as_array_mut()
+            .unwrap()
+            .push(json!(node));
+    }
+
+    let mut root = Map::new();
+    root.insert(
+        "asset".to_string(),
+        json!({ "version": "2.0", "generator": "obj_viewer" }),
+    );
+    root.insert("scene".to_string(), json!(0));
+    root.insert("scenes".to_string(), json!([{ "nodes": scene_nodes }]));
+    // arrays of the top level must not be empty when present
+    let arrays = [
+        ("nodes", nodes),
+        ("meshes", meshes.into_iter().map(|(_, mesh)| mesh).collect()),
+        ("materials", materials),
+        ("textures", textures),
+        ("samplers", samplers),
+        ("images", images),
+        ("accessors", buffer.accessors),
+        ("bufferViews", buffer.views),
+    ];
+    for (name, values) in arrays.iter() {
+        if !values.is_empty() {
+            root.insert(name.to_string(), json!(values));
+        }
+    }
+    if !buffer.bin.is_empty() {
+        root.insert(
+            "buffers".to_string(),
+            json!([{ "byteLength": buffer.bin.len() }]),
+        );
+    }
+    Ok((Value::Object(root), buffer.bin))
+}
+
+fn padded(mut bytes: Vec<u8>, pad: u8) -> Vec<u8> {
+    while !bytes.len().is_multiple_of(4) {
+        bytes.push(pad);
+    }
+    bytes
+}
+
+pub fn write_glb<W: Write>(
+    writer: &mut W,
+    model: &Model,
+    options: &GltfWriteOptions,
+) -> Result<(), Box<dyn Error>> {
+    write_glb_into(writer, model, options, None)
+}
+
+fn write_glb_into<W: Write>(
+    writer: &mut W,
+    model: &Model,
+    options: &GltfWriteOptions,
+    out_dir: Option<&Path>,
+) -> Result<(), Box<dyn Error>> {
+    let (json, bin) = build(model, options, true, out_dir)?;
+    let json = padded(serde_json::to_vec(&json)?, b' ');
+    let bin = padded(bin, 0);
+    let mut length = 12 + 8 + json.len();
+    if !bin.is_empty() {
+        length += 8 + bin.len();
+    }
+    for value in [GLB_MAGIC, GLB_VERSION, length as u32].iter() {
+        writer.write_all(&value.to_le_bytes())?;
+    }
+    writer.write_all(&(json.len() as u32).to_le_bytes())?;
+    writer.write_all(&CHUNK_JSON.to_le_bytes())?;
+    writer.write_all(&json)?;
+    if !bin.is_empty() {
+        writer.write_all(&(bin.len() as u32).to_le_bytes())?;
+        writer.write_all(&CHUNK_BIN.to_le_bytes())?;
+        writer.write_all(&bin)?;
+    }
+    Ok(())
+}
+
+// write .glb, or .gltf with the buffer in a .bin file of the same name next to it
+pub fn save_gltf(
+    path: &Path,
+    model: &Model,
+    options: &GltfWriteOptions,
+) -> Result<(), Box<dyn Error>> {
+    let is_glb = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("glb"));
+    let out_dir = path.parent().unwrap_or_else(|| Path::new(""));
+    if is_glb {
+        let mut writer = BufWriter::new(File::create(path)?);
+        write_glb_into(&mut writer, model, options, Some(out_dir))?;
+        writer.flush()?;
+        return Ok(());
+    }
+
+    let (mut json, bin) = build(model, options, false, Some(out_dir))?;
+    if !bin.is_empty() {
+        let bin_path = path.with_extension("bin");
+        fs::write(&bin_path, &bin)?;
+        let bin_name = bin_path.file_name().ok_or("invalid output path")?;
+        json["buffers"][0]["uri"] = json!(path_to_uri(&bin_name.to_string_lossy()));
+    }
+    let mut writer = BufWriter::new(File::create(path)?);
+    serde_json::to_writer_pretty(&mut writer, &json)?;
+    writer.flush()?;
+    Ok(())
+}
+
+fn percent_decode(uri: &str) -> String {
+    let bytes = uri.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(b)) => {
+                decoded.push(b);
+                i += 3;
+            }
+            (b, _) => {
+                decoded.push(b);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+// bytes of a data URI such as "data:application/octet-stream;base64,..."
+fn decode_data_uri(uri: &str) -> Option<Result<Vec<u8>, Box<dyn Error>>> {
+    let rest = uri.strip_prefix("data:")?;
+    Some(match rest.find(";base64,") {
+        Some(pos) => base64::decode(&rest[pos + 8..]).map_err(|e| e.into()),
+        None => Err("only base64 data URIs are supported".into()),
+    })
+}
+
+fn usize_of(value: &Value, name: &str) -> Result<usize, Box<dyn Error>> {
+    value
+        .as_u64()
+        .map(|v| v as usize)
+        .ok_or_else(|| format!("glTF {} is invalid", name).into())
+}
+
+// values of an accessor and the number of components per element
+type AccessorValues = (Vec<f64>, usize);
+// bytes of a buffer view and its byte stride
+type ViewBytes<'a> = (&'a [u8], Option<usize>);
+// JSON and binary chunks of a GLB file
+type GlbChunks = (Value, Option<Vec<u8>>);
+
+// a parsed glTF document with its buffers loaded
+struct Document {
+    json: Value,
+    buffers: Vec<Vec<u8>>,
+}
+
+impl Document {
+    fn new(
+        json: Value,
+        glb_bin: Option<Vec<u8>>,
+        resolver: &mut Resolver,
+    ) -> Result<Document, Box<dyn Error>> {
+        let mut glb_bin = glb_bin;
+        let mut buffers = Vec::new();
+        for buffer in json["buffers"].as_array().unwrap_or(&Vec::new()) {
+            let bytes = match buffer["uri"].as_str() {
+                None => glb_bin.take().ok_or("glTF buffer has no data")?,
+                Some(uri) => match decode_data_uri(uri) {
+                    Some(bytes) => bytes?,
+                    None => resolver(&percent_decode(uri))?,
+                },
+            };
+            if bytes.len() < usize_of(&buffer["byteLength"], "buffer byteLength")? {
+                return Err("glTF buffer is shorter than its byteLength".into());
+            }
+            buffers.push(bytes);
+        }
+        Ok(Document { json, buffers })
+    }
+
+    fn view(&self, index: usize) -> Result<ViewBytes<'_>, Box<dyn Error>> {
+        let view = &self.json["bufferViews"][index];
+        let buffer = self
+            .buffers
+            .get(usize_of(&view["buffer"], "bufferView buffer")?)
+            .ok_or("glTF bufferView buffer is out of range")?;
+        let offset = view["byteOffset"].as_u64().unwrap_or(0) as usize;
+        let length = usize_of(&view["byteLength"], "bufferView byteLength")?;
+        let bytes = buffer
+            .get(offset..offset + length)
+            .ok_or("glTF bufferView is out of its buffer")?;
+        Ok((bytes, view["byteStride"].as_u64().map(|s| s as usize)))
+    }
+
+    // normalized integers are converted to 0..1 or -1..1
+    fn accessor(&self, index: usize) -> Result<AccessorValues, Box<dyn Error>> {
+        let accessor = &self.json["accessors"][index];
+        if accessor.is_null() {
+            return Err(format!("glTF accessor {} does not exist", index).into());
+        }
+        if !accessor["sparse"].is_null() {
+            return Err("glTF sparse accessors are not supported".into());
+        }
+        let count = usize_of(&accessor["count"], "accessor count")?;
+        let components = match accessor["type"].as_str() {
+            Some("SCALAR") => 1,
+            Some("VEC2") => 2,
+            Some("VEC3") => 3,
+            Some("VEC4") => 4,
+            Some("MAT2") => 4,
+            Some("MAT3") => 9,
+            Some("MAT4") => 16,
+            _ => return Err("glTF accessor type is invalid".into()),
+        };
+        let component_type = accessor["componentType"].as_u64().unwrap_or(0) as u32;
+        let size = match component_type {
+            BYTE | UNSIGNED_BYTE => 1,
+            SHORT | UNSIGNED_SHORT => 2,
+            UNSIGNED_INT | FLOAT => 4,
+            _ => return Err("glTF accessor componentType is invalid".into()),
+        };
+        // the count comes from the JSON, and is checked before anything is allocated for it
+        let value_num = count
+            .checked_mul(components)
+            .ok_or("glTF accessor count is too large")?;
+        let view_index = match accessor["bufferView"].as_u64() {
+            Some(view) => view as usize,
+            // without a buffer view the values are zero
+            None if value_num > MAX_ZERO_VALUES => {
+                return Err("glTF accessor count is too large".into())
+            }
+            None => return Ok((vec![0.0; value_num], components)),
+        };
+        let (bytes, stride) = self.view(view_index)?;
+        let offset = accessor["byteOffset"].as_u64().unwrap_or(0) as usize;
+        let stride = stride.unwrap_or(size * components);
+        let normalized = accessor["normalized"].as_bool().unwrap_or(false);
+        let end = match count.checked_sub(1) {
+            Some(last) => last
+                .checked_mul(stride)
+                .and_then(|start| start.checked_add(offset))
+                .and_then(|start| start.checked_add(size * components)),
+            None => Some(0),
+        };
+        if end.is_none_or(|end| end > bytes.len()) {
+            return Err("glTF accessor is out of its bufferView".into());
+        }
+
+        let mut values = Vec::with_capacity(value_num);
+        for i in 0..count {
+            let start = offset + i * stride;
+            let element = bytes
+                .get(start..start + size * components)
+                .ok_or("glTF accessor is out of its bufferView")?;
+            for c in element.chunks_exact(size) {
+                let value = match component_type {
+                    BYTE => c[0] as i8 as f64,
+                    UNSIGNED_BYTE => c[0] as f64,
+                    SHORT => i16::from_le_bytes([c[0], c[1]]) as f64,
+                    UNSIGNED_SHORT => u16::from_le_bytes([c[0], c[1]]) as f64,
+                    UNSIGNED_INT => u32::from_le_bytes([c[0], c[1], c[2], c[3]]) as f64,
+                    _ => f32::from_le_bytes([c[0], c[1], c[2], c[3]]) as f64,
+                };
+                let value = match (normalized, component_type) {
+                    (true, BYTE) => (value / 127.0).max(-1.0),
+                    (true, UNSIGNED_BYTE) => value / 255.0,
+                    (true, SHORT) => (value / 32767.0).max(-1.0),
+                    (true, UNSIGNED_SHORT) => value / 65535.0,
+                    _ => value,
+                };
+                values.push(value);
+            }
+        }
+        Ok((values, components))
+    }
+
+    fn attribute(
+        &self,
+        primitive: &Value,
+        name: &str,
+    ) -> Result<Option<AccessorValues>, Box<dyn Error>> {
+        match primitive["attributes"][name].as_u64() {
+            Some(index) => Ok(Some(self.accessor(index as usize)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+fn node_matrix(node: &Value) -> Matrix4<f32> {
+    let floats = |value: &Value| -> Option<Vec<f32>> {
+        value
+            .as_array()?
+            .iter()
+            .map(|v| v.as_f64().map(|v| v as f32))
+            .collect()
+    };
+    if let Some(m) = floats(&node["matrix"]).filter(|m| m.len() == 16) {
+        // column-major like cgmath
+        return Matrix4::new(
+            m[0], m[1], m[2], m[3], m[4], m[5], m[6], m[7], m[8], m[9], m[10], m[11], m[12], m[13],
+            m[14], m[15],
+        );
+    }
+    let t = floats(&node["translation"]).filter(|t| t.len() == 3);
+    let r = floats(&node["rotation"]).filter(|r| r.len() == 4);
+    let s = floats(&node["scale"]).filter(|s| s.len() == 3);
+    let t = t.map_or(Matrix4::identity(), |t| {
+        Matrix4::from_translation(Vector3::new(t[0], t[1], t[2]))
+    });
+    // glTF quaternions are (x, y, z, w)
+    let r = r.map_or(Matrix4::identity(), |r| {
+        Matrix4::from(Quaternion::new(r[3], r[0], r[1], r[2]))
+    });
+    let s = s.map_or(Matrix4::identity(), |s| {
+        Matrix4::from_nonuniform_scale(s[0], s[1], s[2])
+    });
+    t * r * s
+}
+
+fn image_extension(mime: &str) -> &'static str {
+    match mime {
+        "image/jpeg" => "jpg",
+        _ => "png",
+    }
+}
+
+// name of the texture file the material refers to. embedded images are copied into the model
+fn load_texture(
+    doc: &Document,
+    texture: usize,
+    model: &mut Model,
+) -> Result<Option<String>, Box<dyn Error>> {
+    let source = match doc.json["textures"][texture]["source"].as_u64() {
+        Some(source) => source as usize,
+        None => return Ok(None),
+    };
+    let image = &doc.json["images"][source];
+    let embedded_name = |mime: &str| match image["name"].as_str() {
+        Some(name) if !name.is_empty() => format!("{}.{}", name, image_extension(mime)),
+        _ => format!("image{}.{}", source, image_extension(mime)),
+    };
+    let (name, bytes) = match (image["uri"].as_str(), image["bufferView"].as_u64()) {
+        (Some(uri), _) => match decode_data_uri(uri) {
+            Some(bytes) => {
+                let mime = uri[5..].split(';').next().unwrap_or("");
+                (embedded_name(mime), bytes?)
+            }
+            None => return Ok(Some(percent_decode(uri))),
+        },
+        (None, Some(view)) => {
+            let mime = image["mimeType"].as_str().unwrap_or("");
+            (embedded_name(mime), doc.view(view as usize)?.0.to_vec())
+        }
+        (None, None) => return Err("glTF image has no data".into()),
+    };
+    if !model.embedded_textures.iter().any(|(n, _)| *n == name) {
+        model.embedded_textures.push((name.clone(), bytes));
+    }
+    Ok(Some(name))
+}
+
+fn load_materials(doc: &Document, model: &mut Model) -> Result<(), Box<dyn Error>> {
+    for (i, value) in doc.json["materials"]
+        .as_array()
+        .unwrap_or(&Vec::new())
+        .iter()
+        .enumerate()
+    {
+        // groups refer to materials by name, so the names have to be unique
+        let mut name = value["name"].as_str().unwrap_or("").to_string();
+        if name.is_empty() || model.materials.iter().any(|m| m.name == name) {
+            name = format!("material{}", i);
+        }
+        let mut material = Material::new(&name);
+        let pbr = &value["pbrMetallicRoughness"];
+        let factor = |value: &Value, default: f32| value.as_f64().map_or(default, |v| v as f32);
+        let color: Vec<f32> = (0..4)
+            .map(|k| factor(&pbr["baseColorFactor"][k], 1.0))
+            .collect();
+        material.diffuse = Some([color[0], color[1], color[2]]);
+        if color[3] < 1.0 {
+            material.dissolve = Some(color[3]);
+        }
+        material.metallic = Some(factor(&pbr["metallicFactor"], 1.0));
+        material.roughness = Some(factor(&pbr["roughnessFactor"], 1.0));
+        if value["emissiveFactor"].is_array() {
+            let e: Vec<f32> = (0..3)
+                .map(|k| factor(&value["emissiveFactor"][k], 0.0))
+                .collect();
+            material.emissive = Some([e[0], e[1], e[2]]);
+        }
+        if let Some(texture) = pbr["baseColorTexture"]["index"].as_u64() {
+            material.diffuse_map = load_texture(doc, texture as usize, model)?;
+        }
+        model.materials.push(material);
+    }
+    Ok(())
+}
+
+// triangles of a primitive as indices into its vertices
+fn triangle_indices(mode: u32, indices: &[u32]) -> Vec<u32> {
+    let mut triangles = Vec::new();
+    match mode {
+        TRIANGLES => triangles.extend_from_slice(&indices[..indices.len() / 3 * 3]),
+        TRIANGLE_STRIP => {
+            for i in 2..indices.len() {
+                // every other triangle of a strip is wound the other way
+                if i % 2 == 0 {
+                    triangles.extend_from_slice(&[indices[i - 2], indices[i - 1], indices[i]]);
+                } else {
+                    triangles.extend_from_slice(&[indices[i - 1], indices[i - 2], indices[i]]);
+                }
+            }
+        }
+        TRIANGLE_FAN => {
+            for i in 2..indices.len() {
+                triangles.extend_from_slice(&[indices[0], indices[i - 1], indices[i]]);
+            }
+        }
+        _ => {}
+    }
+    triangles
+}
+
+fn add_primitive(
+    doc: &Document,
+    primitive: &Value,
+    matrix: &Matrix4<f32>,
+    state: &GroupState,
+    model: &mut Model,
+) -> Result<(), Box<dyn Error>> {
+    let mode = primitive["mode"].as_u64().unwrap_or(TRIANGLES as u64) as u32;
+    if ![POINTS, TRIANGLES, TRIANGLE_STRIP, TRIANGLE_FAN].contains(&mode) {
+        // lines are not shown
+        return Ok(());
+    }
+    let (positions, components) = doc
+        .attribute(primitive, "POSITION")?
+        .ok_or("glTF primitive has no POSITION")?;
+    if components != 3 {
+        return Err("glTF POSITION must be VEC3".into());
+    }
+    let count = positions.len() / 3;
+    let normals = doc.attribute(primitive, "NORMAL")?;
+    let tex_coords = doc.attribute(primitive, "TEXCOORD_0")?;
+    let colors = doc.attribute(primitive, "COLOR_0")?;
+    for (name, attribute, types, type_name) in [
+        ("NORMAL", &normals, &[3][..], "VEC3"),
+        ("TEXCOORD_0", &tex_coords, &[2][..], "VEC2"),
+        ("COLOR_0", &colors, &[3, 4][..], "VEC3 or VEC4"),
+    ]
+    .iter()
+    {
+        if let Some((values, components)) = attribute {
+            if !types.contains(components) {
+                return Err(format!("glTF {} must be {}", name, type_name).into());
+            }
+            if values.len() / components != count {
+                return Err(format!("glTF {} count differs from POSITION", name).into());
+            }
+        }
+    }
+
+    let vertex_base = (model.vertex.len() / 3) as u32;
+    let normal_base = (model.vertex_normal_tmp.len() / 3) as u32;
+    let tex_base = (model.vertex_tex.len() / 2) as u32;
+    for p in positions.chunks_exact(3) {
+        let p = matrix * Vector4::new(p[0] as f32, p[1] as f32, p[2] as f32, 1.0);
+        model.vertex.extend_from_slice(&[p.x, p.y, p.z]);
+    }
+    let linear = Matrix3::from_cols(
+        matrix.x.truncate(),
+        matrix.y.truncate(),
+        matrix.z.truncate(),
+    );
+    if let Some((normals, _)) = &normals {
+        let normal_matrix = linear.invert().unwrap_or(linear).transpose();
+        for n in normals.chunks_exact(3) {
+            let n = normal_matrix * Vector3::new(n[0] as f32, n[1] as f32, n[2] as f32);
+            let n = if n.magnitude2() > 0.0 {
+                n.normalize()
+            } else {
+                n
+            };
+            model.vertex_normal_tmp.extend_from_slice(&[n.x, n.y, n.z]);
+        }
+    }
+    if let Some((tex_coords, components)) = &tex_coords {
+        for uv in tex_coords.chunks_exact(*components) {
+            model
+                .vertex_tex
+                .extend_from_slice(&[uv[0] as f32, 1.0 - uv[1] as f32]);
+        }
+    }
+    // vertices of primitives without colors are white
+    if colors.is_some() || !model.vertex_color.is_empty() {
+        model.vertex_color.resize(vertex_base as usize * 3, 1.0);
+        match &colors {
+            Some((colors, components)) => {
+                for c in colors.chunks_exact(*components) {
+                    model.vertex_color.extend(c[..3].iter().map(|v| *v as f32));
+                }
+            }
+            None => model.vertex_color.resize(model.vertex.len(), 1.0),
+        }
+    }
+
+    let indices: Vec<u32> = match primitive["indices"].as_u64() {
+        Some(index) => doc
+            .accessor(index as usize)?
+            .0
+            .iter()
+            .map(|i| *i as u32)
+            .collect(),
+        None => (0..count as u32).collect(),
+    };
+    if indices.iter().any(|i| *i as usize >= count) {
+        return Err("glTF index is out of range".into());
+    }
+    // a mirroring transform turns the faces inside out
+    let flip = linear.determinant() < 0.0;
+    let corner = |i: u32| Corner {
+        vertex: vertex_base + i,
+        texture: tex_coords.as_ref().map(|_| tex_base + i),
+        normal: normals.as_ref().map(|_| normal_base + i),
+    };
+    for triangle in triangle_indices(mode, &indices).chunks_exact(3) {
+        let polygon = if flip {
+            [
+                corner(triangle[0]),
+                corner(triangle[2]),
+                corner(triangle[1]),
+            ]
+        } else {
+            [
+                corner(triangle[0]),
+                corner(triangle[1]),
+                corner(triangle[2]),
+            ]
+        };
+        model.add_polygon(&polygon, state);
+    }
+    Ok(())
+}
+
+// add the meshes of a node and its children. object is the name of the root node
+fn add_node(
+    doc: &Document,
+    index: usize,
+    parent: &Matrix4<f32>,
+    object: &str,
+    depth: usize,
+    model: &mut Model,
+) -> Result<(), Box<dyn Error>> {
+    let node = &doc.json["nodes"][index];
+    if node.is_null() {
+        return Err(format!("glTF node {} does not exist", index).into());
+    }
+    if depth > doc.json["nodes"].as_array().map_or(0, |n| n.len()) {
+        return Err("glTF node hierarchy has a cycle".into());
+    }
+    let matrix = parent * node_matrix(node);
+    if let Some(mesh_index) = node["mesh"].as_u64() {
+        let mesh = &doc.json["meshes"][mesh_index as usize];
+        let mut state = GroupState::new();
+        state.object = object.to_string();
+        state.name = match (node["name"].as_str(), mesh["name"].as_str()) {
+            (Some(name), _) | (None, Some(name)) if !name.is_empty() => name.to_string(),
+            _ => format!("mesh{}", mesh_index),
+        };
+        let primitives = mesh["primitives"]
+            .as_array()
+            .ok_or_else(|| format!("glTF mesh {} does not exist", mesh_index))?;
+        for primitive in primitives.iter() {
+            state.material = primitive["material"]
+                .as_u64()
+                .and_then(|m| model.materials.get(m as usize))
+                .map(|m| m.name.clone());
+            add_primitive(doc, primitive, &matrix, &state, model)?;
+        }
+    }
+    for child in node["children"].as_array().unwrap_or(&Vec::new()) {
+        let child = usize_of(child, "node children")?;
+        add_node(doc, child, &matrix, object, depth + 1, model)?;
+    }
+    Ok(())
+}
+
+fn split_glb(bytes: &[u8]) -> Result<GlbChunks, Box<dyn Error>> {
+    let word = |pos: usize| -> Result<u32, Box<dyn Error>> {
+        let b = bytes.get(pos..pos + 4).ok_or("GLB file is truncated")?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    };
+    if word(4)? != GLB_VERSION {
+        return Err("only GLB version 2 is supported".into());
+    }
+    let length = (word(8)? as usize).min(bytes.len());
+    let mut json = None;
+    let mut bin = None;
+    let mut pos = 12;
+    while pos + 8 <= length {
+        let chunk_length = word(pos)? as usize;
+        let data = bytes
+            .get(pos + 8..pos + 8 + chunk_length)
+            .ok_or("GLB chunk is truncated")?;
+        match word(pos + 4)? {
+            CHUNK_JSON => json = Some(serde_json::from_slice(data)?),
+            CHUNK_BIN if bin.is_none() => bin = Some(data.to_vec()),
+            _ => {}
+        }
+        pos += 8 + chunk_length;
+    }
+    Ok((json.ok_or("GLB file has no JSON chunk")?, bin))
+}
+
+// read .gltf or .glb data. external buffers are read through the resolver
+pub fn parse_gltf(bytes: &[u8], resolver: &mut Resolver) -> Result<Model, Box<dyn Error>> {
+    let (json, bin) = if bytes.starts_with(&GLB_MAGIC.to_le_bytes()) {
+        split_glb(bytes)?
+    } else {
+        (serde_json::from_slice(bytes)?, None)
+    };
+    let version = json["asset"]["version"].as_str().unwrap_or("");
+    if !version.starts_with("2.") {
+        return Err(format!("unsupported glTF version: {}", version).into());
+    }
+    let doc = Document::new(json, bin, resolver)?;
+
+    let mut model = Model::new();
+    load_materials(&doc, &mut model)?;
+    let roots: Vec<usize> = match doc.json["scenes"]
+        [doc.json["scene"].as_u64().unwrap_or(0) as usize]["nodes"]
+        .as_array()
+    {
+        Some(nodes) => nodes
+            .iter()
+            .filter_map(|n| n.as_u64())
+            .map(|n| n as usize)
+            .collect(),
+        // without a scene every node which is not a child is a root
+        None => {
+            let nodes = doc.json["nodes"].as_array().cloned().unwrap_or_default();
+            let children: Vec<u64> = nodes
+                .iter()
+                .flat_map(|n| n["children"].as_array().cloned().unwrap_or_default())
+                .filter_map(|c| c.as_u64())
+                .collect();
+            (0..nodes.len())
+                .filter(|i| !children.contains(&(*i as u64)))
+                .collect()
+        }
+    };
+    for root in roots {
+        let node = &doc.json["nodes"][root];
+        // meshes below a root node belong to the object named after it
+        let object = if node["mesh"].is_null() {
+            node["name"].as_str().unwrap_or("")
+        } else {
+            ""
+        };
+        add_node(&doc, root, &Matrix4::identity(), object, 0, &mut model)?;
+    }
+    if !model.vertex_color.is_empty() {
+        model.vertex_color.resize(model.vertex.len(), 1.0);
+    }
+    Ok(model)
+}
+
+pub fn load_gltf(path: &str) -> Result<Model, Box<dyn Error>> {
+    let base_dir = Path::new(path).parent().unwrap_or_else(|| Path::new(""));
+    parse_gltf(&fs::read(path)?, &mut dir_resolver(base_dir))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::GroupState;
+    use std::convert::TryInto;
+
+    fn u32_at(bytes: &[u8], pos: usize) -> u32 {
+        u32::from_le_bytes(bytes[pos..pos + 4].try_into().unwrap())
+    }
+
+    fn index(value: &Value, array: &Value) -> usize {
+        let i = value.as_u64().expect("index is not an integer") as usize;
+        assert!(
+            i < array.as_array().map_or(0, |a| a.len()),
+            "index out of range"
+        );
+        i
+    }
+
+    // structural rules of the glTF 2.0 specification
+    fn validate(json: &Value, bin: &[u8]) {
+        assert_eq!("2.0", json["asset"]["version"]);
+        let root = json.as_object().unwrap();
+        for (name, value) in root.iter() {
+            if let Some(array) = value.as_array() {
+                assert!(!array.is_empty(), "{} is empty", name);
+            }
+        }
+        if let Some(buffers) = json["buffers"].as_array() {
+            assert_eq!(1, buffers.len());
+            assert_eq!(bin.len() as u64, buffers[0]["byteLength"].as_u64().unwrap());
+        }
+        for view in json["bufferViews"].as_array().unwrap_or(&Vec::new()) {
+            index(&view["buffer"], &json["buffers"]);
+            let offset = view["byteOffset"].as_u64().unwrap_or(0);
+            let length = view["byteLength"].as_u64().unwrap();
+            assert!(length > 0 && offset + length <= bin.len() as u64);
+        }
+        for accessor in json["accessors"].as_array().unwrap() {
+            let view = &json["bufferViews"][index(&accessor["bufferView"], &json["bufferViews"])];
+            let component_size = match accessor["componentType"].as_u64().unwrap() {
+                5121 => 1,
+                5123 => 2,
+                5125 | 5126 => 4,
+                ty => panic!("invalid component type {}", ty),
+            };
+            let components = match accessor["type"].as_str().unwrap() {
+                "SCALAR" => 1,
+                "VEC2" => 2,
+                "VEC3" => 3,
+                "VEC4" => 4,
+                ty => panic!("invalid type {}", ty),
+            };
+            let count = accessor["count"].as_u64().unwrap();
+            assert!(count > 0);
+            let offset = view["byteOffset"].as_u64().unwrap_or(0);
+            assert_eq!(0, offset % component_size);
+            assert!(count * component_size * components <= view["byteLength"].as_u64().unwrap());
+            assert_eq!(
+                components as usize,
+                accessor["min"].as_array().unwrap().len()
+            );
+            assert_eq!(
+                components as usize,
+                accessor["max"].as_array().unwrap().len()
+            );
+        }
+        let accessors = &json["accessors"];
+        for mesh in json["meshes"].as_array().unwrap() {
+            for primitive in mesh["primitives"].as_array().unwrap() {
+                let attributes = primitive["attributes"].as_object().unwrap();
+                let position = &accessors[index(&attributes["POSITION"], accessors)];
+                let count = position["count"].as_u64().unwrap();
+                for accessor in attributes.values() {
+                    let accessor = &accessors[index(accessor, accessors)];
+                    assert_eq!(count, accessor["count"].as_u64().unwrap());
+                }
+                if !primitive["indices"].is_null() {
+                    let indices = &accessors[index(&primitive["indices"], accessors)];
+                    assert_eq!("SCALAR", indices["type"]);
+                    assert!(indices["max"][0].as_u64().unwrap() < count);
+                    if primitive["mode"] == 4 {
+                        assert_eq!(0, indices["count"].as_u64().unwrap() % 3);
+                    }
+                }
+                if !primitive["material"].is_null() {
+                    index(&primitive["material"], &json["materials"]);
+                }
+            }
+        }
+        for texture in json["textures"].as_array().unwrap_or(&Vec::new()) {
+            index(&texture["source"], &json["images"]);
+            index(&texture["sampler"], &json["samplers"]);
+        }
+        for image in json["images"].as_array().unwrap_or(&Vec::new()) {
+            assert!(image["uri"].is_string() != image["bufferView"].is_u64());
+            if image["bufferView"].is_u64() {
+                assert!(image["mimeType"].is_string());
+            }
+        }
+        // every node has at most one parent and is not a root of the scene at the same time
+        let nodes = &json["nodes"];
+        let mut parents = vec![0; nodes.as_array().unwrap().len()];
+        for node in nodes.as_array().unwrap() {
+            if !node["mesh"].is_null() {
+                index(&node["mesh"], &json["meshes"]);
+            }
+            for child in node["children"].as_array().unwrap_or(&Vec::new()) {
+                parents[index(child, nodes)] += 1;
+            }
+        }
+        for root in json["scenes"][index(&json["scene"], &json["scenes"])]["nodes"]
+            .as_array()
+            .unwrap()
+        {
+            parents[index(root, nodes)] += 1;
+        }
+        assert!(parents.iter().all(|p| *p == 1));
+    }
+
+    // two quads of the same position but different texture coordinates, in two objects
+    fn test_model() -> Model {
+        let mut model = Model::new();
+        model.vertex = vec![0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 0.0, 0.0, 1.0, 0.0];
+        model.vertex_tex = vec![0.0, 0.0, 1.0, 0.0, 1.0, 1.0, 0.0, 1.0, 0.5, 0.5];
+        let mut red = Material::new("red");
+        red.diffuse = Some([1.0, 0.0, 0.0]);
+        red.diffuse_map = Some("red texture.png".to_string());
+        red.shininess = Some(98.0);
+        let mut glass = Material::new("glass");
+        glass.dissolve = Some(0.5);
+        model.materials = vec![red, glass];
+        let corner = |vertex, texture| Corner {
+            vertex,
+            texture: Some(texture),
+            normal: None,
+        };
+        let mut state = GroupState::new();
+        state.object = "body".to_string();
+        state.material = Some("red".to_string());
+        let quad = [corner(0, 0), corner(1, 1), corner(2, 2), corner(3, 3)];
+        model.add_polygon(&quad, &state);
+        state.material = Some("glass".to_string());
+        model.add_polygon(&[corner(0, 4), corner(1, 1), corner(2, 2)], &state);
+        state.object = "".to_string();
+        state.name = "other".to_string();
+        state.material = None;
+        model.add_polygon(&quad, &state);
+        model
+    }
+
+    #[test]
+    fn test_ok_build() {
+        let model = test_model();
+        let (json, bin) = build(&model, &GltfWriteOptions::new(), false, None).unwrap();
+        validate(&json, &bin);
+
+        assert_eq!(2, json["meshes"].as_array().unwrap().len());
+        let primitives = json["meshes"][0]["primitives"].as_array().unwrap();
+        assert_eq!(2, primitives.len());
+        assert_eq!(0, primitives[0]["material"]);
+        assert_eq!(1, primitives[1]["material"]);
+        assert!(json["meshes"][1]["primitives"][0]["material"].is_null());
+
+        // quad as two triangles, with v flipped
+        let accessors = &json["accessors"];
+        let position =
+            &accessors[primitives[0]["attributes"]["POSITION"].as_u64().unwrap() as usize];
+        assert_eq!(4, position["count"]);
+        assert_eq!(json!([0.0, 0.0, 0.0]), position["min"]);
+        assert_eq!(json!([1.0, 1.0, 0.0]), position["max"]);
+        let indices = &accessors[primitives[0]["indices"].as_u64().unwrap() as usize];
+        assert_eq!(6, indices["count"]);
+        assert_eq!(UNSIGNED_SHORT, indices["componentType"]);
+        let tex_coord =
+            &accessors[primitives[0]["attributes"]["TEXCOORD_0"].as_u64().unwrap() as usize];
+        assert_eq!(json!([1.0, 1.0]), tex_coord["max"]);
+
+        // the body object node holds its mesh, the other mesh is at the root
+        assert_eq!(json!([1, 2]), json["scenes"][0]["nodes"]);
+        assert_eq!("body", json["nodes"][1]["name"]);
+        assert_eq!(json!([0]), json["nodes"][1]["children"]);
+
+        let red = &json["materials"][0];
+        assert_eq!(
+            json!([1.0, 0.0, 0.0, 1.0]),
+            red["pbrMetallicRoughness"]["baseColorFactor"]
+        );
+        assert_eq!(0.0, red["pbrMetallicRoughness"]["metallicFactor"]);
+        let roughness = red["pbrMetallicRoughness"]["roughnessFactor"]
+            .as_f64()
+            .unwrap();
+        assert!((roughness - 0.1414).abs() < 1.0e-4);
+        assert_eq!("BLEND", json["materials"][1]["alphaMode"]);
+        assert_eq!("red%20texture.png", json["images"][0]["uri"]);
+    }
+
+    #[test]
+    fn test_ok_write_glb() {
+        let dir = std::env::temp_dir().join("obj_viewer_test_write_glb");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("red texture.png"), b"\x89PNG fake image").unwrap();
+        let options = GltfWriteOptions {
+            embed_textures: true,
+            texture_dir: dir.clone(),
+        };
+        let mut glb = Vec::new();
+        write_glb(&mut glb, &test_model(), &options).unwrap();
+
+        assert_eq!(GLB_MAGIC, u32_at(&glb, 0));
+        assert_eq!(GLB_VERSION, u32_at(&glb, 4));
+        assert_eq!(glb.len() as u32, u32_at(&glb, 8));
+        let json_len = u32_at(&glb, 12) as usize;
+        assert_eq!(CHUNK_JSON, u32_at(&glb, 16));
+        assert_eq!(0, json_len % 4);
+        let json: Value = serde_json::from_slice(&glb[20..20 + json_len]).unwrap();
+        let bin_start = 20 + json_len;
+        let bin_len = u32_at(&glb, bin_start) as usize;
+        assert_eq!(CHUNK_BIN, u32_at(&glb, bin_start + 4));
+        assert_eq!(glb.len(), bin_start + 8 + bin_len);
+        let bin = &glb[bin_start + 8..];
+        let byte_length = json["buffers"][0]["byteLength"].as_u64().unwrap() as usize;
+        assert!(byte_length <= bin_len && bin_len - byte_length < 4);
+        validate(&json, &bin[..byte_length]);
+
+        let image = &json["images"][0];
+        assert_eq!("image/png", image["mimeType"]);
+        let view = &json["bufferViews"][image["bufferView"].as_u64().unwrap() as usize];
+        let offset = view["byteOffset"].as_u64().unwrap() as usize;
+        assert_eq!(b"\x89PNG fake image", &bin[offset..offset + 15]);
+
+        // .gltf embeds the image as a data URI and writes the buffer next to it
+        save_gltf(&dir.join("scene.gltf"), &test_model(), &options).unwrap();
+        let json: Value =
+            serde_json::from_slice(&fs::read(dir.join("scene.gltf")).unwrap()).unwrap();
+        let bin = fs::read(dir.join("scene.bin")).unwrap();
+        validate(&json, &bin);
+        assert_eq!("scene.bin", json["buffers"][0]["uri"]);
+        assert!(json["images"][0]["uri"]
+            .as_str()
+            .unwrap()
+            .starts_with("data:image/png;base64,"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_ok_save_gltf_in_other_directory() {
+        let dir = std::env::temp_dir().join("obj_viewer_test_save_gltf_dir");
+        let _ = fs::remove_dir_all(&dir);
+        let (source, output) = (dir.join("a"), dir.join("b"));
+        fs::create_dir_all(&source).unwrap();
+        fs::create_dir_all(&output).unwrap();
+        fs::write(source.join("red texture.png"), b"\x89PNG fake image").unwrap();
+        let options = GltfWriteOptions {
+            embed_textures: false,
+            texture_dir: source.clone(),
+        };
+        save_gltf(&output.join("out.gltf"), &test_model(), &options).unwrap();
+        let json: Value =
+            serde_json::from_slice(&fs::read(output.join("out.gltf")).unwrap()).unwrap();
+        assert_eq!("../a/red%20texture.png", json["images"][0]["uri"]);
+        let loaded = load_gltf(&output.join("out.gltf").to_string_lossy()).unwrap();
+        let map = loaded.materials[0].diffuse_map.clone().unwrap();
+        assert!(output.join(map).exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_ng_write_glb() {
+        let options = GltfWriteOptions {
+            embed_textures: true,
+            texture_dir: PathBuf::from("/nonexistent"),
+        };
+        assert!(write_glb(&mut Vec::new(), &test_model(), &options).is_err());
+        let mut model = test_model();
+        model.corners[0].vertex = 10;
+        assert!(write_glb(&mut Vec::new(), &model, &GltfWriteOptions::new()).is_err());
+    }
+
+    #[test]
+    fn test_ok_point_cloud() {
+        let mut model = Model::new();
+        model.vertex = vec![0.0, 0.0, 0.0, 1.0, 2.0, 3.0];
+        model.vertex_color = vec![1.0, 0.0, 0.0, 0.0, 1.0, 0.0];
+        let (json, bin) = build(&model, &GltfWriteOptions::new(), true, None).unwrap();
+        validate(&json, &bin);
+        let primitive = &json["meshes"][0]["primitives"][0];
+        assert_eq!(POINTS, primitive["mode"]);
+        assert!(primitive["attributes"]["COLOR_0"].is_u64());
+    }
+    #[test]
+    fn test_ok_import_round_trip() {
+        let model = test_model();
+        let mut glb = Vec::new();
+        write_glb(&mut glb, &model, &GltfWriteOptions::new()).unwrap();
+        let loaded = parse_gltf(&glb, &mut dir_resolver(Path::new(""))).unwrap();
+
+        // the quads come back as triangles with separate vertices per primitive
+        assert_eq!(5, loaded.polygon_sizes.len());
+        assert_eq!(4 + 3 + 4, loaded.vertex.len() / 3);
+        assert_eq!(loaded.vertex.len(), loaded.vertex_normal_tmp.len());
+        assert_eq!(&model.vertex_tex[..8], &loaded.vertex_tex[..8]);
+        let names: Vec<&str> = loaded.materials.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(vec!["red", "glass"], names);
+        assert_eq!(Some(0.0), loaded.materials[0].metallic);
+        assert_eq!(Some(0.5), loaded.materials[1].dissolve);
+        assert_eq!(
+            Some("red texture.png".to_string()),
+            loaded.materials[0].diffuse_map
+        );
+        let groups: Vec<(&str, &str, Option<&str>)> = loaded
+            .groups
+            .iter()
+            .map(|g| (g.object.as_str(), g.name.as_str(), g.material.as_deref()))
+            .collect();
+        assert_eq!(
+            vec![
+                ("body", "default", Some("red")),
+                ("body", "default", Some("glass")),
+                ("", "other", None)
+            ],
+            groups
+        );
+    }
+
+    // two nodes sharing a mesh whose buffer is a data URI, with unsigned byte indices,
+    // a triangle strip and normalized unsigned byte colors
+    fn hierarchy_gltf() -> Value {
+        let mut bin = Vec::new();
+        for value in [
+            0.0f32, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 1.0, 1.0, 0.0,
+        ]
+        .iter()
+        {
+            bin.extend_from_slice(&value.to_le_bytes());
+        }
+        bin.extend_from_slice(&[0, 1, 2, 3]);
+        bin.extend_from_slice(&[
+            255, 0, 0, 255, 0, 255, 0, 255, 0, 0, 255, 255, 255, 255, 255, 255,
+        ]);
+        let uri = format!(
+            "data:application/octet-stream;base64,{}",
+            base64::encode(&bin)
+        );
+        json!({
+            "asset": { "version": "2.0" },
+            "scene": 0,
+            "scenes": [{ "nodes": [0] }],
+            "nodes": [
+                { "name": "root", "translation": [10.0, 0.0, 0.0], "children": [1, 2] },
+                { "name": "plain", "mesh": 0 },
+                { "name": "mirrored", "mesh": 0, "scale": [-2.0, 1.0, 1.0] },
+            ],
+            "meshes": [{ "primitives": [{
+                "attributes": { "POSITION": 0, "COLOR_0": 2 },
+                "indices": 1,
+                "mode": 5,
+            }] }],
+            "accessors": [
+                { "bufferView": 0, "componentType": FLOAT, "count": 4, "type": "VEC3" },
+                { "bufferView": 1, "componentType": UNSIGNED_BYTE, "count": 4, "type": "SCALAR" },
+                {
+                    "bufferView": 2, "componentType": UNSIGNED_BYTE, "normalized": true,
+                    "count": 4, "type": "VEC4"
+                },
+            ],
+            "bufferViews": [
+                { "buffer": 0, "byteLength": 48 },
+                { "buffer": 0, "byteOffset": 48, "byteLength": 4 },
+                { "buffer": 0, "byteOffset": 52, "byteLength": 16 },
+            ],
+            "buffers": [{ "uri": uri, "byteLength": bin.len() }],
+        })
+    }
+
+    #[test]
+    fn test_ok_import_hierarchy() {
+        let json = serde_json::to_vec(&hierarchy_gltf()).unwrap();
+        let model = parse_gltf(&json, &mut dir_resolver(Path::new(""))).unwrap();
+        assert_eq!(8, model.vertex.len() / 3);
+        assert_eq!(&[11.0, 0.0, 0.0], &model.vertex[3..6]);
+        assert_eq!(&[8.0, 0.0, 0.0], &model.vertex[15..18]);
+        assert_eq!(&[1.0, 0.0, 0.0], &model.vertex_color[..3]);
+        assert_eq!("root", model.groups[0].object);
+        assert_eq!("mirrored", model.groups[1].name);
+
+        // the strip 0 1 2 3 is (0 1 2) and (2 1 3), and the mirrored copy is flipped
+        assert_eq!(vec![0, 1, 2, 2, 1, 3], &model.indices.vertex_indices[..6]);
+        assert_eq!(vec![4, 6, 5, 6, 7, 5], &model.indices.vertex_indices[6..]);
+    }
+
+    #[test]
+    fn test_ng_import() {
+        assert!(parse_gltf(b"{}", &mut dir_resolver(Path::new(""))).is_err());
+        assert!(parse_gltf(b"glTF\x02\x00\x00\x00", &mut dir_resolver(Path::new(""))).is_err());
+
+        let mut json = hierarchy_gltf();
+        json["accessors"][1]["count"] = json!(5);
+        assert!(parse_gltf(
+            &serde_json::to_vec(&json).unwrap(),
+            &mut dir_resolver(Path::new(""))
+        )
+        .is_err());
+
+        let mut json = hierarchy_gltf();
+        json["buffers"][0]["uri"] = json!("missing.bin");
+        assert!(parse_gltf(
+            &serde_json::to_vec(&json).unwrap(),
+            &mut dir_resolver(Path::new(""))
+        )
+        .is_err());
+
+        let mut json = hierarchy_gltf();
+        json["nodes"][1]["children"] = json!([0]);
+        assert!(parse_gltf(
+            &serde_json::to_vec(&json).unwrap(),
+            &mut dir_resolver(Path::new(""))
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_ng_import_attribute_type() {
+        let import = |attribute: &str, accessor: usize, type_: &str| {
+            let mut json = hierarchy_gltf();
+            json["meshes"][0]["primitives"][0]["attributes"][attribute] = json!(accessor);
+            json["accessors"][accessor]["type"] = json!(type_);
+            parse_gltf(
+                &serde_json::to_vec(&json).unwrap(),
+                &mut dir_resolver(Path::new("")),
+            )
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+        };
+        assert_eq!(
+            Err("glTF NORMAL must be VEC3".to_string()),
+            import("NORMAL", 2, "VEC4")
+        );
+        assert_eq!(
+            Err("glTF TEXCOORD_0 must be VEC2".to_string()),
+            import("TEXCOORD_0", 1, "SCALAR")
+        );
+        for type_ in ["SCALAR", "VEC2"].iter() {
+            assert_eq!(
+                Err("glTF COLOR_0 must be VEC3 or VEC4".to_string()),
+                import("COLOR_0", 2, type_)
+            );
+        }
+        assert_eq!(Ok(()), import("COLOR_0", 2, "VEC4"));
+    }
+
+    #[test]
+    fn test_ng_import_huge_count() {
+        // counts which do not fit the bufferView, or overflow, fail before allocating
+        let counts = [json!(4_000_000_000_000u64), json!(u64::MAX)];
+        for count in counts.iter() {
+            let mut json = hierarchy_gltf();
+            json["accessors"][0]["count"] = count.clone();
+            assert!(parse_gltf(
+                &serde_json::to_vec(&json).unwrap(),
+                &mut dir_resolver(Path::new(""))
+            )
+            .is_err());
+
+            let mut json = hierarchy_gltf();
+            json["accessors"][0]["count"] = count.clone();
+            json["accessors"][0]
+                .as_object_mut()
+                .unwrap()
+                .remove("bufferView");
+            assert!(parse_gltf(
+                &serde_json::to_vec(&json).unwrap(),
+                &mut dir_resolver(Path::new(""))
+            )
+            .is_err());
+        }
+    }
+}
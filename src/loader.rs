@@ -0,0 +1,469 @@
+use std::convert::TryInto;
+use std::error::Error;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::str;
+
+use crate::archive::{self, ModelArchive};
+use crate::gltf;
+use crate::model::{dir_resolver, Model, Resolver};
+use crate::off;
+use crate::ply;
+use crate::progress::{self, Progress};
+use crate::stl;
+use crate::xyz;
+
+// version of what the loaders produce, which invalidates cached models when it changes.
+// bump it when a loader changes the model it makes of a file
+pub const LOADER_VERSION: u32 = 1;
+
+// bytes from the start of a file given to Loader::sniff
+const SNIFF_LEN: usize = 512;
+
+// importer of one file format. every format is loaded into the same Model. loaders are
+// shared with loading threads
+pub trait Loader: Send + Sync {
+    fn name(&self) -> &str;
+    // lowercase file extensions without the dot
+    fn extensions(&self) -> &[&str];
+    // true if the start of the file (head) looks like this format. file_len is the whole size
+    fn sniff(&self, _head: &[u8], _file_len: u64) -> bool {
+        false
+    }
+    // parse a whole file held in memory. the files it refers to are read through the resolver
+    fn load_from_bytes(
+        &self,
+        data: &[u8],
+        resolver: &mut Resolver,
+    ) -> Result<Model, Box<dyn Error>>;
+    fn load(&self, path: &Path) -> Result<Model, Box<dyn Error>> {
+        self.load_with_progress(path, &Progress::new())
+    }
+    // progress counts the bytes read and the faces loaded, and loading stops when it is
+    // cancelled. by default both are done while the file is read
+    fn load_with_progress(
+        &self,
+        path: &Path,
+        progress: &Progress,
+    ) -> Result<Model, Box<dyn Error>> {
+        let data = progress::read_file(path, progress)?;
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+        let model = self.load_from_bytes(&data, &mut dir_resolver(dir))?;
+        progress.add_faces(model.polygon_sizes.len() as u64);
+        Ok(model)
+    }
+}
+
+fn path_str(path: &Path) -> Result<&str, Box<dyn Error>> {
+    Ok(path.to_str().ok_or("path is not valid UTF-8")?)
+}
+
+// first word of the data, skipping leading whitespace
+fn first_word(head: &[u8]) -> &[u8] {
+    let start = head
+        .iter()
+        .position(|b| !b.is_ascii_whitespace())
+        .unwrap_or(head.len());
+    let len = head[start..]
+        .iter()
+        .position(|b| b.is_ascii_whitespace())
+        .unwrap_or(head.len() - start);
+    &head[start..start + len]
+}
+
+pub struct ObjLoader;
+
+impl Loader for ObjLoader {
+    fn name(&self) -> &str {
+        "OBJ"
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["obj"]
+    }
+
+    fn load_from_bytes(
+        &self,
+        data: &[u8],
+        resolver: &mut Resolver,
+    ) -> Result<Model, Box<dyn Error>> {
+        let mut model = Model::new();
+        model.load_from_bytes_parallel(data, resolver)?;
+        Ok(model)
+    }
+
+    // map the file instead of reading it whole. the progress is the one of the parsed chunks
+    fn load_with_progress(
+        &self,
+        path: &Path,
+        progress: &Progress,
+    ) -> Result<Model, Box<dyn Error>> {
+        let mut model = Model::new();
+        model.load_parallel_with_progress(path_str(path)?, progress)?;
+        Ok(model)
+    }
+}
+
+pub struct StlLoader;
+
+impl Loader for StlLoader {
+    fn name(&self) -> &str {
+        "STL"
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["stl"]
+    }
+
+    // binary STL has no magic number, but its size follows from the triangle count
+    fn sniff(&self, head: &[u8], file_len: u64) -> bool {
+        if let Some(count) = head.get(80..84) {
+            let count = u32::from_le_bytes(count.try_into().unwrap()) as u64;
+            if file_len == 84 + count * 50 {
+                return true;
+            }
+        }
+        first_word(head) == b"solid" && head.windows(5).any(|w| w == b"facet")
+    }
+
+    fn load_from_bytes(
+        &self,
+        data: &[u8],
+        _resolver: &mut Resolver,
+    ) -> Result<Model, Box<dyn Error>> {
+        stl::parse_stl(data)
+    }
+}
+
+pub struct PlyLoader;
+
+impl Loader for PlyLoader {
+    fn name(&self) -> &str {
+        "PLY"
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["ply"]
+    }
+
+    fn sniff(&self, head: &[u8], _file_len: u64) -> bool {
+        head.starts_with(b"ply\n") || head.starts_with(b"ply\r\n")
+    }
+
+    fn load_from_bytes(
+        &self,
+        data: &[u8],
+        _resolver: &mut Resolver,
+    ) -> Result<Model, Box<dyn Error>> {
+        ply::parse_ply(data)
+    }
+}
+
+pub struct GltfLoader;
+
+impl Loader for GltfLoader {
+    fn name(&self) -> &str {
+        "glTF"
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["gltf", "glb"]
+    }
+
+    fn sniff(&self, head: &[u8], _file_len: u64) -> bool {
+        head.starts_with(b"glTF")
+            || (first_word(head).starts_with(b"{") && head.windows(7).any(|w| w == b"\"asset\""))
+    }
+
+    fn load_from_bytes(
+        &self,
+        data: &[u8],
+        resolver: &mut Resolver,
+    ) -> Result<Model, Box<dyn Error>> {
+        gltf::parse_gltf(data, resolver)
+    }
+}
+
+pub struct OffLoader;
+
+impl Loader for OffLoader {
+    fn name(&self) -> &str {
+        "OFF"
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["off"]
+    }
+
+    // OFF, COFF, NOFF, STCNOFF, ...
+    fn sniff(&self, head: &[u8], _file_len: u64) -> bool {
+        let word = first_word(head);
+        word.ends_with(b"OFF") && word.iter().all(|b| b.is_ascii_uppercase())
+    }
+
+    fn load_from_bytes(
+        &self,
+        data: &[u8],
+        _resolver: &mut Resolver,
+    ) -> Result<Model, Box<dyn Error>> {
+        off::parse_off(str::from_utf8(data)?)
+    }
+}
+
+pub struct XyzLoader;
+
+impl Loader for XyzLoader {
+    fn name(&self) -> &str {
+        "XYZ"
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["xyz", "xyzrgb"]
+    }
+
+    fn load_from_bytes(
+        &self,
+        data: &[u8],
+        _resolver: &mut Resolver,
+    ) -> Result<Model, Box<dyn Error>> {
+        xyz::parse_xyz(str::from_utf8(data)?)
+    }
+}
+
+// picks the loader of a file by its content first and by its extension second
+pub struct LoaderRegistry {
+    loaders: Vec<Box<dyn Loader>>,
+}
+
+impl LoaderRegistry {
+    // registry with the loaders of every format this crate reads
+    pub fn new() -> LoaderRegistry {
+        let mut registry = LoaderRegistry::empty();
+        registry.register(Box::new(ObjLoader));
+        registry.register(Box::new(StlLoader));
+        registry.register(Box::new(PlyLoader));
+        registry.register(Box::new(GltfLoader));
+        registry.register(Box::new(OffLoader));
+        registry.register(Box::new(XyzLoader));
+        registry
+    }
+
+    pub fn empty() -> LoaderRegistry {
+        LoaderRegistry {
+            loaders: Vec::new(),
+        }
+    }
+
+    // loaders registered later take precedence, so applications can replace built-in ones
+    pub fn register(&mut self, loader: Box<dyn Loader>) {
+        self.loaders.insert(0, loader);
+    }
+
+    pub fn loaders(&self) -> impl Iterator<Item = &dyn Loader> {
+        self.loaders.iter().map(|loader| loader.as_ref())
+    }
+
+    pub fn find_by_extension(&self, path: &Path) -> Option<&dyn Loader> {
+        let ext = path.extension()?.to_string_lossy().to_lowercase();
+        self.loaders()
+            .find(|loader| loader.extensions().contains(&ext.as_str()))
+    }
+
+    pub fn find_by_content(&self, head: &[u8], file_len: u64) -> Option<&dyn Loader> {
+        self.loaders().find(|loader| loader.sniff(head, file_len))
+    }
+
+    // the path only gives the extension, the content is the head of the data
+    fn find_by_content_or_extension(
+        &self,
+        path: &Path,
+        head: &[u8],
+        file_len: u64,
+    ) -> Result<&dyn Loader, Box<dyn Error>> {
+        self.find_by_content(head, file_len)
+            .or_else(|| self.find_by_extension(path))
+            .ok_or_else(|| format!("unsupported model format: {}", path.display()).into())
+    }
+
+    pub fn find(&self, path: &Path) -> Result<&dyn Loader, Box<dyn Error>> {
+        let mut file = File::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let file_len = file.metadata()?.len();
+        let mut head = Vec::with_capacity(SNIFF_LEN);
+        file.by_ref()
+            .take(SNIFF_LEN as u64)
+            .read_to_end(&mut head)?;
+        self.find_by_content_or_extension(path, &head, file_len)
+    }
+
+    pub fn load(&self, path: &Path) -> Result<Model, Box<dyn Error>> {
+        self.load_with_progress(path, &Progress::new())
+    }
+
+    // gzip files are decompressed first, and a zip archive is loaded if it holds one model.
+    // progress is reported by the loader of the format
+    pub fn load_with_progress(
+        &self,
+        path: &Path,
+        progress: &Progress,
+    ) -> Result<Model, Box<dyn Error>> {
+        if archive::is_zip_file(path) {
+            let mut archive = ModelArchive::open(path)?;
+            let model = match archive.models(self).as_slice() {
+                [name] => archive.load(self, name)?,
+                [] => return Err(format!("archive has no models: {}", path.display()).into()),
+                names => {
+                    return Err(format!(
+                        "archive has several models, choose one of: {}",
+                        names.join(", ")
+                    )
+                    .into())
+                }
+            };
+            progress.add_faces(model.polygon_sizes.len() as u64);
+            return Ok(model);
+        }
+        match self.find(path) {
+            Ok(loader) => loader.load_with_progress(path, progress),
+            Err(e) => {
+                let data = progress::read_file(path, progress)?;
+                if !archive::is_gzip(&data) {
+                    return Err(e);
+                }
+                let dir = path.parent().unwrap_or_else(|| Path::new(""));
+                let model = self.load_from_bytes(path, &data, &mut dir_resolver(dir))?;
+                progress.add_faces(model.polygon_sizes.len() as u64);
+                Ok(model)
+            }
+        }
+    }
+
+    // load data which is not a file on disk, such as an archive entry. name is only used for
+    // its extension, and the files the model refers to are read through the resolver.
+    // gzip data is decompressed first
+    pub fn load_from_bytes(
+        &self,
+        name: &Path,
+        data: &[u8],
+        resolver: &mut Resolver,
+    ) -> Result<Model, Box<dyn Error>> {
+        if archive::is_gzip(data) {
+            let data = archive::gunzip(data)?;
+            return self.load_from_bytes(&archive::strip_gz(name), &data, resolver);
+        }
+        let head = &data[..data.len().min(SNIFF_LEN)];
+        self.find_by_content_or_extension(name, head, data.len() as u64)?
+            .load_from_bytes(data, resolver)
+    }
+}
+
+impl Default for LoaderRegistry {
+    fn default() -> LoaderRegistry {
+        LoaderRegistry::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    struct TestLoader;
+
+    impl Loader for TestLoader {
+        fn name(&self) -> &str {
+            "test"
+        }
+
+        fn extensions(&self) -> &[&str] {
+            &["obj", "test"]
+        }
+
+        fn load_from_bytes(
+            &self,
+            _data: &[u8],
+            _resolver: &mut Resolver,
+        ) -> Result<Model, Box<dyn Error>> {
+            let mut model = Model::new();
+            model.vertex = vec![1.0, 2.0, 3.0];
+            Ok(model)
+        }
+    }
+
+    #[test]
+    fn test_ok_find_by_content() {
+        let registry = LoaderRegistry::new();
+        let name = |head: &[u8], len: u64| registry.find_by_content(head, len).map(|l| l.name());
+        assert_eq!(Some("PLY"), name(b"ply\nformat ascii 1.0\n", 100));
+        assert_eq!(Some("glTF"), name(b"glTF\x02\x00\x00\x00", 100));
+        assert_eq!(Some("glTF"), name(b" {\n \"asset\": {}", 100));
+        assert_eq!(Some("OFF"), name(b"COFF\n3 1 0\n", 100));
+        assert_eq!(Some("STL"), name(b"solid a\n facet normal 0 0 1\n", 100));
+
+        // binary STL whose header starts with "solid"
+        let mut binary = b"solid".to_vec();
+        binary.resize(80, 0);
+        binary.extend_from_slice(&2u32.to_le_bytes());
+        assert_eq!(Some("STL"), name(&binary, 84 + 2 * 50));
+        assert_eq!(None, name(&binary, 84 + 2 * 50 + 1));
+        assert_eq!(None, name(b"v 0 0 0\n", 100));
+    }
+
+    #[test]
+    fn test_ok_find() {
+        let dir = std::env::temp_dir().join("obj_viewer_test_loader_find");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        // the content wins over a wrong extension
+        fs::write(
+            dir.join("points.obj"),
+            "ply\nformat ascii 1.0\nend_header\n",
+        )
+        .unwrap();
+        fs::write(dir.join("model.OBJ"), "v 0 0 0\n").unwrap();
+        fs::write(dir.join("model.unknown"), "v 0 0 0\n").unwrap();
+
+        let mut registry = LoaderRegistry::new();
+        assert_eq!(
+            "PLY",
+            registry.find(&dir.join("points.obj")).unwrap().name()
+        );
+        assert_eq!("OBJ", registry.find(&dir.join("model.OBJ")).unwrap().name());
+        assert!(registry.find(&dir.join("model.unknown")).is_err());
+        assert!(registry.find(&dir.join("missing.obj")).is_err());
+
+        // a registered loader replaces the built-in one for its extensions
+        registry.register(Box::new(TestLoader));
+        assert_eq!(
+            "test",
+            registry.find(&dir.join("model.OBJ")).unwrap().name()
+        );
+        let model = registry.load(&dir.join("model.OBJ")).unwrap();
+        assert_eq!(vec![1.0, 2.0, 3.0], model.vertex);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_ok_load_from_bytes() {
+        let registry = LoaderRegistry::new();
+        let mut resolver = |name: &str| match name {
+            "a.mtl" => Ok(b"newmtl red\nKd 1 0 0\n".to_vec()),
+            _ => Err(std::io::ErrorKind::NotFound.into()),
+        };
+        let obj = b"mtllib a.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n";
+        let model = registry
+            .load_from_bytes(Path::new("inline.obj"), obj, &mut resolver)
+            .unwrap();
+        assert_eq!(vec![3], model.polygon_sizes);
+        assert_eq!("red", model.materials[0].name);
+
+        let off = b"OFF\n3 1 0\n0 0 0\n1 0 0\n0 1 0\n3 0 1 2\n";
+        let model = registry
+            .load_from_bytes(Path::new("inline"), off, &mut resolver)
+            .unwrap();
+        assert_eq!(vec![3], model.polygon_sizes);
+        assert!(registry
+            .load_from_bytes(Path::new("inline"), obj, &mut resolver)
+            .is_err());
+    }
+}
@@ -1,12 +1,15 @@
+use std::env;
+use std::error::Error;
+use std::fs::File;
 use std::mem;
-use std::os::raw::c_void;
+use std::path::{Path, PathBuf};
 use std::process;
+use std::sync::Arc;
+use std::time::Instant;
 
-use c_str_macro::c_str;
 use cgmath::prelude::SquareMatrix;
 use chrono::Local;
 
-use gl::types::{GLfloat, GLsizei, GLsizeiptr};
 // use gl::UNSIGNED_INT_IMAGE_1D;
 use sdl2::event::Event;
 use sdl2::keyboard::Keycode;
@@ -20,19 +23,61 @@ type Vector3 = cgmath::Vector3<f32>;
 type Matrix4 = cgmath::Matrix4<f32>;
 
 use imgui::im_str;
+pub mod antialias;
+pub mod archive;
+pub mod background;
+pub mod bench;
+pub mod cache;
 pub mod camera;
+pub mod cli;
+pub mod convert;
 pub mod display;
+pub mod framebuffer;
+pub mod glsl;
+pub mod gltf;
+pub mod loader;
+pub mod material;
+pub mod mesh;
 pub mod model;
+pub mod obj_parallel;
+pub mod obj_writer;
+pub mod off;
+pub mod overlay;
+pub mod ply;
+pub mod progress;
 pub mod shader;
+pub mod shading;
+pub mod stats;
+pub mod stl;
+pub mod texture;
+pub mod thumbnail;
+pub mod uniform;
+pub mod uniform_editor;
 pub mod vertex;
+pub mod watcher;
+pub mod wireframe;
+pub mod xyz;
+use antialias::{Fxaa, SceneTarget};
+use archive::ModelArchive;
+use background::BackgroundLoad;
+use cache::PreparedModel;
 use camera::CameraState;
+use cli::{Command, ViewOptions};
 use display::DisplayState;
+use loader::LoaderRegistry;
+use mesh::{carry_visibility, Mesh};
 use model::Model;
-use shader::Shader;
-use vertex::Vertex;
+use overlay::Overlay;
+use shader::ShaderVariants;
+use shading::ShadingMode;
+use stats::ModelStats;
+use texture::Texture;
+use uniform::{CameraBlock, LightingBlock, UniformBuffer};
+use uniform_editor::UniformEditor;
+use watcher::FileWatcher;
+use wireframe::{Wireframe, WireframeMode};
 const INIT_WINDOW_WIDTH: u32 = 1200;
 const INIT_WINDOW_HEIGHT: u32 = 1080;
-const FLOAT_NUM: usize = 3;
 // const VERTEX_NUM: usize = 4;
 // const BUF_LEN: usize = FLOAT_NUM * VERTEX_NUM;
 
@@ -42,6 +87,52 @@ fn get_current_time() -> String {
     str_
 }
 fn main() {
+    let command = match cli::parse(env::args().skip(1)) {
+        Ok(command) => command,
+        Err(e) => {
+            eprintln!("{}\n{}", e, cli::USAGE);
+            process::exit(2);
+        }
+    };
+    match command {
+        Command::Help => println!("{}", cli::USAGE),
+        Command::View(options) => run_viewer(&options),
+        Command::Report(options) => match stats::run(&options) {
+            Ok(true) => {}
+            Ok(false) => process::exit(1),
+            Err(e) => {
+                eprintln!("Model error: {}", e);
+                process::exit(1);
+            }
+        },
+        Command::Convert(options) => {
+            if let Err(e) = convert::run(&options) {
+                eprintln!("Convert error: {}", e);
+                process::exit(1);
+            }
+        }
+        Command::Bench(options) => {
+            if let Err(e) = bench::run(&options) {
+                eprintln!("Bench error: {}", e);
+                process::exit(1);
+            }
+        }
+        Command::Thumbnail(options) => match thumbnail::run(&options) {
+            Ok(report) => {
+                if !report.failed.is_empty() {
+                    process::exit(1);
+                }
+            }
+            Err(e) => {
+                eprintln!("Thumbnail error: {}", e);
+                process::exit(1);
+            }
+        },
+    }
+}
+
+fn run_viewer(options: &ViewOptions) {
+    let path = options.path.as_path();
     let sdl_context = sdl2::init().unwrap();
     let video_subsystem = sdl_context.video().unwrap();
     println!("{} OK: init sdl2 video sub system", get_current_time());
@@ -51,6 +142,10 @@ fn main() {
         let gl_attr = video_subsystem.gl_attr();
         gl_attr.set_context_profile(sdl2::video::GLProfile::Core);
         gl_attr.set_context_version(3, 1);
+        if options.samples > 0 {
+            gl_attr.set_multisample_buffers(1);
+            gl_attr.set_multisample_samples(options.samples as u8);
+        }
         let (major, minor) = gl_attr.context_version();
         println!(
             "{} OK: init OpenGL: version={}.{}",
@@ -61,81 +156,142 @@ fn main() {
     }
 
     // create window
-    let window = video_subsystem
-        .window("title", INIT_WINDOW_WIDTH, INIT_WINDOW_HEIGHT)
-        .opengl()
-        .position_centered()
-        .resizable()
-        .build()
-        .unwrap();
+    let build_window = || {
+        video_subsystem
+            .window("title", INIT_WINDOW_WIDTH, INIT_WINDOW_HEIGHT)
+            .opengl()
+            .position_centered()
+            .resizable()
+            .build()
+    };
+    let window = match build_window() {
+        Ok(window) => window,
+        // without a multisampled window, MSAA is drawn offscreen
+        Err(e) if options.samples > 0 => {
+            println!(
+                "{} NG: build multisampled window: {}",
+                get_current_time(),
+                e
+            );
+            let gl_attr = video_subsystem.gl_attr();
+            gl_attr.set_multisample_buffers(0);
+            gl_attr.set_multisample_samples(0);
+            build_window().unwrap()
+        }
+        Err(e) => panic!("{}", e),
+    };
     println!("{} OK: build window", get_current_time());
 
     // set up opengl context
     let _gl_context = window.gl_create_context().unwrap();
     gl::load_with(|s| video_subsystem.gl_get_proc_address(s) as _);
     println!("{} OK: create opengl context", get_current_time());
+    let window_samples = antialias::framebuffer_samples();
+    let max_samples = antialias::max_samples();
+    println!(
+        "{} OK: multisampling: window={} max={}",
+        get_current_time(),
+        window_samples,
+        max_samples
+    );
 
-    // set up shaders
-    let mut shader = Shader::new();
-    shader.setup("rsc/shader/mono_shader.vs", "rsc/shader/mono_shader.fs");
-
-    #[rustfmt::skip]
-    let axis_array:[f32;12]=[
-        0.0,0.0,0.0,
-        1.0,0.0,0.0,
-        0.0,1.0,0.0,
-        0.0,0.0,1.0,
-    ];
-
-    #[rustfmt::skip]
-    let axis_indices: [i32; 6] = [
-        0, 1,
-        0, 2,
-        0, 3,
-    ];
-    let mut model = Model::new();
-    // /home/twmoca/Downloads/IronMan/IronMan.obj
-    // /home/twmoca/Documents/3d_obj/BaseSpiderMan.obj
-    // /home/twmoca/Downloads/uploads_files_2663635_Audi_RS_6_Avant.obj
-    // /home/twmoca/Downloads/RangeRoverSport/2016_Custom_Range_Rover_Sport.obj
-    // /home/twmoca/Documents/3d_obj/Enzo/Enzo.obj
-    if let Err(e) =
-        model.load("/home/twmoca/Documents/3d_obj/teapot.obj")
-    {
-        println!("Model error: {}", e);
-        process::exit(1);
+    // set up a program for each shading mode, built the first time the mode is used. each
+    // model uses the variant for its attributes. they are built again when their files
+    // change, and the last good program stays in use while they fail to build
+    let mut programs: Vec<ShaderVariants> = ShadingMode::ALL
+        .iter()
+        .map(|mode| {
+            let (vertex_path, fragment_path) = mode.shader_files();
+            ShaderVariants::new(vertex_path, fragment_path)
+        })
+        .collect();
+    let mut shader_defines = Vec::new();
+    let mut shader_error = None;
+    // axes, grid, bounding box and normals drawn over the model
+    let mut overlay = Overlay::new();
+    if let Err(e) = overlay.shaders.build(&[]) {
+        println!("Shader error:\n{}", e);
+        shader_error = Some(e);
     }
-    model.create_vertex_normal();
-    let vertex_buf = model.create_concat_vertex();
-
-    let buf_len = vertex_buf.len();
-    let vertex_num = model.vertex.len() / (FLOAT_NUM * 2);
-    println!("buf_len: {}", buf_len);
-    println!("vertex_num: {}", vertex_num);
-    
-    let mut vertex = Vertex::new(
-        (vertex_buf.len() * mem::size_of::<GLfloat>()) as GLsizeiptr,
-        vertex_buf.as_ptr() as *const c_void,
-        gl::STATIC_DRAW,
-        vec![gl::FLOAT, gl::FLOAT],
-        vec![FLOAT_NUM as i32, FLOAT_NUM as i32],
-        (FLOAT_NUM + FLOAT_NUM) as i32 * mem::size_of::<GLfloat>() as GLsizei,
-        vertex_num as i32,
-    );
-    vertex.setup_ibo(
-        model.indices.vertex_indices.len() as GLsizeiptr,
-        model.indices.vertex_indices.as_ptr() as *const c_void,
-    );
-    let mut axis_vertex = Vertex::new(
-        (12 * mem::size_of::<GLfloat>()) as GLsizeiptr,
-        axis_array.as_ptr() as *const c_void,
-        gl::STATIC_DRAW,
-        vec![gl::FLOAT],
-        vec![FLOAT_NUM as i32],
-        FLOAT_NUM as i32 * mem::size_of::<GLfloat>() as GLsizei,
-        4 as i32,
+    // smooth edges over the faces, or hidden lines
+    let mut wireframe = Wireframe::new();
+    if let Err(e) = wireframe.shaders.build(&[]) {
+        println!("Shader error:\n{}", e);
+        shader_error = Some(e);
+    }
+    // the scene is drawn offscreen for FXAA, or for MSAA which the window does not have
+    let mut fxaa = Fxaa::new();
+    if let Err(e) = fxaa.shaders.build(&[]) {
+        println!("Shader error:\n{}", e);
+        shader_error = Some(e);
+    }
+    let mut scene_target: Option<SceneTarget> = None;
+    let mut shader_watcher = FileWatcher::new(shader_files(&programs, &overlay, &wireframe, &fxaa));
+    // uniforms of each program which are edited in the "Uniforms" window
+    let mut uniform_editors: Vec<UniformEditor> = programs
+        .iter()
+        .map(|program| UniformEditor::load(&program.name()))
+        .collect();
+    // images for the matcap mode, by file name
+    let matcaps: Vec<(String, Texture)> = shading::matcap_files(Path::new(shading::MATCAP_DIR))
+        .iter()
+        .filter_map(|path| match Texture::open(path) {
+            Ok(texture) => Some((path.file_stem()?.to_string_lossy().into_owned(), texture)),
+            Err(e) => {
+                println!("Matcap error: {}", e);
+                None
+            }
+        })
+        .collect();
+    // per-frame data which all the programs read from uniform blocks
+    let camera_buffer = UniformBuffer::new(
+        uniform::CAMERA_BINDING,
+        &CameraBlock::new(&Matrix4::identity(), &Matrix4::identity(), (0.0, 0.0, 0.0)),
     );
-    axis_vertex.setup_ibo(6 as GLsizeiptr, axis_indices.as_ptr() as *const c_void);
+    let lighting_buffer = UniformBuffer::new(uniform::LIGHTING_BINDING, &LightingBlock::new());
+    let registry = Arc::new(LoaderRegistry::new());
+    // a zip archive stays open so that another model inside it can be chosen
+    let mut archive = None;
+    let mut archive_entry = String::new();
+    let cache_dir = cache::default_cache_dir();
+    // the window shows an empty model until the first one has been loaded
+    let PreparedModel {
+        mut model,
+        vertex_buffer,
+        ..
+    } = PreparedModel::new(Model::new());
+    let mut model_stats = ModelStats::new(&model);
+    let mut mesh = Mesh::from_model(&model, &vertex_buffer);
+    // the faces with a vertex per corner, uploaded for the shading modes which need it
+    let mut corner_mesh: Option<Mesh> = None;
+    // sub-meshes shown, all of them unless some are hidden in the "Groups" window
+    let mut visible_groups = vec![true; mesh.sub_meshes.len()];
+    // files are parsed on a loading thread, and archive entries right away. either way
+    // the model is uploaded here, on the thread of the OpenGL context
+    let mut loading = None;
+    let mut loaded = None;
+    // a model opened from a file is reloaded when it or the files it refers to change.
+    // a reload keeps the camera and the display settings
+    let mut watcher: Option<FileWatcher> = None;
+    let mut reloading = false;
+    // shown until the next model is loaded, while the window keeps the last good one
+    let mut load_error: Option<String> = None;
+    if archive::is_zip_file(path) {
+        match open_archive(&registry, path, &mut archive, &mut archive_entry) {
+            Ok(new_model) => loaded = Some(PreparedModel::new(new_model)),
+            Err(e) => {
+                println!("Model error: {}", e);
+                load_error = Some(e.to_string());
+            }
+        }
+    } else {
+        loading = Some(BackgroundLoad::start(
+            &registry,
+            path,
+            &cache_dir,
+            options.cache,
+        ));
+    }
 
     // init imgui
     let mut imgui_context = imgui::Context::create();
@@ -149,6 +305,10 @@ fn main() {
 
     let mut cam_state = CameraState::new((INIT_WINDOW_WIDTH, INIT_WINDOW_HEIGHT));
     let mut display_state=DisplayState::new((INIT_WINDOW_WIDTH,INIT_WINDOW_HEIGHT));
+    // models without faces are point clouds
+    display_state.is_enabled_points = model.polygon_sizes.is_empty();
+    display_state.msaa_samples = antialias::supported_samples(options.samples, max_samples);
+    display_state.is_enabled_fxaa = options.fxaa;
     // let mut alpha: f32 = 1.0f32;
     // let mut material_specular: Vector3 = Vector3 {
     //     x: 0.2,
@@ -193,14 +353,182 @@ fn main() {
                     keycode: Some(Keycode::Escape),
                     ..
                 } => break 'main,
+                // dropped files replace the model, which stays as it is if loading fails.
+                // a load still running is cancelled
+                Event::DropFile { filename, .. } => {
+                    let path = Path::new(&filename);
+                    reloading = false;
+                    if archive::is_zip_file(path) {
+                        loading = None;
+                        match open_archive(&registry, path, &mut archive, &mut archive_entry) {
+                            Ok(new_model) => {
+                                watcher = None;
+                                loaded = Some(PreparedModel::new(new_model));
+                            }
+                            Err(e) => {
+                                println!("Model error: {}", e);
+                                load_error = Some(e.to_string());
+                            }
+                        }
+                    } else {
+                        loading = Some(BackgroundLoad::start(
+                            &registry,
+                            path,
+                            &cache_dir,
+                            options.cache,
+                        ));
+                    }
+                }
                 _ => {}
             }
         }
 
+        if shader_watcher.poll(Instant::now()) {
+            let mut result = Ok(());
+            for program in programs
+                .iter_mut()
+                .chain(Some(&mut overlay.shaders))
+                .chain(Some(&mut wireframe.shaders))
+                .chain(Some(&mut fxaa.shaders))
+            {
+                let reloaded = program.reload();
+                if result.is_ok() {
+                    result = reloaded;
+                }
+            }
+            // an include may have been added or removed
+            shader_watcher = FileWatcher::new(shader_files(&programs, &overlay, &wireframe, &fxaa));
+            match result {
+                Ok(()) => {
+                    println!("{} OK: reload shaders", get_current_time());
+                    shader_error = None;
+                }
+                Err(e) => {
+                    println!("Shader error:\n{}", e);
+                    shader_error = Some(e);
+                }
+            }
+        }
+        if loading.is_none() && watcher.as_mut().is_some_and(|w| w.poll(Instant::now())) {
+            let path = watcher.as_ref().unwrap().path();
+            println!(
+                "{} OK: reload model: {}",
+                get_current_time(),
+                path.display()
+            );
+            loading = Some(BackgroundLoad::start(
+                &registry,
+                path,
+                &cache_dir,
+                options.cache,
+            ));
+            reloading = true;
+        }
+        if let Some(result) = loading.as_mut().and_then(|load| load.poll()) {
+            let load_path = loading.take().unwrap().path().to_path_buf();
+            match result {
+                Ok(new_model) => {
+                    archive = None;
+                    watcher = Some(FileWatcher::for_model(&load_path, &new_model.model));
+                    loaded = Some(new_model);
+                }
+                Err(e) if e == progress::CANCELLED => println!("Model error: {}", e),
+                // the last good model stays. a model which never loaded is watched by itself,
+                // so that fixing the file shows it
+                Err(e) => {
+                    println!("Model error: {}", e);
+                    load_error = Some(format!("{}: {}", load_path.display(), e));
+                    if watcher.is_none() && archive.is_none() {
+                        watcher = Some(FileWatcher::new(vec![load_path]));
+                    }
+                }
+            }
+        }
+        if let Some(prepared) = loaded.take() {
+            println!(
+                "{} OK: load model: {} faces{}",
+                get_current_time(),
+                prepared.model.polygon_sizes.len(),
+                if prepared.from_cache {
+                    " from the cache"
+                } else {
+                    ""
+                }
+            );
+            if let Some((min, max)) = prepared.bounds {
+                println!("{} OK: bounds: {:?} - {:?}", get_current_time(), min, max);
+            }
+            let before = mem::take(&mut mesh.sub_meshes);
+            mesh = Mesh::from_model(&prepared.model, &prepared.vertex_buffer);
+            overlay.set_model(&prepared.model);
+            wireframe.set_model();
+            corner_mesh = None;
+            // a reload keeps the sub-meshes which were hidden hidden
+            visible_groups = if reloading {
+                carry_visibility(&before, &visible_groups, &mesh.sub_meshes)
+            } else {
+                vec![true; mesh.sub_meshes.len()]
+            };
+            model = prepared.model;
+            model_stats = ModelStats::new(&model);
+            load_error = None;
+            shader_defines = shader::model_defines(&model);
+            if !reloading {
+                display_state.is_enabled_points = model.polygon_sizes.is_empty();
+            }
+            reloading = false;
+        }
+
+        // the program of the mode is built the first time it is used with these attributes
+        let shading_mode = display_state.shading_mode;
+        let program = &mut programs[shading_mode.index()];
+        if program.get(&shader_defines).is_none() {
+            shader_error = program.build(&shader_defines).err();
+            if let Some(e) = shader_error.as_ref() {
+                println!("Shader error:\n{}", e);
+            }
+            shader_watcher = FileWatcher::new(shader_files(&programs, &overlay, &wireframe, &fxaa));
+        }
+        let program = &programs[shading_mode.index()];
+
+        // the window has the samples it was made with, and other counts need a target of
+        // their own. it is made again when the window is resized
+        let offscreen = fxaa.is_built()
+            && (display_state.is_enabled_fxaa
+                || (display_state.msaa_samples > 0
+                    && display_state.msaa_samples != window_samples));
+        if offscreen {
+            let (width, height) = (
+                display_state.window_width.max(1),
+                display_state.window_height.max(1),
+            );
+            let samples = display_state.msaa_samples;
+            if !scene_target
+                .as_ref()
+                .is_some_and(|target| target.matches(width, height, samples))
+            {
+                scene_target = match SceneTarget::new(width, height, samples) {
+                    Ok(target) => Some(target),
+                    // the window is drawn into as it is
+                    Err(e) => {
+                        println!("Framebuffer error: {}", e);
+                        display_state.msaa_samples = window_samples;
+                        display_state.is_enabled_fxaa = false;
+                        None
+                    }
+                };
+            }
+        } else {
+            scene_target = None;
+        }
+
         unsafe {
             display_state.setup();
 
             gl::Viewport(0, 0, display_state.window_width as i32, display_state.window_height as i32);
+            if let Some(target) = scene_target.as_ref() {
+                target.bind();
+            }
 
             // clear screen
             gl::ClearColor(1.0, 1.0, 1.0, 1.0);
@@ -229,31 +557,72 @@ fn main() {
             let projection_matrix: Matrix4 = cam_state.get_perspective();
 
             // shader use matrices
-            shader.use_program();
-            shader.set_mat4(c_str!("uModel"), &model_matrix);
-            shader.set_mat4(c_str!("uView"), &view_matrix);
-            shader.set_mat4(c_str!("uProjection"), &projection_matrix);
-            shader.set_vec3(c_str!("uViewPosition"), cam_state.position.0, cam_state.position.1, cam_state.position.2);
-            // shader.set_float(c_str!("uAlpha"), alpha);
-            // shader.set_vector3(c_str!("uMaterial.specular"), &material_specular);
-            // shader.set_float(c_str!("uMaterial.shininess"), material_shininess);
-            // shader.set_vector3(c_str!("uLight.direction"), &light_direction);
-            // shader.set_vector3(c_str!("uLight.ambient"), &ambient);
-            // shader.set_vector3(c_str!("uLight.diffuse"), &diffuse);
-            // shader.set_vector3(c_str!("uLight.specular"), &specular);
-
-            // vertex.draw();
-            vertex.draw_elements(
-                gl::TRIANGLES,
-                model.indices.vertex_indices.len() as GLsizei,
-                model.indices.vertex_indices.as_ptr() as *const c_void,
-            );
-            // vertex.draw_elements2(&model.indices);
-            // axis_vertex.draw_elements(
-            //     gl::LINES,
-            //     6 as GLsizei,
-            //     axis_indices.as_ptr() as *const c_void,
-            // );
+            camera_buffer.update(&CameraBlock::new(
+                &view_matrix,
+                &projection_matrix,
+                cam_state.position,
+            ));
+            lighting_buffer.update(&LightingBlock::new());
+            if let Some(shader) = program.get(&shader_defines) {
+                shader.use_program();
+                shader.bind_block("Camera", camera_buffer.binding());
+                shader.bind_block("Lighting", lighting_buffer.binding());
+                shader.set_uniform("uModel", model_matrix);
+                uniform_editors[shading_mode.index()].apply(shader);
+                if shading_mode == ShadingMode::Matcap {
+                    if let Some((_, texture)) = matcaps.get(display_state.matcap) {
+                        texture.bind(0);
+                    }
+                    shader.set_uniform("uMatcap", 0);
+                }
+            }
+            // shader.set_float("uAlpha", alpha);
+            // shader.set_vector3("uMaterial.specular", &material_specular);
+            // shader.set_float("uMaterial.shininess", material_shininess);
+            // shader.set_vector3("uLight.direction", &light_direction);
+            // shader.set_vector3("uLight.ambient", &ambient);
+            // shader.set_vector3("uLight.diffuse", &diffuse);
+            // shader.set_vector3("uLight.specular", &specular);
+
+            let visible: Vec<usize> = (0..visible_groups.len())
+                .filter(|&i| visible_groups[i])
+                .collect();
+            let draws_wire =
+                display_state.wireframe_mode.draws_wire() && !display_state.is_enabled_points;
+            if draws_wire {
+                display_state.wireframe_mode.setup_faces();
+            }
+            if display_state.is_enabled_points {
+                mesh.vertex.draw();
+            } else if shading_mode.needs_corners() {
+                corner_mesh
+                    .get_or_insert_with(|| Mesh::corners(&model))
+                    .draw_sub_meshes(gl::TRIANGLES, visible.iter().copied());
+            } else {
+                mesh.draw_sub_meshes(gl::TRIANGLES, visible.iter().copied());
+            }
+            if draws_wire {
+                wireframe.draw(
+                    &model,
+                    &display_state,
+                    camera_buffer.binding(),
+                    visible.iter().copied(),
+                );
+            }
+            overlay.draw(&model, &display_state, camera_buffer.binding());
+
+            // the resolved scene is copied into the window, through FXAA when it is enabled
+            if let Some(target) = scene_target.as_ref() {
+                let scene = target.resolve();
+                scene.unbind();
+                gl::Viewport(
+                    0,
+                    0,
+                    display_state.window_width as i32,
+                    display_state.window_height as i32,
+                );
+                fxaa.apply(scene, display_state.is_enabled_fxaa);
+            }
 
             imgui_sdl2_context.prepare_frame(
                 imgui_context.io_mut(),
@@ -261,6 +630,18 @@ fn main() {
                 &event_pump.mouse_state(),
             );
             let ui = imgui_context.frame();
+            // the axis labels are drawn behind the windows, at the tips of the axes
+            if display_state.is_enabled_axes {
+                let view_projection = projection_matrix * view_matrix;
+                let draw_list = ui.get_background_draw_list();
+                for (label, tip, [r, g, b]) in overlay.axis_labels() {
+                    if let Some(position) =
+                        overlay::project(&view_projection, tip, ui.io().display_size)
+                    {
+                        draw_list.add_text(position, [r, g, b, 1.0], label);
+                    }
+                }
+            }
             imgui::Window::new(im_str!("Information"))
                 .size([300.0, 450.0], imgui::Condition::FirstUseEver)
                 .position([10.0, 10.0], imgui::Condition::FirstUseEver)
@@ -283,8 +664,120 @@ fn main() {
 
                     ui.checkbox(im_str!("Depth Test"),&mut display_state.is_enabled_depth_test);
                     ui.checkbox(im_str!("Blend"), &mut display_state.is_enabled_blend);
-                    ui.checkbox(im_str!("Wireframe"), &mut display_state.is_enabled_wireframe);
+                    let sample_counts: Vec<u32> = antialias::SAMPLE_COUNTS
+                        .iter()
+                        .copied()
+                        .filter(|&samples| samples <= max_samples)
+                        .collect();
+                    let mut samples_index = sample_counts
+                        .iter()
+                        .position(|&samples| samples == display_state.msaa_samples)
+                        .unwrap_or(0);
+                    let sample_labels: Vec<imgui::ImString> = sample_counts
+                        .iter()
+                        .map(|&samples| imgui::ImString::new(antialias::sample_label(samples)))
+                        .collect();
+                    let sample_labels: Vec<&imgui::ImString> = sample_labels.iter().collect();
+                    if imgui::ComboBox::new(im_str!("MSAA")).build_simple_string(
+                        &ui,
+                        &mut samples_index,
+                        &sample_labels,
+                    ) {
+                        display_state.msaa_samples = sample_counts[samples_index];
+                    }
+                    ui.checkbox(im_str!("FXAA"), &mut display_state.is_enabled_fxaa);
+                    let mut wireframe_index = WireframeMode::ALL
+                        .iter()
+                        .position(|&mode| mode == display_state.wireframe_mode)
+                        .unwrap_or(0);
+                    let wireframe_labels: Vec<imgui::ImString> = WireframeMode::ALL
+                        .iter()
+                        .map(|mode| imgui::ImString::new(mode.label()))
+                        .collect();
+                    let wireframe_labels: Vec<&imgui::ImString> = wireframe_labels.iter().collect();
+                    if imgui::ComboBox::new(im_str!("Wireframe")).build_simple_string(
+                        &ui,
+                        &mut wireframe_index,
+                        &wireframe_labels,
+                    ) {
+                        display_state.wireframe_mode = WireframeMode::ALL[wireframe_index];
+                    }
+                    if display_state.wireframe_mode.draws_wire() {
+                        ui.checkbox(
+                            im_str!("Polygon Edges"),
+                            &mut display_state.is_enabled_polygon_edges,
+                        );
+                        imgui::ColorEdit::new(im_str!("Wire Color"), &mut display_state.wire_color)
+                            .build(&ui);
+                        imgui::Slider::new(im_str!("Wire Width"))
+                            .range(0.5..=4.0)
+                            .build(&ui, &mut display_state.wire_width);
+                    }
                     ui.checkbox(im_str!("Culling"), &mut display_state.is_enabled_culling);
+                    ui.checkbox(im_str!("Points"), &mut display_state.is_enabled_points);
+                    imgui::Slider::new(im_str!("Point Size"))
+                        .range(1.0..=20.0)
+                        .build(&ui, &mut display_state.point_size);
+
+                    ui.checkbox(im_str!("Axes"), &mut display_state.is_enabled_axes);
+                    ui.checkbox(im_str!("Grid"), &mut display_state.is_enabled_grid);
+                    imgui::Slider::new(im_str!("Grid Cells"))
+                        .range(1..=100)
+                        .build(&ui, &mut display_state.grid_cells);
+                    ui.checkbox(
+                        im_str!("Bounding Box"),
+                        &mut display_state.is_enabled_bounding_box,
+                    );
+                    ui.checkbox(
+                        im_str!("Vertex Normals"),
+                        &mut display_state.is_enabled_vertex_normals,
+                    );
+                    ui.checkbox(
+                        im_str!("Face Normals"),
+                        &mut display_state.is_enabled_face_normals,
+                    );
+                    imgui::Slider::new(im_str!("Normal Length"))
+                        .range(0.001..=0.2)
+                        .build(&ui, &mut display_state.normal_length);
+
+                    ui.separator();
+                    let mut mode_index = shading_mode.index();
+                    let mode_labels: Vec<imgui::ImString> = ShadingMode::ALL
+                        .iter()
+                        .map(|mode| imgui::ImString::new(mode.label()))
+                        .collect();
+                    let mode_labels: Vec<&imgui::ImString> = mode_labels.iter().collect();
+                    if imgui::ComboBox::new(im_str!("Shading")).build_simple_string(
+                        &ui,
+                        &mut mode_index,
+                        &mode_labels,
+                    ) {
+                        display_state.shading_mode = ShadingMode::ALL[mode_index];
+                    }
+                    if shading_mode == ShadingMode::Matcap {
+                        if matcaps.is_empty() {
+                            ui.text(format!("No matcap images in {}", shading::MATCAP_DIR));
+                        } else {
+                            let names: Vec<imgui::ImString> = matcaps
+                                .iter()
+                                .map(|(name, _)| imgui::ImString::new(name.as_str()))
+                                .collect();
+                            let names: Vec<&imgui::ImString> = names.iter().collect();
+                            imgui::ComboBox::new(im_str!("Matcap")).build_simple_string(
+                                &ui,
+                                &mut display_state.matcap,
+                                &names,
+                            );
+                        }
+                    }
+
+                    if let Some(watcher) = watcher.as_ref() {
+                        ui.separator();
+                        ui.text("Watching for changes:");
+                        for file in watcher.files() {
+                            ui.text(file.display().to_string());
+                        }
+                    }
 
                     ui.separator();
                     imgui::Slider::new(im_str!("Camera X"))
@@ -299,11 +792,193 @@ fn main() {
                         .range(-5.0..=5.0)
                         .build(&ui, &mut cam_state.position.2);
                 });
+            imgui::Window::new(im_str!("Statistics"))
+                .size([300.0, 450.0], imgui::Condition::FirstUseEver)
+                .position([320.0, 10.0], imgui::Condition::FirstUseEver)
+                .build(&ui, || {
+                    for line in model_stats.report_lines() {
+                        ui.text(line);
+                    }
+                });
+            if mesh.sub_meshes.len() > 1 {
+                imgui::Window::new(im_str!("Groups"))
+                    .size([300.0, 300.0], imgui::Condition::FirstUseEver)
+                    .position([630.0, 430.0], imgui::Condition::FirstUseEver)
+                    .build(&ui, || {
+                        if ui.button(im_str!("Show All"), [0.0, 0.0]) {
+                            visible_groups
+                                .iter_mut()
+                                .for_each(|visible| *visible = true);
+                        }
+                        // show only the sub-meshes with the material of the one clicked
+                        let mut only_material = None;
+                        for (i, sub_mesh) in mesh.sub_meshes.iter().enumerate() {
+                            let material = sub_mesh.material.as_deref().unwrap_or("no material");
+                            // labels are made unique, as groups may share a name
+                            ui.checkbox(
+                                &imgui::ImString::new(format!(
+                                    "{} ({})##{}",
+                                    sub_mesh.name, material, i
+                                )),
+                                &mut visible_groups[i],
+                            );
+                            ui.same_line(0.0);
+                            if ui.small_button(&imgui::ImString::new(format!("Only##{}", i))) {
+                                only_material = Some(sub_mesh.material.as_deref());
+                            }
+                        }
+                        if let Some(material) = only_material {
+                            visible_groups
+                                .iter_mut()
+                                .for_each(|visible| *visible = false);
+                            for i in mesh.with_material(material) {
+                                visible_groups[i] = true;
+                            }
+                        }
+                    });
+            }
+            let mut cancel_loading = false;
+            if let Some(load) = loading.as_ref() {
+                imgui::Window::new(im_str!("Loading"))
+                    .size([400.0, 120.0], imgui::Condition::FirstUseEver)
+                    .position([10.0, 470.0], imgui::Condition::FirstUseEver)
+                    .build(&ui, || {
+                        let progress = load.progress();
+                        ui.text(load.path().display().to_string());
+                        let overlay = imgui::ImString::new(format!(
+                            "{:.1} / {:.1} MB",
+                            progress.bytes_read() as f64 / 1_000_000.0,
+                            progress.total_bytes() as f64 / 1_000_000.0
+                        ));
+                        imgui::ProgressBar::new(progress.fraction())
+                            .overlay_text(&overlay)
+                            .size([-1.0, 0.0])
+                            .build(&ui);
+                        ui.text(format!("Faces: {}", progress.faces()));
+                        cancel_loading = ui.button(im_str!("Cancel"), [0.0, 0.0]);
+                    });
+            }
+            let mut dismiss_error = false;
+            if let Some(error) = load_error.as_ref() {
+                imgui::Window::new(im_str!("Load Error"))
+                    .size([500.0, 100.0], imgui::Condition::FirstUseEver)
+                    .position([320.0, 470.0], imgui::Condition::FirstUseEver)
+                    .build(&ui, || {
+                        ui.text_colored([0.9, 0.1, 0.1, 1.0], error);
+                        dismiss_error = ui.button(im_str!("Dismiss"), [0.0, 0.0]);
+                    });
+            }
+            if let Some(shader) = program.get(&shader_defines) {
+                let uniform_editor = &mut uniform_editors[shading_mode.index()];
+                imgui::Window::new(im_str!("Uniforms"))
+                    .size([300.0, 200.0], imgui::Condition::FirstUseEver)
+                    .position([630.0, 220.0], imgui::Condition::FirstUseEver)
+                    .build(&ui, || uniform_editor.build_ui(&ui, shader));
+            }
+            // uniforms which could not be set are shown along with the build errors
+            let shader_warnings = program
+                .get(&shader_defines)
+                .map(|shader| shader.warnings())
+                .unwrap_or_default();
+            if shader_error.is_some() || !shader_warnings.is_empty() {
+                imgui::Window::new(im_str!("Shader Errors"))
+                    .size([500.0, 200.0], imgui::Condition::FirstUseEver)
+                    .position([320.0, 580.0], imgui::Condition::FirstUseEver)
+                    .build(&ui, || {
+                        for message in shader_error.iter().flat_map(|error| error.messages.iter()) {
+                            ui.text_colored([0.9, 0.1, 0.1, 1.0], message.to_string());
+                        }
+                        for warning in shader_warnings.iter() {
+                            ui.text_colored([0.9, 0.6, 0.1, 1.0], warning);
+                        }
+                    });
+            }
+            let mut chosen_entry = None;
+            if let Some(archive) = archive.as_ref() {
+                imgui::Window::new(im_str!("Archive"))
+                    .size([300.0, 200.0], imgui::Condition::FirstUseEver)
+                    .position([630.0, 10.0], imgui::Condition::FirstUseEver)
+                    .build(&ui, || {
+                        for name in archive.models(&registry) {
+                            let selected = name == archive_entry;
+                            if imgui::Selectable::new(&imgui::ImString::new(name.as_str()))
+                                .selected(selected)
+                                .build(&ui)
+                                && !selected
+                            {
+                                chosen_entry = Some(name);
+                            }
+                        }
+                    });
+            }
             imgui_sdl2_context.prepare_render(&ui, &window);
             renderer.render(ui);
 
+            // a dropped load stops on its thread
+            if cancel_loading {
+                loading = None;
+            }
+            if dismiss_error {
+                load_error = None;
+            }
+            // the model stays as it is if the chosen one fails to load
+            if let (Some(name), Some(archive)) = (chosen_entry, archive.as_mut()) {
+                match archive.load(&registry, &name) {
+                    Ok(new_model) => {
+                        loading = None;
+                        reloading = false;
+                        loaded = Some(PreparedModel::new(new_model));
+                        archive_entry = name;
+                    }
+                    Err(e) => {
+                        println!("Model error: {}", e);
+                        load_error = Some(e.to_string());
+                    }
+                }
+            }
+
             window.gl_swap_window();
         }
         // ::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 60))
     }
 }
+
+// load the first model of a zip archive, which is kept open in `archive`
+fn open_archive(
+    registry: &LoaderRegistry,
+    path: &Path,
+    archive: &mut Option<ModelArchive<File>>,
+    archive_entry: &mut String,
+) -> Result<Model, Box<dyn Error>> {
+    let mut new_archive = ModelArchive::open(path)?;
+    let name = new_archive
+        .models(registry)
+        .into_iter()
+        .next()
+        .ok_or_else(|| format!("archive has no models: {}", path.display()))?;
+    let model = new_archive.load(registry, &name)?;
+    *archive = Some(new_archive);
+    *archive_entry = name;
+    Ok(model)
+}
+
+// the files of all the programs, which are watched together
+fn shader_files(
+    programs: &[ShaderVariants],
+    overlay: &Overlay,
+    wireframe: &Wireframe,
+    fxaa: &Fxaa,
+) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let programs = programs
+        .iter()
+        .chain(Some(&overlay.shaders))
+        .chain(Some(&wireframe.shaders))
+        .chain(Some(&fxaa.shaders));
+    for file in programs.flat_map(|program| program.files()) {
+        if !files.contains(&file) {
+            files.push(file);
+        }
+    }
+    files
+}
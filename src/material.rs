@@ -0,0 +1,394 @@
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Component, Path, PathBuf};
+
+use crate::obj_writer;
+
+// keys of the texture maps as they are written
+pub const MAP_KEYS: [&str; 6] = ["map_Ka", "map_Kd", "map_Ks", "map_Ns", "map_d", "map_Bump"];
+
+// material of a .mtl file. unset values are left as None so that they are not written back
+#[derive(Clone, Debug, PartialEq)]
+pub struct Material {
+    pub name: String,
+    pub ambient: Option<[f32; 3]>,
+    pub diffuse: Option<[f32; 3]>,
+    pub specular: Option<[f32; 3]>,
+    pub emissive: Option<[f32; 3]>,
+    pub shininess: Option<f32>,
+    pub optical_density: Option<f32>,
+    pub dissolve: Option<f32>,
+    pub illumination: Option<u32>,
+    // PBR extension of MTL (Pm, Pr)
+    pub metallic: Option<f32>,
+    pub roughness: Option<f32>,
+    pub ambient_map: Option<String>,
+    pub diffuse_map: Option<String>,
+    pub specular_map: Option<String>,
+    pub shininess_map: Option<String>,
+    pub dissolve_map: Option<String>,
+    pub bump_map: Option<String>,
+    // options written before the file name of a map, such as "-s 1 1 1", by its key in
+    // MAP_KEYS
+    pub map_options: Vec<(String, String)>,
+}
+
+impl Material {
+    pub fn new(name: &str) -> Material {
+        Material {
+            name: name.to_string(),
+            ambient: None,
+            diffuse: None,
+            specular: None,
+            emissive: None,
+            shininess: None,
+            optical_density: None,
+            dissolve: None,
+            illumination: None,
+            metallic: None,
+            roughness: None,
+            ambient_map: None,
+            diffuse_map: None,
+            specular_map: None,
+            shininess_map: None,
+            dissolve_map: None,
+            bump_map: None,
+            map_options: Vec::new(),
+        }
+    }
+
+    // the texture maps in the order of MAP_KEYS
+    pub fn maps(&self) -> [&Option<String>; 6] {
+        [
+            &self.ambient_map,
+            &self.diffuse_map,
+            &self.specular_map,
+            &self.shininess_map,
+            &self.dissolve_map,
+            &self.bump_map,
+        ]
+    }
+
+    pub fn maps_mut(&mut self) -> [&mut Option<String>; 6] {
+        [
+            &mut self.ambient_map,
+            &mut self.diffuse_map,
+            &mut self.specular_map,
+            &mut self.shininess_map,
+            &mut self.dissolve_map,
+            &mut self.bump_map,
+        ]
+    }
+
+    pub fn map_options(&self, key: &str) -> Option<&str> {
+        self.map_options
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, options)| options.as_str())
+    }
+
+    // set the map of the key in MAP_KEYS with its options
+    fn set_map(&mut self, key: &str, map: Option<(String, String)>) {
+        let index = match MAP_KEYS.iter().position(|&k| k == key) {
+            Some(index) => index,
+            None => return,
+        };
+        self.map_options.retain(|(k, _)| k != key);
+        if let Some((options, _)) = map.as_ref().filter(|(options, _)| !options.is_empty()) {
+            self.map_options.push((key.to_string(), options.clone()));
+        }
+        *self.maps_mut()[index] = map.map(|(_, file)| file);
+    }
+}
+
+fn read_color(ss: &[&str], counter: usize) -> Result<[f32; 3], Box<dyn Error>> {
+    let invalid = || format!("Material color is invalid. Line number:{}", counter);
+    let mut color = [0.0; 3];
+    for (i, value) in color.iter_mut().enumerate() {
+        *value = ss
+            .get(i + 1)
+            .ok_or_else(invalid)?
+            .parse()
+            .map_err(|_| invalid())?;
+    }
+    Ok(color)
+}
+
+fn read_float(ss: &[&str], counter: usize) -> Result<f32, Box<dyn Error>> {
+    ss.get(1)
+        .and_then(|s| s.parse().ok())
+        .ok_or_else(|| format!("Material value is invalid. Line number:{}", counter).into())
+}
+
+// number of values an option of a texture map takes. -o, -s and -t take 1 to 3
+fn map_option_values(option: &str) -> Option<usize> {
+    match option {
+        "-o" | "-s" | "-t" => Some(3),
+        "-mm" => Some(2),
+        "-blendu" | "-blendv" | "-boost" | "-cc" | "-clamp" | "-bm" | "-imfchan" | "-texres"
+        | "-type" => Some(1),
+        _ => None,
+    }
+}
+
+// texture maps may have options before the file name, which may have spaces in it.
+// returns the options and the file name
+fn read_map(ss: &[&str]) -> Option<(String, String)> {
+    let mut i = 1;
+    while let Some(values) = ss.get(i).and_then(|option| map_option_values(option)) {
+        let optional = matches!(ss[i], "-o" | "-s" | "-t");
+        i += 1;
+        if !optional {
+            i = (i + values).min(ss.len());
+            continue;
+        }
+        // the values are numbers, and the file name comes after them
+        for _ in 0..values {
+            if i + 1 >= ss.len() || ss[i].parse::<f32>().is_err() {
+                break;
+            }
+            i += 1;
+        }
+    }
+    if i >= ss.len() {
+        return None;
+    }
+    Some((ss[1..i].join(" "), ss[i..].join(" ")))
+}
+
+pub fn parse_mtl<R: BufRead>(reader: R) -> Result<Vec<Material>, Box<dyn Error>> {
+    let mut materials: Vec<Material> = Vec::new();
+    for (i, line) in reader.lines().enumerate() {
+        let counter = i + 1;
+        let str = line?;
+        let ss: Vec<&str> = str.split_whitespace().collect();
+        if ss.is_empty() {
+            continue;
+        }
+        if ss[0] == "newmtl" {
+            materials.push(Material::new(&ss[1..].join(" ")));
+            continue;
+        }
+        let material = match materials.last_mut() {
+            Some(material) => material,
+            None => continue,
+        };
+        match ss[0] {
+            "Ka" => material.ambient = Some(read_color(&ss, counter)?),
+            "Kd" => material.diffuse = Some(read_color(&ss, counter)?),
+            "Ks" => material.specular = Some(read_color(&ss, counter)?),
+            "Ke" => material.emissive = Some(read_color(&ss, counter)?),
+            "Ns" => material.shininess = Some(read_float(&ss, counter)?),
+            "Ni" => material.optical_density = Some(read_float(&ss, counter)?),
+            "d" => material.dissolve = Some(read_float(&ss, counter)?),
+            "Tr" => material.dissolve = Some(1.0 - read_float(&ss, counter)?),
+            "illum" => material.illumination = Some(read_float(&ss, counter)? as u32),
+            "Pm" => material.metallic = Some(read_float(&ss, counter)?),
+            "Pr" => material.roughness = Some(read_float(&ss, counter)?),
+            "map_Ka" | "map_Kd" | "map_Ks" | "map_Ns" | "map_d" => {
+                material.set_map(ss[0], read_map(&ss))
+            }
+            "map_Bump" | "map_bump" | "bump" => material.set_map("map_Bump", read_map(&ss)),
+            _ => {}
+        }
+    }
+    Ok(materials)
+}
+
+pub fn load_mtl(path: &str) -> Result<Vec<Material>, Box<dyn Error>> {
+    let file = File::open(path)?;
+    parse_mtl(BufReader::new(file))
+}
+
+pub fn write_mtl<W: Write>(
+    writer: &mut W,
+    materials: &[Material],
+    precision: Option<usize>,
+) -> Result<(), Box<dyn Error>> {
+    let format = |v: f32| obj_writer::format_value(v, precision);
+    for material in materials.iter() {
+        writeln!(writer, "newmtl {}", material.name)?;
+        let colors = [
+            ("Ka", material.ambient),
+            ("Kd", material.diffuse),
+            ("Ks", material.specular),
+            ("Ke", material.emissive),
+        ];
+        for (key, color) in colors.iter() {
+            if let Some(c) = color {
+                writeln!(
+                    writer,
+                    "{} {} {} {}",
+                    key,
+                    format(c[0]),
+                    format(c[1]),
+                    format(c[2])
+                )?;
+            }
+        }
+        let values = [
+            ("Ns", material.shininess),
+            ("Ni", material.optical_density),
+            ("d", material.dissolve),
+            ("Pm", material.metallic),
+            ("Pr", material.roughness),
+        ];
+        for (key, value) in values.iter() {
+            if let Some(v) = value {
+                writeln!(writer, "{} {}", key, format(*v))?;
+            }
+        }
+        if let Some(illumination) = material.illumination {
+            writeln!(writer, "illum {}", illumination)?;
+        }
+        for (key, map) in MAP_KEYS.iter().zip(material.maps().iter()) {
+            if let Some(map) = map {
+                match material.map_options(key) {
+                    Some(options) => writeln!(writer, "{} {} {}", key, options, map)?,
+                    None => writeln!(writer, "{} {}", key, map)?,
+                }
+            }
+        }
+        writeln!(writer)?;
+    }
+    Ok(())
+}
+
+// the path of a texture map for a model written into out_dir, when the map is relative to
+// texture_dir. maps which are not found keep their path
+pub fn rebase_map(map: &str, texture_dir: &Path, out_dir: &Path) -> String {
+    let file = match fs::canonicalize(texture_dir.join(map)) {
+        Ok(file) => file,
+        Err(_) => return map.to_string(),
+    };
+    let out_dir = if out_dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        out_dir
+    };
+    let out_dir = match fs::canonicalize(out_dir) {
+        Ok(out_dir) => out_dir,
+        Err(_) => return file.to_string_lossy().into_owned(),
+    };
+    let common = file
+        .components()
+        .zip(out_dir.components())
+        .take_while(|(a, b)| a == b)
+        .count();
+    // paths on other drives have nothing in common
+    if common == 0 {
+        return file.to_string_lossy().into_owned();
+    }
+    let mut relative = PathBuf::new();
+    for _ in out_dir.components().skip(common) {
+        relative.push(Component::ParentDir);
+    }
+    relative.extend(file.components().skip(common));
+    // MTL files and URIs separate directories with slashes
+    relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ok_parse_mtl() {
+        let mtl = "\
+# comment
+newmtl red paint
+Ka 0.1 0.1 0.1
+Kd 1.0 0.0 0.0
+Ns 10
+Tr 0.25
+Pr 0.5
+illum 2
+map_Kd -s 1 1 1 textures/red.png
+
+newmtl glass
+d 0.5
+";
+        let materials = parse_mtl(mtl.as_bytes()).unwrap();
+        assert_eq!(2, materials.len());
+        let red = &materials[0];
+        assert_eq!("red paint", red.name);
+        assert_eq!(Some([0.1, 0.1, 0.1]), red.ambient);
+        assert_eq!(Some([1.0, 0.0, 0.0]), red.diffuse);
+        assert_eq!(None, red.specular);
+        assert_eq!(Some(10.0), red.shininess);
+        assert_eq!(Some(0.75), red.dissolve);
+        assert_eq!(Some(0.5), red.roughness);
+        assert_eq!(None, red.metallic);
+        assert_eq!(Some(2), red.illumination);
+        assert_eq!(Some("textures/red.png".to_string()), red.diffuse_map);
+        assert_eq!(Some("-s 1 1 1"), red.map_options("map_Kd"));
+        assert_eq!(Some(0.5), materials[1].dissolve);
+    }
+
+    #[test]
+    fn test_ng_parse_mtl() {
+        assert!(parse_mtl("newmtl a\nKd 1 0\n".as_bytes()).is_err());
+        assert!(parse_mtl("newmtl a\nNs x\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_ok_write_mtl() {
+        let mut material = Material::new("red");
+        material.diffuse = Some([1.0, 0.5, 0.0]);
+        material.dissolve = Some(0.5);
+        material.illumination = Some(2);
+        material.diffuse_map = Some("red.png".to_string());
+        let mut buf = Vec::new();
+        write_mtl(&mut buf, &[material.clone()], Some(3)).unwrap();
+
+        let expected = "newmtl red\nKd 1 0.5 0\nd 0.5\nillum 2\nmap_Kd red.png\n\n";
+        assert_eq!(expected, String::from_utf8(buf.clone()).unwrap());
+        assert_eq!(vec![material], parse_mtl(&buf[..]).unwrap());
+    }
+
+    #[test]
+    fn test_ok_map_options() {
+        let mtl = "\
+newmtl a
+map_Kd my texture.png
+map_Ks -s 2 spec.png
+map_d -clamp on
+bump -bm 0.5 -o 0.1 0.2 bump map.png
+";
+        let materials = parse_mtl(mtl.as_bytes()).unwrap();
+        let a = &materials[0];
+        assert_eq!(Some("my texture.png".to_string()), a.diffuse_map);
+        assert_eq!(None, a.map_options("map_Kd"));
+        assert_eq!(Some("bump map.png".to_string()), a.bump_map);
+        assert_eq!(Some("-bm 0.5 -o 0.1 0.2"), a.map_options("map_Bump"));
+        assert_eq!(Some("spec.png".to_string()), a.specular_map);
+        // an option without a file name after it is no map
+        assert_eq!(None, a.dissolve_map);
+
+        let mut buf = Vec::new();
+        write_mtl(&mut buf, &materials, None).unwrap();
+        let written = String::from_utf8(buf.clone()).unwrap();
+        assert!(written.contains("map_Bump -bm 0.5 -o 0.1 0.2 bump map.png\n"));
+        assert_eq!(materials, parse_mtl(&buf[..]).unwrap());
+    }
+
+    #[test]
+    fn test_ok_rebase_map() {
+        let dir = std::env::temp_dir().join("obj_viewer_test_rebase_map");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("a/tex")).unwrap();
+        fs::create_dir_all(dir.join("b")).unwrap();
+        fs::write(dir.join("a/tex/red.png"), b"png").unwrap();
+        let (a, b) = (dir.join("a"), dir.join("b"));
+        assert_eq!("../a/tex/red.png", rebase_map("tex/red.png", &a, &b));
+        assert_eq!("tex/red.png", rebase_map("tex/red.png", &a, &a));
+        assert_eq!("red.png", rebase_map("tex/red.png", &a, &a.join("tex")));
+        assert_eq!("missing.png", rebase_map("missing.png", &a, &b));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,202 @@
+use gl::types::GLenum;
+
+use crate::model::Model;
+use crate::shading;
+use crate::vertex::{Vertex, VertexLayout};
+
+// a part of the faces which is drawn on its own, such as a group of the model
+#[derive(Clone, Debug, PartialEq)]
+pub struct SubMesh {
+    pub name: String,
+    pub material: Option<String>,
+    // range in the indices, or in the vertices of a mesh without indices
+    pub first: usize,
+    pub count: usize,
+}
+
+// the groups of a model as sub-meshes. a model without groups is one sub-mesh
+pub fn sub_meshes(model: &Model) -> Vec<SubMesh> {
+    let index_num = model.indices.vertex_indices.len();
+    if model.groups.is_empty() {
+        return vec![SubMesh {
+            name: "default".to_string(),
+            material: None,
+            first: 0,
+            count: index_num,
+        }];
+    }
+    model
+        .groups
+        .iter()
+        .filter(|group| group.index_count > 0)
+        .map(|group| SubMesh {
+            name: group.name.clone(),
+            material: group.material.clone(),
+            first: group.index_start,
+            count: group.index_count,
+        })
+        .collect()
+}
+
+// ranges of the chosen sub-meshes, with the ones which follow each other joined
+pub fn merge_ranges<'a>(sub_meshes: impl Iterator<Item = &'a SubMesh>) -> Vec<(usize, usize)> {
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for sub_mesh in sub_meshes {
+        match ranges.last_mut() {
+            Some((first, count)) if *first + *count == sub_mesh.first => *count += sub_mesh.count,
+            _ => ranges.push((sub_mesh.first, sub_mesh.count)),
+        }
+    }
+    ranges
+}
+
+// the visibility of the sub-meshes of a reloaded model, kept from the sub-meshes before with
+// the same name and material. new ones are visible
+pub fn carry_visibility(before: &[SubMesh], visible: &[bool], after: &[SubMesh]) -> Vec<bool> {
+    let mut used = vec![false; before.len()];
+    after
+        .iter()
+        .map(|sub_mesh| {
+            let same = (0..before.len()).find(|&i| {
+                !used[i]
+                    && before[i].name == sub_mesh.name
+                    && before[i].material == sub_mesh.material
+            });
+            match same {
+                Some(i) => {
+                    used[i] = true;
+                    visible.get(i).copied().unwrap_or(true)
+                }
+                None => true,
+            }
+        })
+        .collect()
+}
+
+// a vertex array with the sub-meshes of its faces
+pub struct Mesh {
+    pub vertex: Vertex,
+    pub sub_meshes: Vec<SubMesh>,
+}
+
+impl Mesh {
+    pub fn new(vertex: Vertex, sub_meshes: Vec<SubMesh>) -> Mesh {
+        Mesh { vertex, sub_meshes }
+    }
+
+    // the concatenated vertex buffer of a model with its index buffer
+    pub fn from_model(model: &Model, vertex_buf: &[f32]) -> Mesh {
+        let layout = VertexLayout::floats(&[3, 3, 3]);
+        let mut vertex = Vertex::from_slice(vertex_buf, &layout, gl::STATIC_DRAW);
+        vertex.set_compact_indices(&model.indices.vertex_indices);
+        Mesh::new(vertex, sub_meshes(model))
+    }
+
+    // the faces with a vertex per corner, which has the same sub-meshes as the index buffer
+    pub fn corners(model: &Model) -> Mesh {
+        let corner_buf = shading::corner_buffer(model);
+        let layout = VertexLayout::floats(&[3, 3, 3, 2, 1]);
+        let vertex = Vertex::from_slice(&corner_buf, &layout, gl::STATIC_DRAW);
+        Mesh::new(vertex, sub_meshes(model))
+    }
+
+    // sub-meshes with the material
+    pub fn with_material<'a>(
+        &'a self,
+        material: Option<&'a str>,
+    ) -> impl Iterator<Item = usize> + 'a {
+        self.sub_meshes
+            .iter()
+            .enumerate()
+            .filter(move |(_, sub_mesh)| sub_mesh.material.as_deref() == material)
+            .map(|(i, _)| i)
+    }
+
+    // draw all the faces
+    pub fn draw(&self, mode: GLenum) {
+        self.draw_sub_meshes(mode, 0..self.sub_meshes.len());
+    }
+
+    // draw the sub-meshes at the indices, in as few ranges as they can be joined into
+    pub fn draw_sub_meshes(&self, mode: GLenum, indices: impl Iterator<Item = usize>) {
+        let ranges = merge_ranges(indices.filter_map(|i| self.sub_meshes.get(i)));
+        self.vertex.draw_ranges(mode, &ranges);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::Group;
+
+    fn sub_mesh(first: usize, count: usize) -> SubMesh {
+        SubMesh {
+            name: String::new(),
+            material: None,
+            first,
+            count,
+        }
+    }
+
+    #[test]
+    fn test_ok_sub_meshes() {
+        let mut model = Model::new();
+        model.indices.vertex_indices = vec![0; 9];
+        assert_eq!(vec![(0, 9)], merge_ranges(sub_meshes(&model).iter()));
+
+        for (i, count) in [3, 0, 6].iter().enumerate() {
+            model.groups.push(Group {
+                name: format!("g{}", i),
+                object: String::new(),
+                material: Some("red".to_string()),
+                smoothing_group: 0,
+                face_start: 0,
+                face_count: count / 3,
+                index_start: if i == 0 { 0 } else { 3 },
+                index_count: *count,
+            });
+        }
+        // groups without faces are left out
+        let sub_meshes = sub_meshes(&model);
+        assert_eq!(
+            vec!["g0", "g2"],
+            sub_meshes.iter().map(|s| &s.name[..]).collect::<Vec<_>>()
+        );
+        assert_eq!((3, 6), (sub_meshes[1].first, sub_meshes[1].count));
+    }
+
+    #[test]
+    fn test_ok_merge_ranges() {
+        let sub_meshes = [
+            sub_mesh(0, 3),
+            sub_mesh(3, 6),
+            sub_mesh(9, 3),
+            sub_mesh(12, 3),
+        ];
+        assert_eq!(vec![(0, 15)], merge_ranges(sub_meshes.iter()));
+        let chosen = [0, 2, 3].iter().map(|&i| &sub_meshes[i]);
+        assert_eq!(vec![(0, 3), (9, 6)], merge_ranges(chosen));
+        assert!(merge_ranges(sub_meshes[..0].iter()).is_empty());
+    }
+
+    #[test]
+    fn test_ok_carry_visibility() {
+        let named = |name: &str, material: &str| SubMesh {
+            name: name.to_string(),
+            material: Some(material.to_string()),
+            first: 0,
+            count: 3,
+        };
+        let before = [named("a", "red"), named("b", "red"), named("b", "blue")];
+        let after = [
+            named("b", "blue"),
+            named("c", "red"),
+            named("a", "red"),
+            named("b", "green"),
+        ];
+        assert_eq!(
+            vec![false, true, true, true],
+            carry_visibility(&before, &[true, false, false], &after)
+        );
+    }
+}
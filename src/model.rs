@@ -1,44 +1,44 @@
 use std::error::Error;
-use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::Path;
 
-enum ObjAttribute {
+use memmap2::Mmap;
+
+use crate::material::{self, Material};
+use crate::obj_parallel;
+use crate::progress::Progress;
+
+pub enum ObjAttribute {
     VertexPos,
     TexturePos,
     VertexNormal,
     PolygonIndex,
     MaterialName,
+    UseMaterial,
     ObjectName,
+    GroupName,
+    SmoothingGroup,
     Comment,
     Other,
 }
 
-fn detect_obj_attribute(str_: &str) -> ObjAttribute {
+pub fn detect_obj_attribute(str_: &str) -> ObjAttribute {
     match str_ {
         "v" => ObjAttribute::VertexPos,
         "vt" => ObjAttribute::TexturePos,
         "vn" => ObjAttribute::VertexNormal,
         "f" => ObjAttribute::PolygonIndex,
         "mtllib" => ObjAttribute::MaterialName,
+        "usemtl" => ObjAttribute::UseMaterial,
         "o" => ObjAttribute::ObjectName,
+        "g" => ObjAttribute::GroupName,
+        "s" => ObjAttribute::SmoothingGroup,
         "#" => ObjAttribute::Comment,
         _ => ObjAttribute::Other,
     }
 }
 
-enum LoadError{
-    OpenFileFailed,
-    ReadError,
-}
-
-enum Polygon {
-    Triangle = 3,
-    Quadrangle = 4,
-    Pentagon = 5,
-    Hexagon = 6,
-    Polygon,
-}
-
 fn read_one_line(
     input_str: &Vec<&str>,
     out_buf: &mut Vec<f32>,
@@ -57,6 +57,59 @@ fn read_one_line(
     Ok(())
 }
 
+// read the first `count` values of a line and ignore optional ones such as the w of "vt u v w"
+fn read_values(
+    input_str: &Vec<&str>,
+    out_buf: &mut Vec<f32>,
+    count: usize,
+    error_msg: &str,
+    error_pos: i32,
+) -> Result<(), Box<dyn Error>> {
+    let start = out_buf.len();
+    let result = read_one_line(input_str, out_buf, error_msg, error_pos);
+    if result.is_err() || out_buf.len() - start < count {
+        out_buf.truncate(start);
+        let error_msg = format!("{} Error line number:{}", error_msg, error_pos);
+        return Err(error_msg.into());
+    }
+    out_buf.truncate(start + count);
+    Ok(())
+}
+
+// the rest of a line such as "g left arm", or None if it is empty
+fn read_name(input_str: &[&str]) -> Option<String> {
+    if input_str.len() < 2 {
+        return None;
+    }
+    Some(input_str[1..].join(" "))
+}
+
+// supplies the files a model refers to, such as mtllib files and textures, by the
+// relative name written in the model. a missing file is an io::ErrorKind::NotFound error
+pub type Resolver<'a> = dyn FnMut(&str) -> io::Result<Vec<u8>> + 'a;
+
+// file data of a texture by the name its map refers to
+pub type TextureData = (String, Vec<u8>);
+
+// resolver for files next to a model on disk
+pub fn dir_resolver(dir: &Path) -> impl FnMut(&str) -> io::Result<Vec<u8>> {
+    let dir = dir.to_path_buf();
+    move |name| {
+        let path = dir.join(name);
+        fs::read(&path).map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))
+    }
+}
+
+// vertex colors of point formats, which are either 0..1 floats or 0..255 integers, as 0..1
+pub fn unit_colors(colors: &[f32]) -> Vec<f32> {
+    let scale = if colors.iter().any(|c| *c > 1.0) {
+        1.0 / 255.0
+    } else {
+        1.0
+    };
+    colors.iter().map(|c| c * scale).collect()
+}
+
 pub struct Indices {
     pub vertex_indices: Vec<u32>,
     pub texture_indices: Vec<u32>,
@@ -74,12 +127,96 @@ impl Indices {
         }
     }
 }
+
+// one polygon corner as written in the file, with 0-based indices
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Corner {
+    pub vertex: u32,
+    pub texture: Option<u32>,
+    pub normal: Option<u32>,
+}
+
+// consecutive polygons sharing the same group, object, material and smoothing group
+#[derive(Clone, Debug, PartialEq)]
+pub struct Group {
+    pub name: String,
+    pub object: String,
+    pub material: Option<String>,
+    pub smoothing_group: u32,
+    // range in polygon_sizes
+    pub face_start: usize,
+    pub face_count: usize,
+    // range in indices.vertex_indices
+    pub index_start: usize,
+    pub index_count: usize,
+}
+
+impl Group {
+    fn new(state: &GroupState, face_start: usize, index_start: usize) -> Group {
+        Group {
+            name: state.name.clone(),
+            object: state.object.clone(),
+            material: state.material.clone(),
+            smoothing_group: state.smoothing_group,
+            face_start,
+            face_count: 0,
+            index_start,
+            index_count: 0,
+        }
+    }
+
+    fn has_state(&self, state: &GroupState) -> bool {
+        self.name == state.name
+            && self.object == state.object
+            && self.material == state.material
+            && self.smoothing_group == state.smoothing_group
+    }
+}
+
+// g/o/usemtl/s statements seen so far while loading
+pub struct GroupState {
+    pub name: String,
+    pub object: String,
+    pub material: Option<String>,
+    pub smoothing_group: u32,
+}
+
+impl GroupState {
+    pub fn new() -> GroupState {
+        GroupState {
+            name: "default".to_string(),
+            object: String::new(),
+            material: None,
+            smoothing_group: 0,
+        }
+    }
+}
+
+impl Default for GroupState {
+    fn default() -> GroupState {
+        GroupState::new()
+    }
+}
+
 pub struct Model {
     pub vertex: Vec<f32>,
     pub vertex_tex: Vec<f32>,
     pub vertex_normal: Vec<f32>,
     pub vertex_normal_tmp: Vec<f32>,
+    // rgb in 0..1 per vertex, empty if the file has no vertex colors
+    pub vertex_color: Vec<f32>,
+    // other scalar values per vertex, such as the confidence of a scanned point
+    pub vertex_properties: Vec<(String, Vec<f32>)>,
+    // triangulated indices for rendering
     pub indices: Indices,
+    // polygons as written in the file
+    pub corners: Vec<Corner>,
+    pub polygon_sizes: Vec<u32>,
+    pub groups: Vec<Group>,
+    pub material_libs: Vec<String>,
+    pub materials: Vec<Material>,
+    // images stored inside the model file, by the name the texture maps refer to
+    pub embedded_textures: Vec<TextureData>,
 }
 impl Model {
     pub fn new() -> Model {
@@ -88,179 +225,368 @@ impl Model {
             vertex_tex: Vec::new(),
             vertex_normal: Vec::new(),
             vertex_normal_tmp: Vec::new(),
+            vertex_color: Vec::new(),
+            vertex_properties: Vec::new(),
             indices: Indices::new(),
+            corners: Vec::new(),
+            polygon_sizes: Vec::new(),
+            groups: Vec::new(),
+            material_libs: Vec::new(),
+            materials: Vec::new(),
+            embedded_textures: Vec::new(),
+        }
+    }
+
+    // OBJ indices start at 1, and negative ones count back from the last element
+    fn parse_index(str_: &str, element_num: usize, counter: i32) -> Result<u32, Box<dyn Error>> {
+        let index = match str_.parse::<i64>()? {
+            index if index > 0 => index - 1,
+            index if index < 0 => element_num as i64 + index,
+            _ => -1,
+        };
+        if index < 0 {
+            let error_text = format!("Polygon index is invalid. Line number:{}", counter);
+            return Err(error_text.into());
         }
+        Ok(index as u32)
     }
 
-    fn load_indices(&mut self, input_str: &Vec<&str>, counter: i32) -> Result<(), Box<dyn Error>> {
-        '_units: for i in 0..=2 {
+    fn load_corner(&self, input_str: &[&str], counter: i32) -> Result<Corner, Box<dyn Error>> {
+        let optional_index = |i: usize,
+                              element_num: usize|
+         -> Result<Option<u32>, Box<dyn Error>> {
             match input_str.get(i) {
-                Some(t) => {
-                    // is empty?
-                    if t.is_empty() {
-                        continue '_units;
-                    }
-                    let index = t.parse::<u32>()? - 1;
-                    // println!("index :{}",index);
-                    match i {
-                        0 => self.indices.vertex_indices.push(index),
-                        1 => self.indices.texture_indices.push(index),
-                        2 => self.indices.normal_indices.push(index),
-                        _ => (),
-                    }
+                Some(t) if !t.is_empty() => Ok(Some(Model::parse_index(t, element_num, counter)?)),
+                _ => Ok(None),
+            }
+        };
+        let vertex = match input_str.first() {
+            Some(t) if !t.is_empty() => Model::parse_index(t, self.vertex.len() / 3, counter)?,
+            _ => {
+                let error_text = format!("Polygon index is invalid. Line number:{}", counter);
+                return Err(error_text.into());
+            }
+        };
+        Ok(Corner {
+            vertex,
+            texture: optional_index(1, self.vertex_tex.len() / 2)?,
+            normal: optional_index(2, self.vertex_normal_tmp.len() / 3)?,
+        })
+    }
+
+    // store the polygon and split it into a triangle fan for rendering
+    pub fn add_polygon(&mut self, polygon: &[Corner], state: &GroupState) {
+        let needs_group = match self.groups.last() {
+            Some(group) => !group.has_state(state),
+            None => true,
+        };
+        if needs_group {
+            let group = Group::new(
+                state,
+                self.polygon_sizes.len(),
+                self.indices.vertex_indices.len(),
+            );
+            self.groups.push(group);
+        }
+
+        let index_start = self.indices.vertex_indices.len();
+        for i in 1..polygon.len().saturating_sub(1) {
+            for corner in [polygon[0], polygon[i], polygon[i + 1]].iter() {
+                self.indices.vertex_indices.push(corner.vertex);
+                if let Some(texture) = corner.texture {
+                    self.indices.texture_indices.push(texture);
                 }
-                None => {
-                    let error_text = format!("Polygon index is invalid. Line number:{}", counter);
-                    return Err(error_text.into());
+                if let Some(normal) = corner.normal {
+                    self.indices.normal_indices.push(normal);
                 }
             }
         }
-        Ok(())
+        self.corners.extend_from_slice(polygon);
+        self.polygon_sizes.push(polygon.len() as u32);
+
+        if let Some(group) = self.groups.last_mut() {
+            group.face_count += 1;
+            group.index_count += self.indices.vertex_indices.len() - index_start;
+        }
     }
 
     pub fn load(&mut self, path: &str) -> Result<(), Box<dyn Error>> {
         let file = File::open(path)?;
-        let buf_reader = BufReader::new(file);
-        let mut counter = 1;
-
-        for line in buf_reader.lines() {
-            if let Ok(str) = line {
-                // ex) str = "v 0.0 0.1 0.2"
-                // ex) ss = "v" "0.0" "0.1" "0.2"
-                let ss: Vec<&str> = str.split(' ').collect();
-                let data_attr = detect_obj_attribute(ss[0]); // first str is data attribute
-                match data_attr {
-                    ObjAttribute::VertexPos => {
-                        read_one_line(
-                            &ss,
-                            &mut self.vertex,
-                            "Vertex position is invalid.",
-                            counter,
-                        )?;
-                    }
-                    ObjAttribute::TexturePos => {
-                        read_one_line(
-                            &ss,
-                            &mut self.vertex_tex,
-                            "Vertex texture position is invalid.",
-                            counter,
-                        )?;
-                    }
-                    ObjAttribute::VertexNormal => {
-                        read_one_line(
-                            &ss,
-                            &mut self.vertex_normal_tmp,
-                            "Vertex normal is invalid.",
-                            counter,
-                        )?;
-                    }
-                    ObjAttribute::ObjectName => {
-                        // println!("Object name: {}", str);
-                    }
-                    ObjAttribute::MaterialName => {
-                        // println!("Material name: {}", str);
-                    }
-                    ObjAttribute::PolygonIndex => {
-                        // ss= f ?/?/? ?/?/? ?/?/? ?/?/?
-                        let polygon = match ss.len() - 1 {
-                            3 => Polygon::Triangle,
-                            4 => Polygon::Quadrangle,
-                            5 => Polygon::Pentagon,
-                            6 => Polygon::Hexagon,
-                            _ => Polygon::Polygon,
-                        };
-
-                        match polygon {
-                            Polygon::Triangle => {
-                                for i in 1..=3 {
-                                    let separate_index =
-                                        ss.get(i).ok_or("Polygon index is invalid.")?;
-                                    let separate_index: Vec<&str> =
-                                        separate_index.split('/').collect();
-                                    self.load_indices(&separate_index, counter)?;
-                                }
-                            }
-                            Polygon::Quadrangle => {
-                                let mut ss_str: Vec<Vec<&str>> = Vec::new();
-                                ss_str.resize(polygon as usize, Vec::new());
-                                for i in 1..ss.len() {
-                                    // str_=?/?/?
-                                    let str_ = ss.get(i).ok_or("Polygon index is invalid.")?;
-                                    let arr_index = i - 1;
-                                    ss_str[arr_index] = str_.split('/').collect();
-                                }
-                                let index_for_quad = [[0, 1, 2], [2, 3, 0]];
-                                for row in index_for_quad.iter() {
-                                    for col in row.iter() {
-                                        self.load_indices(ss_str.get(*col).ok_or("err")?, counter)?;
-                                    }
-                                }
-                            }
-                            Polygon::Pentagon => {
-                                let mut ss_str: Vec<Vec<&str>> = Vec::new();
-                                ss_str.resize(polygon as usize, Vec::new());
-                                for i in 1..ss.len() {
-                                    // str_=?/?/?
-                                    let str_ = ss.get(i).ok_or("Polygon index is invalid.")?;
-                                    let arr_index = i - 1;
-                                    ss_str[arr_index] = str_.split('/').collect();
-                                }
-                                let index_for_penta = [[0, 1, 2], [2, 3, 4], [4, 0, 1]];
-                                for row in index_for_penta.iter() {
-                                    for col in row.iter() {
-                                        self.load_indices(ss_str.get(*col).ok_or("err")?, counter)?;
-                                    }
-                                }
-                            }
-                            Polygon::Hexagon => {
-                                let mut ss_str: Vec<Vec<&str>> = Vec::new();
-                                ss_str.resize(polygon as usize, Vec::new());
-                                for i in 1..ss.len() {
-                                    // str_=?/?/?
-                                    let str_ = ss.get(i).ok_or("Polygon index is invalid.")?;
-                                    let arr_index = i - 1;
-                                    ss_str[arr_index] = str_.split('/').collect();
-                                }
-                                let index_for_hexa = [[0, 1, 2], [2, 3, 4], [4, 5, 0], [0, 2, 4]];
-                                for row in index_for_hexa.iter() {
-                                    for col in row.iter() {
-                                        self.load_indices(ss_str.get(*col).ok_or("err")?, counter)?;
-                                    }
-                                }
-                            }
-                            _ => {
-                                // println!("{}",counter);
-                            }
-                        }
-                        // self.vertex_normal[self.vertex[separate_index[0]as usize]]=self.vertex_normal_tmp[separate_index[2]];
-                    }
-                    ObjAttribute::Comment => {
-                        // println!("Comment: {}", str);
-                    }
-                    ObjAttribute::Other => {
-                        // println!("Other: {}", str);
+        let dir = Path::new(path).parent().unwrap_or_else(|| Path::new(""));
+        self.load_from_reader(file, &mut dir_resolver(dir))
+    }
+
+    // parse the file on worker threads. the file is memory-mapped instead of read
+    pub fn load_parallel(&mut self, path: &str) -> Result<(), Box<dyn Error>> {
+        self.load_parallel_with_progress(path, &Progress::new())
+    }
+
+    pub fn load_parallel_with_progress(
+        &mut self,
+        path: &str,
+        progress: &Progress,
+    ) -> Result<(), Box<dyn Error>> {
+        let file = File::open(path)?;
+        let dir = Path::new(path).parent().unwrap_or_else(|| Path::new(""));
+        if file.metadata()?.len() == 0 {
+            return self.load_from_bytes_with_progress(&[], &mut dir_resolver(dir), progress);
+        }
+        // the file must not be changed by other processes while it is mapped
+        let map = unsafe { Mmap::map(&file)? };
+        self.load_from_bytes_with_progress(&map, &mut dir_resolver(dir), progress)
+    }
+
+    // same as load_from_bytes, but large data is parsed in chunks on worker threads
+    pub fn load_from_bytes_parallel(
+        &mut self,
+        bytes: &[u8],
+        resolver: &mut Resolver,
+    ) -> Result<(), Box<dyn Error>> {
+        self.load_from_bytes_with_progress(bytes, resolver, &Progress::new())
+    }
+
+    // parse on worker threads, reporting progress and stopping when it is cancelled
+    pub fn load_from_bytes_with_progress(
+        &mut self,
+        bytes: &[u8],
+        resolver: &mut Resolver,
+        progress: &Progress,
+    ) -> Result<(), Box<dyn Error>> {
+        obj_parallel::parse(self, bytes, obj_parallel::CHUNK_SIZE, progress)?;
+        self.load_materials(resolver)
+    }
+
+    pub fn load_from_str(
+        &mut self,
+        text: &str,
+        resolver: &mut Resolver,
+    ) -> Result<(), Box<dyn Error>> {
+        self.load_from_reader(text.as_bytes(), resolver)
+    }
+
+    pub fn load_from_bytes(
+        &mut self,
+        bytes: &[u8],
+        resolver: &mut Resolver,
+    ) -> Result<(), Box<dyn Error>> {
+        self.load_from_reader(bytes, resolver)
+    }
+
+    // read OBJ data from any source. the mtllib files are read through the resolver
+    pub fn load_from_reader<R: Read>(
+        &mut self,
+        reader: R,
+        resolver: &mut Resolver,
+    ) -> Result<(), Box<dyn Error>> {
+        let buf_reader = BufReader::new(reader);
+        let mut state = GroupState::new();
+
+        for (i, line) in buf_reader.lines().enumerate() {
+            let counter = i as i32 + 1;
+            let str = line?;
+            // ex) str = "v 0.0 0.1 0.2"
+            // ex) ss = "v" "0.0" "0.1" "0.2"
+            let ss: Vec<&str> = str.split_whitespace().collect();
+            if ss.is_empty() {
+                continue;
+            }
+            let data_attr = detect_obj_attribute(ss[0]); // first str is data attribute
+            match data_attr {
+                ObjAttribute::VertexPos => {
+                    read_values(
+                        &ss,
+                        &mut self.vertex,
+                        3,
+                        "Vertex position is invalid.",
+                        counter,
+                    )?;
+                }
+                ObjAttribute::TexturePos => {
+                    read_values(
+                        &ss,
+                        &mut self.vertex_tex,
+                        2,
+                        "Vertex texture position is invalid.",
+                        counter,
+                    )?;
+                }
+                ObjAttribute::VertexNormal => {
+                    read_values(
+                        &ss,
+                        &mut self.vertex_normal_tmp,
+                        3,
+                        "Vertex normal is invalid.",
+                        counter,
+                    )?;
+                }
+                ObjAttribute::ObjectName => {
+                    state.object = read_name(&ss).unwrap_or_default();
+                }
+                ObjAttribute::GroupName => {
+                    state.name = read_name(&ss).unwrap_or_else(|| "default".to_string());
+                }
+                ObjAttribute::MaterialName => {
+                    self.material_libs
+                        .extend(ss[1..].iter().map(|s| s.to_string()));
+                }
+                ObjAttribute::UseMaterial => {
+                    state.material = read_name(&ss);
+                }
+                ObjAttribute::SmoothingGroup => {
+                    // "s off" and "s 0" both disable smoothing
+                    state.smoothing_group = ss.get(1).and_then(|s| s.parse().ok()).unwrap_or(0);
+                }
+                ObjAttribute::PolygonIndex => {
+                    // ss= f ?/?/? ?/?/? ?/?/? ?/?/?
+                    let mut polygon = Vec::with_capacity(ss.len() - 1);
+                    for str_ in ss[1..].iter() {
+                        let separate_index: Vec<&str> = str_.split('/').collect();
+                        polygon.push(self.load_corner(&separate_index, counter)?);
                     }
-                };
+                    self.add_polygon(&polygon, &state);
+                }
+                ObjAttribute::Comment => {
+                    // println!("Comment: {}", str);
+                }
+                ObjAttribute::Other => {
+                    // println!("Other: {}", str);
+                }
+            };
+        }
+        self.load_materials(resolver)
+    }
+
+    // load the mtllib files of the model. missing files are skipped
+    fn load_materials(&mut self, resolver: &mut Resolver) -> Result<(), Box<dyn Error>> {
+        for lib in self.material_libs.iter() {
+            match resolver(lib) {
+                Ok(bytes) => self.materials.extend(material::parse_mtl(&bytes[..])?),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
             }
-            counter += 1;
         }
         Ok(())
     }
 
+    // image data of every texture map of the materials, by the name the maps refer to.
+    // embedded images come from the model itself, and missing files are skipped
+    pub fn read_textures(
+        &self,
+        resolver: &mut Resolver,
+    ) -> Result<Vec<TextureData>, Box<dyn Error>> {
+        let mut textures: Vec<TextureData> = Vec::new();
+        for material in self.materials.iter() {
+            for map in material.maps().iter().filter_map(|map| map.as_ref()) {
+                if textures.iter().any(|(name, _)| name == map) {
+                    continue;
+                }
+                let embedded = self.embedded_textures.iter().find(|(name, _)| name == map);
+                let bytes = match embedded {
+                    Some((_, bytes)) => bytes.clone(),
+                    None => match resolver(map) {
+                        Ok(bytes) => bytes,
+                        Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                        Err(e) => return Err(e.into()),
+                    },
+                };
+                textures.push((map.clone(), bytes));
+            }
+        }
+        Ok(textures)
+    }
+
     pub fn create_vertex_normal(&mut self) {
-        if self.vertex.len() != self.vertex_normal.len() {
-            self.vertex_normal.resize(self.vertex.len(), 0.0);
+        self.vertex_normal.clear();
+        self.vertex_normal.resize(self.vertex.len(), 0.0);
+
+        // use the normals of the file if every polygon corner has one
+        let has_normal = !self.vertex_normal_tmp.is_empty()
+            && self.indices.normal_indices.len() == self.indices.vertex_indices.len();
+        if has_normal {
+            for (v, n) in self
+                .indices
+                .vertex_indices
+                .iter()
+                .zip(self.indices.normal_indices.iter())
+            {
+                let (v, n) = (*v as usize * 3, *n as usize * 3);
+                if v + 3 > self.vertex_normal.len() || n + 3 > self.vertex_normal_tmp.len() {
+                    continue;
+                }
+                self.vertex_normal[v..v + 3].copy_from_slice(&self.vertex_normal_tmp[n..n + 3]);
+            }
+            return;
         }
-        for i in 0..self.vertex_normal.len() {
-            self.vertex_normal[i] = self.vertex_normal_tmp[self.indices.normal_indices[i] as usize];
-            // self.vertex_normal[i] = self.vertex_normal_tmp[self.indices.normal_indices[i] as usize];
-            // self.vertex_normal[i] = self.vertex_normal_tmp[self.indices.vertex_indices[self.indices.normal_indices[i] as usize] as usize];
+
+        // otherwise accumulate face normals to each vertex
+        self.vertex_normal = self.smooth_normals();
+    }
+
+    // whether every vertex index of the triangle is in self.vertex. buffers with an entry
+    // per corner keep one for a triangle out of range, so that the sub-meshes still match
+    pub fn triangle_in_range(&self, triangle: &[u32]) -> bool {
+        triangle
+            .iter()
+            .all(|&v| v as usize * 3 + 3 <= self.vertex.len())
+    }
+
+    // normals of each vertex averaged from the faces around it, weighted by face area
+    pub fn smooth_normals(&self) -> Vec<f32> {
+        let mut normals = vec![0.0; self.vertex.len()];
+        for triangle in self.indices.vertex_indices.chunks_exact(3) {
+            if !self.triangle_in_range(triangle) {
+                continue;
+            }
+            let (a, b, c) = (
+                triangle[0] as usize * 3,
+                triangle[1] as usize * 3,
+                triangle[2] as usize * 3,
+            );
+            let p = |i: usize| [self.vertex[i], self.vertex[i + 1], self.vertex[i + 2]];
+            let (pa, pb, pc) = (p(a), p(b), p(c));
+            let e1 = [pb[0] - pa[0], pb[1] - pa[1], pb[2] - pa[2]];
+            let e2 = [pc[0] - pa[0], pc[1] - pa[1], pc[2] - pa[2]];
+            let normal = [
+                e1[1] * e2[2] - e1[2] * e2[1],
+                e1[2] * e2[0] - e1[0] * e2[2],
+                e1[0] * e2[1] - e1[1] * e2[0],
+            ];
+            for i in [a, b, c].iter() {
+                for (dst, n) in normals[*i..*i + 3].iter_mut().zip(normal.iter()) {
+                    *dst += n;
+                }
+            }
         }
+        for normal in normals.chunks_exact_mut(3) {
+            let len =
+                (normal[0] * normal[0] + normal[1] * normal[1] + normal[2] * normal[2]).sqrt();
+            if len > 0.0 {
+                normal.iter_mut().for_each(|x| *x /= len);
+            }
+        }
+        normals
+    }
+
+    // returns (min, max) of the vertex positions, or None if the model is empty
+    pub fn bounding_box(&self) -> Option<([f32; 3], [f32; 3])> {
+        let mut positions = self.vertex.chunks_exact(3);
+        let first = positions.next()?;
+        let mut min = [first[0], first[1], first[2]];
+        let mut max = min;
+        for p in positions {
+            for k in 0..3 {
+                min[k] = min[k].min(p[k]);
+                max[k] = max[k].max(p[k]);
+            }
+        }
+        Some((min, max))
     }
 
+    // position, normal and color of each vertex. vertices without a color are white
     pub fn create_concat_vertex(&self) -> Vec<f32> {
         let mut vec: Vec<f32> = Vec::new();
         let vertex_num = self.vertex.len() / 3;
+        let has_color = self.vertex_color.len() == self.vertex.len();
         for i in 0..vertex_num {
             vec.push(self.vertex[i * 3 + 0]);
             vec.push(self.vertex[i * 3 + 1]);
@@ -268,10 +594,14 @@ impl Model {
             vec.push(self.vertex_normal[i * 3 + 0]);
             vec.push(self.vertex_normal[i * 3 + 1]);
             vec.push(self.vertex_normal[i * 3 + 2]);
+            if has_color {
+                vec.extend_from_slice(&self.vertex_color[i * 3..i * 3 + 3]);
+            } else {
+                vec.extend_from_slice(&[1.0, 1.0, 1.0]);
+            }
         }
         vec
     }
-
 }
 
 #[cfg(test)]
@@ -300,10 +630,22 @@ mod tests {
         let expected = ObjAttribute::MaterialName;
         assert_eq!(expected as i32, actual as i32);
 
+        let actual = detect_obj_attribute("usemtl");
+        let expected = ObjAttribute::UseMaterial;
+        assert_eq!(expected as i32, actual as i32);
+
         let actual = detect_obj_attribute("o");
         let expected = ObjAttribute::ObjectName;
         assert_eq!(expected as i32, actual as i32);
 
+        let actual = detect_obj_attribute("g");
+        let expected = ObjAttribute::GroupName;
+        assert_eq!(expected as i32, actual as i32);
+
+        let actual = detect_obj_attribute("s");
+        let expected = ObjAttribute::SmoothingGroup;
+        assert_eq!(expected as i32, actual as i32);
+
         let actual = detect_obj_attribute("#");
         let expected = ObjAttribute::Comment;
         assert_eq!(expected as i32, actual as i32);
@@ -364,4 +706,177 @@ mod tests {
         let mut model = Model::new();
         model.load("/home/twmoca/Documents/3d_obj/BaseSpiderMan.obj");
     }
+
+    fn no_files(_name: &str) -> io::Result<Vec<u8>> {
+        Err(io::ErrorKind::NotFound.into())
+    }
+
+    fn load_str(obj: &str) -> Result<Model, Box<dyn Error>> {
+        let mut model = Model::new();
+        model.load_from_str(obj, &mut no_files)?;
+        Ok(model)
+    }
+
+    #[test]
+    fn test_ok_load_polygons() {
+        let obj = "\
+mtllib scene.mtl
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+v 0.5 2 0
+vt 0 0
+vt 1 0 0
+vn 0 0 1
+o body
+g front
+usemtl red
+f 1/1/1 2/2/1 3/2/1
+s 1
+f 1//1 2//1 3//1 5//1 4//1
+g back
+f -5 -1 -3
+";
+        let model = load_str(obj).unwrap();
+        assert_eq!(15, model.vertex.len());
+        assert_eq!(vec![0.0, 0.0, 1.0, 0.0], model.vertex_tex);
+        assert_eq!(vec!["scene.mtl".to_string()], model.material_libs);
+        assert_eq!(vec![3, 5, 3], model.polygon_sizes);
+        assert_eq!(11, model.corners.len());
+        #[rustfmt::skip]
+        let expected = vec![
+            0, 1, 2,
+            0, 1, 2, 0, 2, 4, 0, 4, 3,
+            0, 4, 2,
+        ];
+        assert_eq!(expected, model.indices.vertex_indices);
+        assert_eq!(vec![0, 1, 1], model.indices.texture_indices);
+        assert_eq!(12, model.indices.normal_indices.len());
+        let last = model.corners[10];
+        assert_eq!((2, None, None), (last.vertex, last.texture, last.normal));
+
+        assert_eq!(3, model.groups.len());
+        let group = &model.groups[1];
+        assert_eq!(
+            ("front", "body"),
+            (group.name.as_str(), group.object.as_str())
+        );
+        assert_eq!(Some("red".to_string()), group.material);
+        assert_eq!(1, group.smoothing_group);
+        assert_eq!(
+            (1, 1, 3, 9),
+            (
+                group.face_start,
+                group.face_count,
+                group.index_start,
+                group.index_count
+            )
+        );
+        let group = &model.groups[2];
+        assert_eq!(("back", 1), (group.name.as_str(), group.smoothing_group));
+        assert_eq!(
+            (2, 1, 12, 3),
+            (
+                group.face_start,
+                group.face_count,
+                group.index_start,
+                group.index_count
+            )
+        );
+    }
+
+    #[test]
+    fn test_ng_load_polygons() {
+        let invalid = [
+            "v 0 0 0\nf 0 1 1\n",
+            "v 0 0 0\nf 1 -2 1\n",
+            "v 0 0 0\nf 1 a 1\n",
+            "v 0 0\n",
+            "vn 0 x 0\n",
+        ];
+        for obj in invalid.iter() {
+            assert!(load_str(obj).is_err());
+        }
+    }
+
+    #[test]
+    fn test_ok_load_from_reader() {
+        let obj = b"mtllib a.mtl missing.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\nusemtl red\nf 1 2 3\n";
+        let mut requested = Vec::new();
+        let mut resolver = |name: &str| {
+            requested.push(name.to_string());
+            match name {
+                "a.mtl" => {
+                    Ok(b"newmtl red\nKd 1 0 0\nmap_Kd red.png\nmap_Bump none.png\n".to_vec())
+                }
+                "red.png" => Ok(b"png".to_vec()),
+                _ => Err(io::ErrorKind::NotFound.into()),
+            }
+        };
+        let mut model = Model::new();
+        model
+            .load_from_reader(std::io::Cursor::new(&obj[..]), &mut resolver)
+            .unwrap();
+        assert_eq!(vec![3], model.polygon_sizes);
+        assert_eq!(1, model.materials.len());
+        assert_eq!(Some([1.0, 0.0, 0.0]), model.materials[0].diffuse);
+
+        let textures = model.read_textures(&mut resolver).unwrap();
+        assert_eq!(vec![("red.png".to_string(), b"png".to_vec())], textures);
+        assert_eq!(
+            vec!["a.mtl", "missing.mtl", "red.png", "none.png"],
+            requested
+        );
+
+        // errors other than a missing file are reported
+        let mut broken = |_: &str| Err(io::Error::other("broken"));
+        assert!(Model::new().load_from_bytes(obj, &mut broken).is_err());
+    }
+
+    #[test]
+    fn test_ok_create_vertex_normal() {
+        // normals given by the file
+        let mut model = Model::new();
+        model.vertex = vec![0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0];
+        model.vertex_normal_tmp = vec![0.0, 1.0, 0.0, 0.0, 0.0, 1.0];
+        model.indices.vertex_indices = vec![0, 1, 2];
+        model.indices.normal_indices = vec![1, 1, 0];
+        model.create_vertex_normal();
+        let expected = vec![0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 1.0, 0.0];
+        assert_eq!(expected, model.vertex_normal);
+
+        // normals computed from the faces
+        model.vertex_normal_tmp.clear();
+        model.indices.normal_indices.clear();
+        model.create_vertex_normal();
+        let expected = vec![0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0];
+        assert_eq!(expected, model.vertex_normal);
+    }
+
+    #[test]
+    fn test_ng_triangle_in_range() {
+        let mut model = Model::new();
+        model.vertex = vec![0.0; 3 * 3];
+        assert!(model.triangle_in_range(&[0, 1, 2]));
+        assert!(!model.triangle_in_range(&[0, 1, 3]));
+        assert!(!model.triangle_in_range(&[u32::MAX, 0, 1]));
+    }
+
+    #[test]
+    fn test_ok_unit_colors() {
+        assert_eq!(vec![0.5, 1.0, 0.0], unit_colors(&[0.5, 1.0, 0.0]));
+        assert_eq!(vec![1.0, 0.0, 0.0], unit_colors(&[255.0, 0.0, 0.0]));
+        assert!(unit_colors(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_ok_bounding_box() {
+        let mut model = Model::new();
+        assert_eq!(None, model.bounding_box());
+
+        model.vertex = vec![1.0, -2.0, 0.5, -1.0, 3.0, 0.0];
+        let expected = ([-1.0, -2.0, 0.0], [1.0, 3.0, 0.5]);
+        assert_eq!(Some(expected), model.bounding_box());
+    }
 }
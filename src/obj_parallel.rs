@@ -0,0 +1,457 @@
+use std::error::Error;
+use std::str::{self, SplitWhitespace};
+
+use rayon::prelude::*;
+
+use crate::model::{detect_obj_attribute, Corner, GroupState, Model, ObjAttribute};
+use crate::progress::Progress;
+
+// chunks end at the first line end after this many bytes
+pub const CHUNK_SIZE: usize = 4 << 20;
+
+// g/o/usemtl/s statement, which applies to the faces after it
+enum Statement {
+    Object(String),
+    Group(String),
+    Material(Option<String>),
+    SmoothingGroup(u32),
+}
+
+// errors keep the line number within their chunk until the chunks are merged
+enum ParseError {
+    // message of read_values, such as "Vertex position is invalid."
+    Values(&'static str),
+    PolygonIndex,
+    Other(String),
+}
+
+impl ParseError {
+    fn into_error(self, counter: usize) -> Box<dyn Error> {
+        match self {
+            ParseError::Values(msg) => format!("{} Error line number:{}", msg, counter).into(),
+            ParseError::PolygonIndex => polygon_index_error(counter),
+            ParseError::Other(msg) => msg.into(),
+        }
+    }
+}
+
+fn polygon_index_error(counter: usize) -> Box<dyn Error> {
+    format!("Polygon index is invalid. Line number:{}", counter).into()
+}
+
+// indices of a corner as written in the file, 0 where there is none. negative indices are
+// resolved when the chunks are merged, since they count back from the elements of the
+// chunks before
+type RawCorner = [i64; 3];
+
+struct RawFace {
+    counter: usize,
+    // v, vt and vn counts of the chunk before this face
+    counts: [usize; 3],
+    corner_start: usize,
+    corner_end: usize,
+    // false if parsing stopped inside the face. its corners up to there are still resolved
+    // so that an invalid index before the error is reported first, as by Model::load
+    complete: bool,
+}
+
+#[derive(Default)]
+struct Chunk {
+    line_count: usize,
+    vertex: Vec<f32>,
+    vertex_tex: Vec<f32>,
+    vertex_normal: Vec<f32>,
+    corners: Vec<RawCorner>,
+    faces: Vec<RawFace>,
+    // statements with the number of faces before them
+    statements: Vec<(usize, Statement)>,
+    material_libs: Vec<String>,
+    // parsing stops at the first error
+    error: Option<(usize, ParseError)>,
+}
+
+// split the data after line ends, so that every chunk holds whole lines
+fn split_chunks(data: &[u8], chunk_size: usize) -> Vec<&[u8]> {
+    let mut chunks = Vec::with_capacity(data.len() / chunk_size.max(1) + 1);
+    let mut rest = data;
+    while !rest.is_empty() {
+        let end = match rest.iter().skip(chunk_size).position(|b| *b == b'\n') {
+            Some(i) => chunk_size + i + 1,
+            None => rest.len(),
+        };
+        let (chunk, tail) = rest.split_at(end);
+        chunks.push(chunk);
+        rest = tail;
+    }
+    chunks
+}
+
+// same as read_values of the model: every value must be a number and there must be `count`
+fn read_values(
+    words: SplitWhitespace,
+    out_buf: &mut Vec<f32>,
+    count: usize,
+    error_msg: &'static str,
+) -> Result<(), ParseError> {
+    let start = out_buf.len();
+    for word in words {
+        let value = word
+            .parse::<f32>()
+            .map_err(|_| ParseError::Values(error_msg))?;
+        out_buf.push(value);
+    }
+    if out_buf.len() - start < count {
+        return Err(ParseError::Values(error_msg));
+    }
+    out_buf.truncate(start + count);
+    Ok(())
+}
+
+fn read_name(words: SplitWhitespace) -> Option<String> {
+    let words: Vec<&str> = words.collect();
+    if words.is_empty() {
+        return None;
+    }
+    Some(words.join(" "))
+}
+
+fn parse_raw_index(str_: &str) -> Result<i64, ParseError> {
+    match str_.parse::<i64>() {
+        Ok(0) => Err(ParseError::PolygonIndex),
+        Ok(index) => Ok(index),
+        Err(e) => Err(ParseError::Other(e.to_string())),
+    }
+}
+
+// "v", "v/vt", "v//vn" or "v/vt/vn". components are stored as soon as they are parsed
+fn parse_corner(word: &str, corner: &mut RawCorner) -> Result<(), ParseError> {
+    let mut parts = word.split('/');
+    match parts.next() {
+        Some(t) if !t.is_empty() => corner[0] = parse_raw_index(t)?,
+        _ => return Err(ParseError::PolygonIndex),
+    }
+    for value in corner[1..].iter_mut() {
+        match parts.next() {
+            Some(t) if !t.is_empty() => *value = parse_raw_index(t)?,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+impl Chunk {
+    fn parse_face(&mut self, words: SplitWhitespace, counter: usize) -> Result<(), ParseError> {
+        let counts = [
+            self.vertex.len() / 3,
+            self.vertex_tex.len() / 2,
+            self.vertex_normal.len() / 3,
+        ];
+        let corner_start = self.corners.len();
+        let mut result = Ok(());
+        for word in words {
+            let mut corner = [0; 3];
+            result = parse_corner(word, &mut corner);
+            self.corners.push(corner);
+            if result.is_err() {
+                break;
+            }
+        }
+        self.faces.push(RawFace {
+            counter,
+            counts,
+            corner_start,
+            corner_end: self.corners.len(),
+            complete: result.is_ok(),
+        });
+        result
+    }
+
+    fn parse_line(&mut self, line: &str, counter: usize) -> Result<(), ParseError> {
+        let mut words = line.split_whitespace();
+        let first = match words.next() {
+            Some(word) => word,
+            None => return Ok(()),
+        };
+        let face_num = self.faces.len();
+        match detect_obj_attribute(first) {
+            ObjAttribute::VertexPos => {
+                read_values(words, &mut self.vertex, 3, "Vertex position is invalid.")?
+            }
+            ObjAttribute::TexturePos => read_values(
+                words,
+                &mut self.vertex_tex,
+                2,
+                "Vertex texture position is invalid.",
+            )?,
+            ObjAttribute::VertexNormal => read_values(
+                words,
+                &mut self.vertex_normal,
+                3,
+                "Vertex normal is invalid.",
+            )?,
+            ObjAttribute::ObjectName => {
+                let name = read_name(words).unwrap_or_default();
+                self.statements.push((face_num, Statement::Object(name)));
+            }
+            ObjAttribute::GroupName => {
+                let name = read_name(words).unwrap_or_else(|| "default".to_string());
+                self.statements.push((face_num, Statement::Group(name)));
+            }
+            ObjAttribute::MaterialName => {
+                self.material_libs.extend(words.map(|s| s.to_string()));
+            }
+            ObjAttribute::UseMaterial => {
+                let name = read_name(words);
+                self.statements.push((face_num, Statement::Material(name)));
+            }
+            ObjAttribute::SmoothingGroup => {
+                let group = words.next().and_then(|s| s.parse().ok()).unwrap_or(0);
+                self.statements
+                    .push((face_num, Statement::SmoothingGroup(group)));
+            }
+            ObjAttribute::PolygonIndex => self.parse_face(words, counter)?,
+            ObjAttribute::Comment | ObjAttribute::Other => {}
+        }
+        Ok(())
+    }
+}
+
+fn parse_chunk(data: &[u8]) -> Chunk {
+    let mut chunk = Chunk::default();
+    // lines before invalid UTF-8 are parsed, and the error is reported at its line
+    let (text, utf8_error) = match str::from_utf8(data) {
+        Ok(text) => (text, false),
+        Err(e) => {
+            let valid = &data[..e.valid_up_to()];
+            let end = valid.iter().rposition(|b| *b == b'\n').map_or(0, |i| i + 1);
+            (str::from_utf8(&data[..end]).unwrap_or(""), true)
+        }
+    };
+    for line in text.split('\n') {
+        chunk.line_count += 1;
+        if let Err(e) = chunk.parse_line(line, chunk.line_count) {
+            chunk.error = Some((chunk.line_count, e));
+            return chunk;
+        }
+    }
+    // the piece after the last line end is not a line of its own
+    chunk.line_count -= 1;
+    if utf8_error {
+        let error = ParseError::Other("stream did not contain valid UTF-8".to_string());
+        chunk.error = Some((chunk.line_count + 1, error));
+    }
+    chunk
+}
+
+// OBJ indices start at 1, and negative ones count back from the last element
+fn resolve_index(raw: i64, element_num: usize, counter: usize) -> Result<u32, Box<dyn Error>> {
+    let index = if raw > 0 {
+        raw - 1
+    } else {
+        element_num as i64 + raw
+    };
+    if index < 0 {
+        return Err(polygon_index_error(counter));
+    }
+    Ok(index as u32)
+}
+
+fn apply(state: &mut GroupState, statement: Statement) {
+    match statement {
+        Statement::Object(name) => state.object = name,
+        Statement::Group(name) => state.name = name,
+        Statement::Material(name) => state.material = name,
+        Statement::SmoothingGroup(group) => state.smoothing_group = group,
+    }
+}
+
+// append the chunks in file order, giving line numbers and indices their place in the file
+fn merge(model: &mut Model, chunks: Vec<Chunk>) -> Result<(), Box<dyn Error>> {
+    let total = |f: fn(&Chunk) -> usize| chunks.iter().map(f).sum::<usize>();
+    model.vertex.reserve(total(|c| c.vertex.len()));
+    model.vertex_tex.reserve(total(|c| c.vertex_tex.len()));
+    model
+        .vertex_normal_tmp
+        .reserve(total(|c| c.vertex_normal.len()));
+    model.polygon_sizes.reserve(total(|c| c.faces.len()));
+    model.corners.reserve(total(|c| c.corners.len()));
+
+    let mut state = GroupState::new();
+    let mut line_offset = 0;
+    let mut polygon = Vec::new();
+    for chunk in chunks {
+        let offsets = [
+            model.vertex.len() / 3,
+            model.vertex_tex.len() / 2,
+            model.vertex_normal_tmp.len() / 3,
+        ];
+        model.vertex.extend_from_slice(&chunk.vertex);
+        model.vertex_tex.extend_from_slice(&chunk.vertex_tex);
+        model
+            .vertex_normal_tmp
+            .extend_from_slice(&chunk.vertex_normal);
+        model.material_libs.extend(chunk.material_libs);
+
+        let mut statements = chunk.statements.into_iter().peekable();
+        for (i, face) in chunk.faces.iter().enumerate() {
+            while let Some((_, statement)) = statements.next_if(|(pos, _)| *pos <= i) {
+                apply(&mut state, statement);
+            }
+            let counter = line_offset + face.counter;
+            let element_nums = [
+                offsets[0] + face.counts[0],
+                offsets[1] + face.counts[1],
+                offsets[2] + face.counts[2],
+            ];
+            polygon.clear();
+            for raw in chunk.corners[face.corner_start..face.corner_end].iter() {
+                let mut indices = [None; 3];
+                for ((index, raw), element_num) in
+                    indices.iter_mut().zip(raw.iter()).zip(element_nums.iter())
+                {
+                    if *raw != 0 {
+                        *index = Some(resolve_index(*raw, *element_num, counter)?);
+                    }
+                }
+                polygon.push(Corner {
+                    vertex: indices[0].unwrap_or(0),
+                    texture: indices[1],
+                    normal: indices[2],
+                });
+            }
+            if face.complete {
+                model.add_polygon(&polygon, &state);
+            }
+        }
+        for (_, statement) in statements {
+            apply(&mut state, statement);
+        }
+        if let Some((counter, error)) = chunk.error {
+            return Err(error.into_error(line_offset + counter));
+        }
+        line_offset += chunk.line_count;
+    }
+    Ok(())
+}
+
+// parse OBJ data on worker threads. the model is the same as the one of Model::load, and
+// so are the errors and their line numbers. progress counts the bytes and faces of the
+// chunks parsed so far
+pub fn parse(
+    model: &mut Model,
+    data: &[u8],
+    chunk_size: usize,
+    progress: &Progress,
+) -> Result<(), Box<dyn Error>> {
+    progress.set_total_bytes(data.len() as u64);
+    let chunks: Vec<Chunk> = split_chunks(data, chunk_size)
+        .into_par_iter()
+        .map(|data| {
+            // chunks left when cancelled are skipped
+            if progress.is_cancelled() {
+                return Chunk::default();
+            }
+            let chunk = parse_chunk(data);
+            progress.add_bytes(data.len() as u64);
+            progress.add_faces(chunk.faces.len() as u64);
+            chunk
+        })
+        .collect();
+    progress.check_cancelled()?;
+    merge(model, chunks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+
+    fn no_files(_name: &str) -> io::Result<Vec<u8>> {
+        Err(io::ErrorKind::NotFound.into())
+    }
+
+    fn load_both(obj: &[u8], chunk_size: usize) -> (Result<Model, String>, Result<Model, String>) {
+        let mut sequential = Model::new();
+        let sequential = sequential
+            .load_from_bytes(obj, &mut no_files)
+            .map(|_| sequential)
+            .map_err(|e| e.to_string());
+        let mut parallel = Model::new();
+        let parallel = parse(&mut parallel, obj, chunk_size, &Progress::new())
+            .map(|_| parallel)
+            .map_err(|e| e.to_string());
+        (sequential, parallel)
+    }
+
+    #[test]
+    fn test_ok_split_chunks() {
+        let data = b"v 0 0 0\nv 1 0 0\nf 1 2 3";
+        let chunks = split_chunks(data, 4);
+        assert_eq!(
+            vec![&b"v 0 0 0\n"[..], &b"v 1 0 0\n"[..], &b"f 1 2 3"[..]],
+            chunks
+        );
+        assert_eq!(vec![&data[..]], split_chunks(data, 100));
+        assert!(split_chunks(b"", 4).is_empty());
+    }
+
+    #[test]
+    fn test_ok_parse() {
+        let mut obj = String::from("mtllib a.mtl\n# comment\n");
+        for i in 0..40 {
+            obj += &format!("v {} 0 0\nv {} 1 0\nvt 0 {}\nvn 0 0 1\n", i, i, i);
+            if i % 7 == 0 {
+                obj += &format!("g part{}\nusemtl m{}\ns {}\n", i, i % 3, i % 2);
+            }
+            obj += "f -1/-1/-1 -2/1/-1 1//1\n";
+            if i > 2 {
+                obj += "f 1 -3 -2 2\r\n";
+            }
+        }
+        obj += "o last\nf 3/2 4/3 5/4";
+        for chunk_size in [1, 7, 64, 1000, CHUNK_SIZE].iter() {
+            let (sequential, parallel) = load_both(obj.as_bytes(), *chunk_size);
+            let (sequential, parallel) = (sequential.unwrap(), parallel.unwrap());
+            assert_eq!(sequential.vertex, parallel.vertex);
+            assert_eq!(sequential.vertex_tex, parallel.vertex_tex);
+            assert_eq!(sequential.vertex_normal_tmp, parallel.vertex_normal_tmp);
+            assert_eq!(sequential.corners, parallel.corners);
+            assert_eq!(sequential.polygon_sizes, parallel.polygon_sizes);
+            assert_eq!(
+                sequential.indices.vertex_indices,
+                parallel.indices.vertex_indices
+            );
+            assert_eq!(
+                sequential.indices.texture_indices,
+                parallel.indices.texture_indices
+            );
+            assert_eq!(
+                sequential.indices.normal_indices,
+                parallel.indices.normal_indices
+            );
+            assert_eq!(sequential.groups, parallel.groups);
+            assert_eq!(sequential.material_libs, parallel.material_libs);
+        }
+    }
+
+    #[test]
+    fn test_ng_parse() {
+        let invalid: [&[u8]; 8] = [
+            b"v 0 0 0\nf 0 1 1\n",
+            b"v 0 0 0\nf 1 -2 1\n",
+            b"v 0 0 0\nf 1 a 1\n",
+            b"v 0 0 0\nf -2/x 1 1\n",
+            b"v 0 0 0\n\nf 1/1/1 1\n v 0 0\n",
+            b"v 0 0 0\nv 0 0 0\nvn 0 x 0\nf -3 1 1\n",
+            b"v 0 0 0\nv 0 0 0\nf 1 2 -5\nvt 0\n",
+            b"v 0 0 0\nf 1 1 1\nv 0 \xff 0\n",
+        ];
+        for obj in invalid.iter() {
+            for chunk_size in [1, 9, 1000].iter() {
+                let (sequential, parallel) = load_both(obj, *chunk_size);
+                let sequential = sequential.err().unwrap();
+                assert_eq!(Some(sequential), parallel.err(), "{:?}", obj);
+            }
+        }
+    }
+}
@@ -0,0 +1,371 @@
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Component, Path, PathBuf};
+
+use crate::material;
+use crate::model::{Corner, Model};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum IndexStyle {
+    // 1-based indices from the beginning of the file
+    Absolute,
+    // negative indices counted back from the last element
+    Relative,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ObjWriteOptions {
+    // significant digits of the values, which are rounded. None writes the shortest
+    // digits which read back as the same value
+    pub precision: Option<usize>,
+    pub index_style: IndexStyle,
+    // directory the texture paths of the materials are relative to
+    pub texture_dir: PathBuf,
+}
+
+impl ObjWriteOptions {
+    pub fn new() -> ObjWriteOptions {
+        ObjWriteOptions {
+            precision: None,
+            index_style: IndexStyle::Absolute,
+            texture_dir: PathBuf::new(),
+        }
+    }
+}
+
+impl Default for ObjWriteOptions {
+    fn default() -> ObjWriteOptions {
+        ObjWriteOptions::new()
+    }
+}
+
+fn format_index(index: u32, element_num: usize, style: IndexStyle) -> String {
+    match style {
+        IndexStyle::Absolute => (index as i64 + 1).to_string(),
+        IndexStyle::Relative => (index as i64 - element_num as i64).to_string(),
+    }
+}
+
+fn format_corner(corner: &Corner, model: &Model, style: IndexStyle) -> String {
+    let v = format_index(corner.vertex, model.vertex.len() / 3, style);
+    let t = corner
+        .texture
+        .map(|t| format_index(t, model.vertex_tex.len() / 2, style));
+    let n = corner
+        .normal
+        .map(|n| format_index(n, model.vertex_normal_tmp.len() / 3, style));
+    match (t, n) {
+        (None, None) => v,
+        (Some(t), None) => format!("{}/{}", v, t),
+        (None, Some(n)) => format!("{}//{}", v, n),
+        (Some(t), Some(n)) => format!("{}/{}/{}", v, t, n),
+    }
+}
+
+// a value rounded to `precision` significant digits, written with the fewest digits which
+// read back as the rounded value
+pub fn format_value(value: f32, precision: Option<usize>) -> String {
+    match precision {
+        Some(digits) => {
+            let rounded = format!("{:.*e}", digits.max(1) - 1, value);
+            rounded.parse::<f32>().unwrap_or(value).to_string()
+        }
+        None => value.to_string(),
+    }
+}
+
+fn write_values<W: Write>(
+    writer: &mut W,
+    key: &str,
+    values: &[f32],
+    count: usize,
+    precision: Option<usize>,
+) -> Result<(), Box<dyn Error>> {
+    for value in values.chunks_exact(count) {
+        write!(writer, "{}", key)?;
+        for v in value.iter() {
+            write!(writer, " {}", format_value(*v, precision))?;
+        }
+        writeln!(writer)?;
+    }
+    Ok(())
+}
+
+// write the model as OBJ with mtllib lines for model.material_libs
+pub fn write_obj<W: Write>(
+    writer: &mut W,
+    model: &Model,
+    options: &ObjWriteOptions,
+) -> Result<(), Box<dyn Error>> {
+    write_obj_with_libs(writer, model, &model.material_libs, options)
+}
+
+fn write_obj_with_libs<W: Write>(
+    writer: &mut W,
+    model: &Model,
+    material_libs: &[String],
+    options: &ObjWriteOptions,
+) -> Result<(), Box<dyn Error>> {
+    for lib in material_libs.iter() {
+        writeln!(writer, "mtllib {}", lib)?;
+    }
+    write_values(writer, "v", &model.vertex, 3, options.precision)?;
+    write_values(writer, "vt", &model.vertex_tex, 2, options.precision)?;
+    write_values(writer, "vn", &model.vertex_normal_tmp, 3, options.precision)?;
+
+    // the loader starts with these values, so they are only written once they change
+    let mut object = "";
+    let mut name = "default";
+    let mut material: Option<&str> = None;
+    let mut smoothing_group = 0;
+
+    let mut groups = model.groups.iter().peekable();
+    let mut corner_start = 0;
+    for (face, &size) in model.polygon_sizes.iter().enumerate() {
+        if let Some(group) = groups.next_if(|group| group.face_start == face) {
+            if group.object != object {
+                object = &group.object;
+                writeln!(writer, "o {}", object)?;
+            }
+            if group.name != name {
+                name = &group.name;
+                writeln!(writer, "g {}", name)?;
+            }
+            if group.material.as_deref() != material {
+                material = group.material.as_deref();
+                writeln!(writer, "usemtl {}", material.unwrap_or(""))?;
+            }
+            if group.smoothing_group != smoothing_group {
+                smoothing_group = group.smoothing_group;
+                match smoothing_group {
+                    0 => writeln!(writer, "s off")?,
+                    s => writeln!(writer, "s {}", s)?,
+                }
+            }
+        }
+        let polygon = &model.corners[corner_start..corner_start + size as usize];
+        let polygon: Vec<String> = polygon
+            .iter()
+            .map(|corner| format_corner(corner, model, options.index_style))
+            .collect();
+        writeln!(writer, "f {}", polygon.join(" "))?;
+        corner_start += size as usize;
+    }
+    Ok(())
+}
+
+// write path and, if the model has materials, a .mtl file with the same name next to it.
+// the texture maps refer to the files they referred to from options.texture_dir
+pub fn save_obj(
+    path: &Path,
+    model: &Model,
+    options: &ObjWriteOptions,
+) -> Result<(), Box<dyn Error>> {
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    let mut material_libs = model.material_libs.clone();
+    if !model.materials.is_empty() {
+        let mut materials = model.materials.clone();
+        for map in materials.iter_mut().flat_map(|m| m.maps_mut()) {
+            // images embedded in the source file are written next to the output below
+            if let Some(map) = map
+                .as_mut()
+                .filter(|map| !model.embedded_textures.iter().any(|(name, _)| name == *map))
+            {
+                *map = material::rebase_map(map, &options.texture_dir, dir);
+            }
+        }
+        let mtl_path = path.with_extension("mtl");
+        let mut mtl_writer = BufWriter::new(File::create(&mtl_path)?);
+        material::write_mtl(&mut mtl_writer, &materials, options.precision)?;
+        mtl_writer.flush()?;
+        let mtl_name = mtl_path.file_name().ok_or("invalid output path")?;
+        material_libs = vec![mtl_name.to_string_lossy().to_string()];
+    }
+    // images embedded in the source file are written out for the texture maps to refer to
+    for (name, bytes) in model.embedded_textures.iter() {
+        let relative = Path::new(name);
+        if !relative
+            .components()
+            .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+        {
+            return Err(format!("texture is outside the output directory: {}", name).into());
+        }
+        let texture_path = dir.join(relative);
+        if let Some(parent) = texture_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(texture_path, bytes)?;
+    }
+
+    let mut writer = BufWriter::new(File::create(path)?);
+    write_obj_with_libs(&mut writer, model, &material_libs, options)?;
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OBJ: &str = "\
+mtllib scene.mtl
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+v 0.5 2 0.25
+vt 0 0
+vt 1 0
+vt 1 1
+vn 0 0 1
+vn 0 1 0
+o body
+g front
+usemtl red
+f 1/1/1 2/2/1 3/3/1
+s 1
+f 1//1 2//1 3//1 5//2 4//2
+g back
+usemtl blue
+s off
+f -5 -1 -3
+f 1/1 2/2 3/3 4/3
+o head
+usemtl
+f 2 3 4
+";
+
+    const MTL: &str = "\
+newmtl red
+Kd 1 0 0
+map_Kd red.png
+newmtl blue
+Kd 0 0 1
+d 0.5
+";
+
+    fn temp_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(name);
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn load(path: &Path) -> Model {
+        let mut model = Model::new();
+        model.load(&path.to_string_lossy()).unwrap();
+        model
+    }
+
+    fn assert_same_model(expected: &Model, actual: &Model) {
+        assert_eq!(expected.vertex, actual.vertex);
+        assert_eq!(expected.vertex_tex, actual.vertex_tex);
+        assert_eq!(expected.vertex_normal_tmp, actual.vertex_normal_tmp);
+        assert_eq!(expected.corners, actual.corners);
+        assert_eq!(expected.polygon_sizes, actual.polygon_sizes);
+        assert_eq!(expected.groups, actual.groups);
+        assert_eq!(
+            expected.indices.vertex_indices,
+            actual.indices.vertex_indices
+        );
+        assert_eq!(
+            expected.indices.texture_indices,
+            actual.indices.texture_indices
+        );
+        assert_eq!(
+            expected.indices.normal_indices,
+            actual.indices.normal_indices
+        );
+        assert_eq!(expected.materials, actual.materials);
+    }
+
+    #[test]
+    fn test_ok_round_trip() {
+        let dir = temp_dir("obj_viewer_test_round_trip");
+        fs::write(dir.join("scene.obj"), OBJ).unwrap();
+        fs::write(dir.join("scene.mtl"), MTL).unwrap();
+        let original = load(&dir.join("scene.obj"));
+        assert_eq!(2, original.materials.len());
+        assert_eq!(4, original.groups.len());
+
+        let styles = [IndexStyle::Absolute, IndexStyle::Relative];
+        for (i, index_style) in styles.iter().enumerate() {
+            let options = ObjWriteOptions {
+                index_style: *index_style,
+                texture_dir: dir.clone(),
+                ..ObjWriteOptions::new()
+            };
+            let path = dir.join(format!("saved{}.obj", i));
+            save_obj(&path, &original, &options).unwrap();
+            let saved = load(&path);
+            assert_same_model(&original, &saved);
+            assert_eq!(vec![format!("saved{}.mtl", i)], saved.material_libs);
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_ok_write_obj() {
+        let dir = temp_dir("obj_viewer_test_write_obj");
+        fs::write(
+            dir.join("scene.obj"),
+            "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\nf 3 2 1\n",
+        )
+        .unwrap();
+        let model = load(&dir.join("scene.obj"));
+        fs::remove_dir_all(&dir).unwrap();
+
+        let options = ObjWriteOptions {
+            precision: Some(1),
+            index_style: IndexStyle::Relative,
+            ..ObjWriteOptions::new()
+        };
+        let mut buf = Vec::new();
+        write_obj(&mut buf, &model, &options).unwrap();
+        let expected = "\
+v 0 0 0
+v 1 0 0
+v 0 1 0
+f -3 -2 -1
+f -1 -2 -3
+";
+        assert_eq!(expected, String::from_utf8(buf).unwrap());
+    }
+
+    #[test]
+    fn test_ok_format_value() {
+        // the default keeps every value as it is
+        for &value in [0.0000001, 123456.79, -0.1, 1.0e-30].iter() {
+            let text = format_value(value, None);
+            assert_eq!(value, text.parse::<f32>().unwrap());
+        }
+        assert_eq!("0.0000001", format_value(0.0000001, None));
+        assert_eq!("0.00000012", format_value(0.000000123, Some(2)));
+        assert_eq!("123000", format_value(123456.79, Some(3)));
+        assert_eq!("-0.1", format_value(-0.1, Some(6)));
+    }
+
+    #[test]
+    fn test_ok_save_obj_in_other_directory() {
+        let dir = temp_dir("obj_viewer_test_save_obj_dir");
+        let (source, output) = (dir.join("a"), dir.join("b"));
+        fs::create_dir_all(&source).unwrap();
+        fs::create_dir_all(&output).unwrap();
+        fs::write(source.join("scene.obj"), OBJ).unwrap();
+        fs::write(source.join("scene.mtl"), MTL).unwrap();
+        fs::write(source.join("red.png"), b"png").unwrap();
+        let original = load(&source.join("scene.obj"));
+
+        let options = ObjWriteOptions {
+            texture_dir: source.clone(),
+            ..ObjWriteOptions::new()
+        };
+        save_obj(&output.join("out.obj"), &original, &options).unwrap();
+        let saved = load(&output.join("out.obj"));
+        let map = saved.materials[0].diffuse_map.clone().unwrap();
+        assert_eq!("../a/red.png", map);
+        assert!(output.join(map).exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
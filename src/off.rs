@@ -0,0 +1,211 @@
+use std::error::Error;
+use std::fs;
+
+use crate::model::{unit_colors, Corner, GroupState, Model};
+
+// values of a vertex line in the order of the header prefixes, "[ST][C][N]OFF"
+struct Layout {
+    tex_coords: bool,
+    colors: bool,
+    normals: bool,
+}
+
+fn parse_header(keyword: &str) -> Result<Layout, Box<dyn Error>> {
+    let mut rest = keyword
+        .strip_suffix("OFF")
+        .ok_or_else(|| format!("not an OFF file: {}", keyword))?;
+    let mut take = |prefix: &str| match rest.strip_prefix(prefix) {
+        Some(r) => {
+            rest = r;
+            true
+        }
+        None => false,
+    };
+    let layout = Layout {
+        tex_coords: take("ST"),
+        colors: take("C"),
+        normals: take("N"),
+    };
+    if !rest.is_empty() {
+        return Err(format!("unsupported OFF variant: {}", keyword).into());
+    }
+    Ok(layout)
+}
+
+fn parse_values(ss: &[&str], counter: usize) -> Result<Vec<f32>, Box<dyn Error>> {
+    ss.iter()
+        .map(|s| {
+            s.parse::<f32>()
+                .map_err(|_| format!("OFF value is invalid. Line number:{}", counter).into())
+        })
+        .collect()
+}
+
+pub fn parse_off(text: &str) -> Result<Model, Box<dyn Error>> {
+    // non-empty lines without comments, with their line numbers
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.split('#').next().unwrap_or("")))
+        .map(|(counter, line)| (counter, line.split_whitespace().collect::<Vec<&str>>()))
+        .filter(|(_, ss)| !ss.is_empty());
+
+    let (header_counter, header) = lines.next().ok_or("OFF file is empty")?;
+    let layout = parse_header(header[0])?;
+    // the counts may follow the keyword on the same line
+    let (counter, counts) = match header.len() {
+        1 => lines.next().ok_or("OFF file has no counts")?,
+        _ => (header_counter, header[1..].to_vec()),
+    };
+    let counts: Vec<usize> = counts
+        .iter()
+        .take(2)
+        .map(|s| s.parse::<usize>())
+        .collect::<Result<_, _>>()
+        .map_err(|_| format!("OFF counts are invalid. Line number:{}", counter))?;
+    if counts.len() < 2 {
+        return Err(format!("OFF counts are invalid. Line number:{}", counter).into());
+    }
+    let (vertex_num, face_num) = (counts[0], counts[1]);
+
+    let mut model = Model::new();
+    let mut colors = Vec::new();
+    for _ in 0..vertex_num {
+        let (counter, ss) = lines
+            .next()
+            .ok_or("OFF file has fewer vertices than its count")?;
+        let values = parse_values(&ss, counter)?;
+        let invalid = || format!("OFF vertex is invalid. Line number:{}", counter);
+        if values.len() < 3 {
+            return Err(invalid().into());
+        }
+        model.vertex.extend_from_slice(&values[..3]);
+        let mut rest = &values[3..];
+        if layout.normals {
+            if rest.len() < 3 {
+                return Err(invalid().into());
+            }
+            model.vertex_normal_tmp.extend_from_slice(&rest[..3]);
+            rest = &rest[3..];
+        }
+        if layout.colors {
+            // rgb or rgba, followed by the texture coordinate if there is one
+            let tex_len = if layout.tex_coords { 2 } else { 0 };
+            let color_len = rest.len().saturating_sub(tex_len);
+            if color_len != 3 && color_len != 4 {
+                return Err(invalid().into());
+            }
+            colors.extend_from_slice(&rest[..3]);
+            rest = &rest[color_len..];
+        }
+        if layout.tex_coords {
+            if rest.len() < 2 {
+                return Err(invalid().into());
+            }
+            model.vertex_tex.extend_from_slice(&rest[..2]);
+        }
+    }
+    model.vertex_color = unit_colors(&colors);
+
+    let state = GroupState::new();
+    let mut polygon = Vec::new();
+    for _ in 0..face_num {
+        let (counter, ss) = lines
+            .next()
+            .ok_or("OFF file has fewer faces than its count")?;
+        let invalid = || format!("OFF face is invalid. Line number:{}", counter);
+        let size: usize = ss[0].parse().map_err(|_| invalid())?;
+        // values after the indices are the face color, which is not kept
+        if size < 3 || ss.len() < size + 1 {
+            return Err(invalid().into());
+        }
+        polygon.clear();
+        for s in ss[1..=size].iter() {
+            let index: usize = s.parse().map_err(|_| invalid())?;
+            if index >= vertex_num {
+                let error_text = format!("OFF index is out of range. Line number:{}", counter);
+                return Err(error_text.into());
+            }
+            polygon.push(Corner {
+                vertex: index as u32,
+                texture: if layout.tex_coords {
+                    Some(index as u32)
+                } else {
+                    None
+                },
+                normal: if layout.normals {
+                    Some(index as u32)
+                } else {
+                    None
+                },
+            });
+        }
+        model.add_polygon(&polygon, &state);
+    }
+    Ok(model)
+}
+
+pub fn load_off(path: &str) -> Result<Model, Box<dyn Error>> {
+    parse_off(&fs::read_to_string(path)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ok_parse_off() {
+        let off = "\
+OFF
+# a square and a triangle
+5 2 0
+0 0 0
+1 0 0
+1 1 0
+0 1 0
+0.5 2 0
+4 0 1 2 3
+3 3 2 4 255 0 0
+";
+        let model = parse_off(off).unwrap();
+        assert_eq!(15, model.vertex.len());
+        assert_eq!(vec![4, 3], model.polygon_sizes);
+        assert_eq!(
+            vec![0, 1, 2, 0, 2, 3, 3, 2, 4],
+            model.indices.vertex_indices
+        );
+        assert!(model.vertex_color.is_empty());
+    }
+
+    #[test]
+    fn test_ok_parse_variants() {
+        let coff = "COFF 3 1 0\n0 0 0 255 0 0 255\n1 0 0 0 255 0 255\n0 1 0 0 0 255 255\n3 0 1 2\n";
+        let model = parse_off(coff).unwrap();
+        assert_eq!(vec![1.0, 0.0, 0.0], model.vertex_color[..3].to_vec());
+        assert_eq!(9, model.vertex_color.len());
+
+        let stcnoff = "STCNOFF\n3 1 3\n\
+0 0 0 0 0 1 1 0 0 0 0\n1 0 0 0 0 1 0 1 0 1 0\n0 1 0 0 0 1 0 0 1 0 1\n3 0 1 2\n";
+        let model = parse_off(stcnoff).unwrap();
+        assert_eq!(vec![0.0, 0.0, 1.0], model.vertex_normal_tmp[..3].to_vec());
+        assert_eq!(vec![0.0, 0.0, 1.0], model.vertex_color[6..].to_vec());
+        assert_eq!(vec![0.0, 0.0, 1.0, 0.0, 0.0, 1.0], model.vertex_tex);
+        assert_eq!(Some(2), model.corners[2].texture);
+        assert_eq!(Some(2), model.corners[2].normal);
+    }
+
+    #[test]
+    fn test_ng_parse_off() {
+        assert!(parse_off("").is_err());
+        assert!(parse_off("PLY\n3 1 0\n").is_err());
+        assert!(parse_off("4OFF\n3 1 0\n").is_err());
+        assert!(parse_off("OFF\n3 1 0\n0 0 0\n1 0 0\n").is_err());
+        assert!(parse_off("OFF\n3 1 0\n0 0 0\n1 0 0\n0 1\n3 0 1 2\n").is_err());
+        assert!(parse_off("OFF\n3 1 0\n0 0 0\n1 0 0\n0 1 0\n3 0 1 3\n").is_err());
+        assert!(parse_off("OFF\n3 1 0\n0 0 0\n1 0 0\n0 1 0\n4 0 1 2\n").is_err());
+
+        // the line of counts after the keyword is the line of the header
+        let error = parse_off("# comment\n\nOFF 3 x 0\n").err().unwrap();
+        assert_eq!("OFF counts are invalid. Line number:3", error.to_string());
+    }
+}
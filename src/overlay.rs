@@ -0,0 +1,395 @@
+use cgmath::prelude::SquareMatrix;
+use gl::types::GLuint;
+
+use crate::display::DisplayState;
+use crate::model::Model;
+use crate::shader::ShaderVariants;
+use crate::vertex::{Vertex, VertexLayout};
+
+type Matrix4 = cgmath::Matrix4<f32>;
+type Vector4 = cgmath::Vector4<f32>;
+
+pub const AXIS_COLORS: [[f32; 3]; 3] = [[0.9, 0.1, 0.1], [0.1, 0.7, 0.1], [0.1, 0.2, 0.9]];
+const GRID_COLOR: [f32; 3] = [0.6, 0.6, 0.6];
+const BOX_COLOR: [f32; 3] = [0.9, 0.6, 0.1];
+const VERTEX_NORMAL_COLOR: [f32; 3] = [0.1, 0.7, 0.8];
+const FACE_NORMAL_COLOR: [f32; 3] = [0.8, 0.1, 0.7];
+
+// the lines are pairs of vertices with a position and a color
+fn push_line(lines: &mut Vec<f32>, from: [f32; 3], to: [f32; 3], color: [f32; 3]) {
+    lines.extend_from_slice(&from);
+    lines.extend_from_slice(&color);
+    lines.extend_from_slice(&to);
+    lines.extend_from_slice(&color);
+}
+
+// length of the longest side of the bounds, 1 without bounds
+pub fn model_size(bounds: Option<([f32; 3], [f32; 3])>) -> f32 {
+    let size = match bounds {
+        Some((min, max)) => (0..3).map(|i| max[i] - min[i]).fold(0.0, f32::max),
+        None => 0.0,
+    };
+    if size > 0.0 {
+        size
+    } else {
+        1.0
+    }
+}
+
+// x, y and z from the origin in red, green and blue
+pub fn axis_lines(length: f32) -> Vec<f32> {
+    let mut lines = Vec::new();
+    for (i, &color) in AXIS_COLORS.iter().enumerate() {
+        let mut tip = [0.0; 3];
+        tip[i] = length;
+        push_line(&mut lines, [0.0; 3], tip, color);
+    }
+    lines
+}
+
+// the smallest of 1, 2 or 5 times a power of ten which is at least `size`
+pub fn nice_step(size: f32) -> f32 {
+    if size <= 0.0 || !size.is_finite() {
+        return 1.0;
+    }
+    let power = 10f32.powf(size.log10().floor());
+    // log10 is not exact, which leaves the mantissa of a power of ten a little off 1 or 10
+    let mantissa = size / power;
+    let step = if mantissa <= 1.0 + 1e-5 {
+        1.0
+    } else if mantissa <= 2.0 {
+        2.0
+    } else if mantissa <= 5.0 {
+        5.0
+    } else {
+        10.0
+    };
+    step * power
+}
+
+// a square grid on the floor under the bounds, `cells` cells across. the cells have a round
+// size which covers the model
+pub fn grid_lines(min: [f32; 3], max: [f32; 3], cells: u32) -> Vec<f32> {
+    let cells = cells.max(1);
+    let extent = (max[0] - min[0]).max(max[2] - min[2]);
+    let step = nice_step(extent / cells as f32);
+    let half = step * cells as f32 / 2.0;
+    let center = |i: usize| ((min[i] + max[i]) / 2.0 / step).round() * step;
+    let (x, z, y) = (center(0), center(2), min[1]);
+    let mut lines = Vec::new();
+    for i in 0..=cells {
+        let offset = -half + i as f32 * step;
+        push_line(
+            &mut lines,
+            [x + offset, y, z - half],
+            [x + offset, y, z + half],
+            GRID_COLOR,
+        );
+        push_line(
+            &mut lines,
+            [x - half, y, z + offset],
+            [x + half, y, z + offset],
+            GRID_COLOR,
+        );
+    }
+    lines
+}
+
+// the 12 edges of a box
+pub fn box_lines(min: [f32; 3], max: [f32; 3]) -> Vec<f32> {
+    let corner = |i: usize| {
+        [
+            if i & 1 == 0 { min[0] } else { max[0] },
+            if i & 2 == 0 { min[1] } else { max[1] },
+            if i & 4 == 0 { min[2] } else { max[2] },
+        ]
+    };
+    let mut lines = Vec::new();
+    for i in 0..8 {
+        // each edge once, from the corner with the lower coordinate
+        for bit in [1, 2, 4].iter() {
+            if i & bit == 0 {
+                push_line(&mut lines, corner(i), corner(i | bit), BOX_COLOR);
+            }
+        }
+    }
+    lines
+}
+
+// a line along the normal of each vertex which has one
+pub fn vertex_normal_lines(model: &Model, length: f32) -> Vec<f32> {
+    let mut lines = Vec::new();
+    for (position, normal) in model
+        .vertex
+        .chunks_exact(3)
+        .zip(model.vertex_normal.chunks_exact(3))
+    {
+        let norm = (normal[0] * normal[0] + normal[1] * normal[1] + normal[2] * normal[2]).sqrt();
+        if norm == 0.0 {
+            continue;
+        }
+        let from = [position[0], position[1], position[2]];
+        let to = [
+            from[0] + normal[0] / norm * length,
+            from[1] + normal[1] / norm * length,
+            from[2] + normal[2] / norm * length,
+        ];
+        push_line(&mut lines, from, to, VERTEX_NORMAL_COLOR);
+    }
+    lines
+}
+
+// a line along the normal of each triangle, from its center
+pub fn face_normal_lines(model: &Model, length: f32) -> Vec<f32> {
+    let position = |i: u32| {
+        let i = i as usize * 3;
+        cgmath::Vector3::new(model.vertex[i], model.vertex[i + 1], model.vertex[i + 2])
+    };
+    let mut lines = Vec::new();
+    for triangle in model.indices.vertex_indices.chunks_exact(3) {
+        if !model.triangle_in_range(triangle) {
+            continue;
+        }
+        let (a, b, c) = (
+            position(triangle[0]),
+            position(triangle[1]),
+            position(triangle[2]),
+        );
+        let normal = (b - a).cross(c - a);
+        let norm = (normal.x * normal.x + normal.y * normal.y + normal.z * normal.z).sqrt();
+        if norm == 0.0 {
+            continue;
+        }
+        let center = (a + b + c) / 3.0;
+        let tip = center + normal / norm * length;
+        push_line(&mut lines, center.into(), tip.into(), FACE_NORMAL_COLOR);
+    }
+    lines
+}
+
+// window position of a point, None behind the camera
+pub fn project(
+    view_projection: &Matrix4,
+    point: [f32; 3],
+    window_size: [f32; 2],
+) -> Option<[f32; 2]> {
+    let clip = view_projection * Vector4::new(point[0], point[1], point[2], 1.0);
+    if clip.w <= 0.0 {
+        return None;
+    }
+    let (x, y) = (clip.x / clip.w, clip.y / clip.w);
+    Some([
+        (x * 0.5 + 0.5) * window_size[0],
+        (0.5 - y * 0.5) * window_size[1],
+    ])
+}
+
+fn upload_lines(lines: &[f32]) -> Vertex {
+    Vertex::from_slice(lines, &VertexLayout::floats(&[3, 3]), gl::STATIC_DRAW)
+}
+
+// a layer of lines made for some setting, such as the length of the normals
+struct Layer<K> {
+    key: K,
+    vertex: Vertex,
+}
+
+impl<K: PartialEq> Layer<K> {
+    // the lines for the key, made again when the key has changed
+    fn draw(layer: &mut Option<Layer<K>>, key: K, lines: impl FnOnce() -> Vec<f32>) {
+        if layer.as_ref().is_none_or(|layer| layer.key != key) {
+            *layer = Some(Layer {
+                key,
+                vertex: upload_lines(&lines()),
+            });
+        }
+        if let Some(layer) = layer {
+            layer.vertex.draw_arrays(gl::LINES);
+        }
+    }
+}
+
+// lines drawn over the model to help see it: axes, a grid, the bounding box and normals.
+// the lines of the model are made when they are first shown
+pub struct Overlay {
+    pub shaders: ShaderVariants,
+    bounds: Option<([f32; 3], [f32; 3])>,
+    axes: Option<Layer<()>>,
+    grid: Option<Layer<u32>>,
+    bounding_box: Option<Layer<()>>,
+    vertex_normals: Option<Layer<f32>>,
+    face_normals: Option<Layer<f32>>,
+}
+
+impl Overlay {
+    pub fn new() -> Overlay {
+        Overlay {
+            shaders: ShaderVariants::new("rsc/shader/line_shader.vs", "rsc/shader/line_shader.fs"),
+            bounds: None,
+            axes: None,
+            grid: None,
+            bounding_box: None,
+            vertex_normals: None,
+            face_normals: None,
+        }
+    }
+
+    // forget the lines of the model before
+    pub fn set_model(&mut self, model: &Model) {
+        self.bounds = model.bounding_box();
+        self.axes = None;
+        self.grid = None;
+        self.bounding_box = None;
+        self.vertex_normals = None;
+        self.face_normals = None;
+    }
+
+    // length of the axes, a bit longer than the model
+    pub fn axis_length(&self) -> f32 {
+        model_size(self.bounds) * 0.75
+    }
+
+    // the tips of the axes with their labels and colors
+    pub fn axis_labels(&self) -> Vec<(&'static str, [f32; 3], [f32; 3])> {
+        let length = self.axis_length();
+        ["X", "Y", "Z"]
+            .iter()
+            .enumerate()
+            .map(|(i, &label)| {
+                let mut tip = [0.0; 3];
+                tip[i] = length * 1.05;
+                (label, tip, AXIS_COLORS[i])
+            })
+            .collect()
+    }
+
+    // draw the layers which are shown, with the camera block at the binding
+    pub fn draw(&mut self, model: &Model, state: &DisplayState, camera_binding: GLuint) {
+        let shader = match self.shaders.get(&[]) {
+            Some(shader) if shader.id != 0 => shader,
+            _ => return,
+        };
+        unsafe {
+            shader.use_program();
+            shader.bind_block("Camera", camera_binding);
+            shader.set_uniform("uModel", Matrix4::identity());
+        }
+        if state.is_enabled_axes {
+            let length = self.axis_length();
+            Layer::draw(&mut self.axes, (), || axis_lines(length));
+        }
+        if let Some((min, max)) = self.bounds {
+            if state.is_enabled_grid {
+                let cells = state.grid_cells.max(1) as u32;
+                Layer::draw(&mut self.grid, cells, || grid_lines(min, max, cells));
+            }
+            if state.is_enabled_bounding_box {
+                Layer::draw(&mut self.bounding_box, (), || box_lines(min, max));
+            }
+        }
+        // the length is a share of the model size
+        let key = state.normal_length;
+        let length = key * model_size(self.bounds);
+        if state.is_enabled_vertex_normals {
+            Layer::draw(&mut self.vertex_normals, key, || {
+                vertex_normal_lines(model, length)
+            });
+        }
+        if state.is_enabled_face_normals {
+            Layer::draw(&mut self.face_normals, key, || {
+                face_normal_lines(model, length)
+            });
+        }
+    }
+}
+
+impl Default for Overlay {
+    fn default() -> Overlay {
+        Overlay::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the line pairs as (from, to)
+    fn ends(lines: &[f32]) -> Vec<([f32; 3], [f32; 3])> {
+        lines
+            .chunks_exact(12)
+            .map(|line| ([line[0], line[1], line[2]], [line[6], line[7], line[8]]))
+            .collect()
+    }
+
+    #[test]
+    fn test_ok_nice_step() {
+        assert_eq!(2.0, nice_step(1.2));
+        assert_eq!(0.5, nice_step(0.25));
+        assert_eq!(50.0, nice_step(42.0));
+        assert_eq!(10.0, nice_step(8.0));
+        assert_eq!(1.0, nice_step(1.0));
+        assert_eq!(0.1, nice_step(0.1));
+        assert_eq!(100.0, nice_step(100.0));
+        assert_eq!(1.0, nice_step(0.0));
+    }
+
+    #[test]
+    fn test_ok_grid_lines() {
+        let lines = ends(&grid_lines([-1.0, -2.0, -1.0], [3.0, 2.0, 1.0], 4));
+        // 5 lines along each axis, 1 unit apart on the floor of the bounds
+        assert_eq!(10, lines.len());
+        assert_eq!(([-1.0, -2.0, -2.0], [-1.0, -2.0, 2.0]), lines[0]);
+        assert_eq!(([3.0, -2.0, -2.0], [3.0, -2.0, 2.0]), lines[8]);
+
+        // a step rounded down would leave the ends of the model out
+        let wide = ends(&grid_lines([-2.3, 0.0, 0.0], [2.3, 0.0, 0.0], 4));
+        assert!(wide[0].0[0] <= -2.3);
+        assert!(wide[8].0[0] >= 2.3);
+    }
+
+    #[test]
+    fn test_ok_box_lines() {
+        let ends = ends(&box_lines([0.0; 3], [1.0, 2.0, 3.0]));
+        assert_eq!(12, ends.len());
+        for (from, to) in ends {
+            let changed = (0..3).filter(|&i| from[i] != to[i]).count();
+            assert_eq!(1, changed);
+        }
+    }
+
+    #[test]
+    fn test_ok_normal_lines() {
+        let mut model = Model::new();
+        model.vertex = vec![0.0, 0.0, 0.0, 3.0, 0.0, 0.0, 0.0, 3.0, 0.0];
+        model.vertex_normal = vec![0.0, 0.0, 2.0, 0.0, 0.0, 0.0, 0.0, 0.0, 1.0];
+        model.indices.vertex_indices = vec![0, 1, 2];
+        // a vertex without a normal has no line
+        assert_eq!(
+            vec![
+                ([0.0; 3], [0.0, 0.0, 0.5]),
+                ([0.0, 3.0, 0.0], [0.0, 3.0, 0.5])
+            ],
+            ends(&vertex_normal_lines(&model, 0.5))
+        );
+        assert_eq!(
+            vec![([1.0, 1.0, 0.0], [1.0, 1.0, 2.0])],
+            ends(&face_normal_lines(&model, 2.0))
+        );
+    }
+
+    #[test]
+    fn test_ok_project() {
+        let size = [200.0, 100.0];
+        assert_eq!(
+            Some([100.0, 50.0]),
+            project(&Matrix4::identity(), [0.0; 3], size)
+        );
+        assert_eq!(
+            Some([200.0, 0.0]),
+            project(&Matrix4::identity(), [1.0, 1.0, 0.0], size)
+        );
+        let mut behind = Matrix4::identity();
+        behind.w.w = -1.0;
+        assert_eq!(None, project(&behind, [0.0; 3], size));
+    }
+}
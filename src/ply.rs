@@ -0,0 +1,788 @@
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::model::{Corner, GroupState, Model};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+impl Format {
+    fn name(&self) -> &'static str {
+        match self {
+            Format::Ascii => "ascii",
+            Format::BinaryLittleEndian => "binary_little_endian",
+            Format::BinaryBigEndian => "binary_big_endian",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ScalarType {
+    Char,
+    UChar,
+    Short,
+    UShort,
+    Int,
+    UInt,
+    Float,
+    Double,
+}
+
+impl ScalarType {
+    fn from_name(name: &str) -> Option<ScalarType> {
+        match name {
+            "char" | "int8" => Some(ScalarType::Char),
+            "uchar" | "uint8" => Some(ScalarType::UChar),
+            "short" | "int16" => Some(ScalarType::Short),
+            "ushort" | "uint16" => Some(ScalarType::UShort),
+            "int" | "int32" => Some(ScalarType::Int),
+            "uint" | "uint32" => Some(ScalarType::UInt),
+            "float" | "float32" => Some(ScalarType::Float),
+            "double" | "float64" => Some(ScalarType::Double),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            ScalarType::Char => "char",
+            ScalarType::UChar => "uchar",
+            ScalarType::Short => "short",
+            ScalarType::UShort => "ushort",
+            ScalarType::Int => "int",
+            ScalarType::UInt => "uint",
+            ScalarType::Float => "float",
+            ScalarType::Double => "double",
+        }
+    }
+
+    fn is_float(&self) -> bool {
+        matches!(self, ScalarType::Float | ScalarType::Double)
+    }
+
+    // bytes of a binary value
+    fn size(&self) -> usize {
+        match self {
+            ScalarType::Char | ScalarType::UChar => 1,
+            ScalarType::Short | ScalarType::UShort => 2,
+            ScalarType::Int | ScalarType::UInt | ScalarType::Float => 4,
+            ScalarType::Double => 8,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PropertyType {
+    Scalar(ScalarType),
+    // type of the item count and type of the items
+    List(ScalarType, ScalarType),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Property {
+    pub name: String,
+    pub kind: PropertyType,
+}
+
+// values of one property for every element, all converted to f64
+#[derive(Clone, Debug, PartialEq)]
+pub enum Column {
+    Scalar(Vec<f64>),
+    // items of element i are values[offsets[i]..offsets[i + 1]]
+    List {
+        offsets: Vec<usize>,
+        values: Vec<f64>,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Element {
+    pub name: String,
+    pub count: usize,
+    pub properties: Vec<Property>,
+    pub columns: Vec<Column>,
+}
+
+impl Element {
+    pub fn column(&self, name: &str) -> Option<&Column> {
+        let i = self.properties.iter().position(|p| p.name == name)?;
+        self.columns.get(i)
+    }
+
+    fn scalar(&self, name: &str) -> Option<&Vec<f64>> {
+        match self.column(name)? {
+            Column::Scalar(values) => Some(values),
+            Column::List { .. } => None,
+        }
+    }
+
+    fn scalar_type(&self, name: &str) -> Option<ScalarType> {
+        match self.properties.iter().find(|p| p.name == name)?.kind {
+            PropertyType::Scalar(ty) => Some(ty),
+            PropertyType::List(..) => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlyData {
+    pub format: Format,
+    pub comments: Vec<String>,
+    pub elements: Vec<Element>,
+}
+
+impl PlyData {
+    pub fn element(&self, name: &str) -> Option<&Element> {
+        self.elements.iter().find(|e| e.name == name)
+    }
+}
+
+fn parse_header(text: &str) -> Result<PlyData, Box<dyn Error>> {
+    let mut lines = text.lines().enumerate();
+    match lines.next() {
+        Some((_, line)) if line.trim() == "ply" => {}
+        _ => return Err("not a PLY file".into()),
+    }
+    let mut data = PlyData {
+        format: Format::Ascii,
+        comments: Vec::new(),
+        elements: Vec::new(),
+    };
+    for (i, line) in lines {
+        let counter = i + 1;
+        let invalid = || format!("PLY header is invalid. Line number:{}", counter);
+        let ss: Vec<&str> = line.split_whitespace().collect();
+        match ss.first().copied() {
+            Some("format") => {
+                data.format = match ss.get(1).copied() {
+                    Some("ascii") => Format::Ascii,
+                    Some("binary_little_endian") => Format::BinaryLittleEndian,
+                    Some("binary_big_endian") => Format::BinaryBigEndian,
+                    _ => return Err(invalid().into()),
+                };
+            }
+            Some("comment") | Some("obj_info") => data.comments.push(ss[1..].join(" ")),
+            Some("element") => {
+                if ss.len() != 3 {
+                    return Err(invalid().into());
+                }
+                data.elements.push(Element {
+                    name: ss[1].to_string(),
+                    count: ss[2].parse().map_err(|_| invalid())?,
+                    properties: Vec::new(),
+                    columns: Vec::new(),
+                });
+            }
+            Some("property") => {
+                let scalar = |name: &str| ScalarType::from_name(name).ok_or_else(invalid);
+                let property = match ss.len() {
+                    3 => Property {
+                        name: ss[2].to_string(),
+                        kind: PropertyType::Scalar(scalar(ss[1])?),
+                    },
+                    5 if ss[1] == "list" => Property {
+                        name: ss[4].to_string(),
+                        kind: PropertyType::List(scalar(ss[2])?, scalar(ss[3])?),
+                    },
+                    _ => return Err(invalid().into()),
+                };
+                let element = data.elements.last_mut().ok_or_else(invalid)?;
+                element.properties.push(property);
+            }
+            Some("end_header") => return Ok(data),
+            _ => {}
+        }
+    }
+    Err("PLY header has no end_header".into())
+}
+
+// rows reserved for an element of an ASCII body, whose size is not known in advance
+const ASCII_RESERVE: usize = 1 << 16;
+
+// reads the body values one by one, whatever the format is
+trait ValueReader {
+    fn read(&mut self, ty: ScalarType) -> Result<f64, Box<dyn Error>>;
+    // the most rows with the properties which the rest of the body can hold. the element
+    // count of the header is not trusted to reserve space
+    fn max_rows(&self, properties: &[Property]) -> usize;
+}
+
+struct AsciiReader<'a> {
+    tokens: std::str::SplitAsciiWhitespace<'a>,
+}
+
+impl<'a> ValueReader for AsciiReader<'a> {
+    fn read(&mut self, _ty: ScalarType) -> Result<f64, Box<dyn Error>> {
+        let token = self.tokens.next().ok_or("PLY data is truncated")?;
+        Ok(token
+            .parse::<f64>()
+            .map_err(|_| format!("PLY value is invalid: {}", token))?)
+    }
+
+    fn max_rows(&self, _properties: &[Property]) -> usize {
+        ASCII_RESERVE
+    }
+}
+
+struct BinaryReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    big_endian: bool,
+}
+
+impl<'a> BinaryReader<'a> {
+    fn bytes<const N: usize>(&mut self) -> Result<[u8; N], Box<dyn Error>> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + N)
+            .ok_or("PLY data is truncated")?;
+        self.pos += N;
+        let mut bytes: [u8; N] = bytes.try_into()?;
+        if self.big_endian {
+            bytes.reverse();
+        }
+        Ok(bytes)
+    }
+}
+
+impl<'a> ValueReader for BinaryReader<'a> {
+    fn read(&mut self, ty: ScalarType) -> Result<f64, Box<dyn Error>> {
+        Ok(match ty {
+            ScalarType::Char => i8::from_le_bytes(self.bytes()?) as f64,
+            ScalarType::UChar => u8::from_le_bytes(self.bytes()?) as f64,
+            ScalarType::Short => i16::from_le_bytes(self.bytes()?) as f64,
+            ScalarType::UShort => u16::from_le_bytes(self.bytes()?) as f64,
+            ScalarType::Int => i32::from_le_bytes(self.bytes()?) as f64,
+            ScalarType::UInt => u32::from_le_bytes(self.bytes()?) as f64,
+            ScalarType::Float => f32::from_le_bytes(self.bytes()?) as f64,
+            ScalarType::Double => f64::from_le_bytes(self.bytes()?),
+        })
+    }
+
+    fn max_rows(&self, properties: &[Property]) -> usize {
+        // a list takes at least its count
+        let row_size: usize = properties
+            .iter()
+            .map(|p| match p.kind {
+                PropertyType::Scalar(ty) | PropertyType::List(ty, _) => ty.size(),
+            })
+            .sum();
+        self.data.len().saturating_sub(self.pos) / row_size.max(1)
+    }
+}
+
+fn read_elements(data: &mut PlyData, reader: &mut dyn ValueReader) -> Result<(), Box<dyn Error>> {
+    for element in data.elements.iter_mut() {
+        let capacity = element.count.min(reader.max_rows(&element.properties));
+        element.columns = element
+            .properties
+            .iter()
+            .map(|p| match p.kind {
+                PropertyType::Scalar(_) => Column::Scalar(Vec::with_capacity(capacity)),
+                PropertyType::List(..) => Column::List {
+                    offsets: vec![0],
+                    values: Vec::new(),
+                },
+            })
+            .collect();
+        for _ in 0..element.count {
+            for (property, column) in element.properties.iter().zip(element.columns.iter_mut()) {
+                match (property.kind, column) {
+                    (PropertyType::Scalar(ty), Column::Scalar(values)) => {
+                        values.push(reader.read(ty)?);
+                    }
+                    (PropertyType::List(count_ty, item_ty), Column::List { offsets, values }) => {
+                        let count = reader.read(count_ty)?;
+                        if count < 0.0 {
+                            return Err("PLY list size is negative".into());
+                        }
+                        for _ in 0..count as usize {
+                            values.push(reader.read(item_ty)?);
+                        }
+                        offsets.push(values.len());
+                    }
+                    _ => unreachable!(),
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+// parse the header and all elements of a PLY file
+pub fn parse_ply_data(bytes: &[u8]) -> Result<PlyData, Box<dyn Error>> {
+    const END_HEADER: &[u8] = b"end_header";
+    let end = bytes
+        .windows(END_HEADER.len())
+        .position(|w| w == END_HEADER)
+        .ok_or("PLY header has no end_header")?;
+    let body_start = match bytes[end..].iter().position(|&b| b == b'\n') {
+        Some(newline) => end + newline + 1,
+        None => bytes.len(),
+    };
+    let header = std::str::from_utf8(&bytes[..body_start])?;
+    let mut data = parse_header(header)?;
+    let body = &bytes[body_start..];
+    match data.format {
+        Format::Ascii => {
+            let text = std::str::from_utf8(body)?;
+            let mut reader = AsciiReader {
+                tokens: text.split_ascii_whitespace(),
+            };
+            read_elements(&mut data, &mut reader)?;
+        }
+        Format::BinaryLittleEndian | Format::BinaryBigEndian => {
+            let mut reader = BinaryReader {
+                data: body,
+                pos: 0,
+                big_endian: data.format == Format::BinaryBigEndian,
+            };
+            read_elements(&mut data, &mut reader)?;
+        }
+    }
+    Ok(data)
+}
+
+// names of the vertex properties mapped to the model, in order of preference
+const POSITION_NAMES: [&str; 3] = ["x", "y", "z"];
+const NORMAL_NAMES: [&str; 3] = ["nx", "ny", "nz"];
+const COLOR_NAMES: [[&str; 3]; 2] = [
+    ["red", "green", "blue"],
+    ["diffuse_red", "diffuse_green", "diffuse_blue"],
+];
+const UV_NAMES: [[&str; 2]; 4] = [
+    ["u", "v"],
+    ["s", "t"],
+    ["texture_u", "texture_v"],
+    ["texture_s", "texture_t"],
+];
+
+fn columns<'a>(element: &'a Element, names: &[&str]) -> Option<Vec<&'a Vec<f64>>> {
+    names.iter().map(|name| element.scalar(name)).collect()
+}
+
+// convert PLY data to a model. unknown scalar vertex properties are kept in vertex_properties
+pub fn ply_to_model(data: &PlyData) -> Result<Model, Box<dyn Error>> {
+    let mut model = Model::new();
+    let vertex = data
+        .element("vertex")
+        .ok_or("PLY file has no vertex element")?;
+    let positions = columns(vertex, &POSITION_NAMES).ok_or("PLY vertex has no x, y and z")?;
+    let normals = columns(vertex, &NORMAL_NAMES);
+    let colors = COLOR_NAMES
+        .iter()
+        .find_map(|names| Some((columns(vertex, names)?, vertex.scalar_type(names[0])?)));
+    let uvs = UV_NAMES.iter().find_map(|names| columns(vertex, names));
+    let mut used: Vec<&str> = POSITION_NAMES.to_vec();
+    if normals.is_some() {
+        used.extend_from_slice(&NORMAL_NAMES);
+    }
+    if let Some(names) = COLOR_NAMES.iter().find(|n| columns(vertex, *n).is_some()) {
+        used.extend_from_slice(names);
+    }
+    if let Some(names) = UV_NAMES.iter().find(|n| columns(vertex, *n).is_some()) {
+        used.extend_from_slice(names);
+    }
+
+    for i in 0..vertex.count {
+        model.vertex.extend(positions.iter().map(|c| c[i] as f32));
+        if let Some(normals) = &normals {
+            model
+                .vertex_normal_tmp
+                .extend(normals.iter().map(|c| c[i] as f32));
+        }
+        if let Some((colors, ty)) = &colors {
+            // integer colors are 0..255
+            let scale = if ty.is_float() { 1.0 } else { 1.0 / 255.0 };
+            model
+                .vertex_color
+                .extend(colors.iter().map(|c| (c[i] * scale) as f32));
+        }
+        if let Some(uvs) = &uvs {
+            model.vertex_tex.extend(uvs.iter().map(|c| c[i] as f32));
+        }
+    }
+    for property in vertex.properties.iter() {
+        if used.contains(&property.name.as_str()) {
+            continue;
+        }
+        if let Some(values) = vertex.scalar(&property.name) {
+            let values = values.iter().map(|v| *v as f32).collect();
+            model
+                .vertex_properties
+                .push((property.name.clone(), values));
+        }
+    }
+
+    let face = match data.element("face") {
+        Some(face) => face,
+        None => return Ok(model),
+    };
+    let (offsets, values) = match face
+        .column("vertex_indices")
+        .or_else(|| face.column("vertex_index"))
+    {
+        Some(Column::List { offsets, values }) => (offsets, values),
+        _ => return Err("PLY face has no vertex_indices".into()),
+    };
+    let state = GroupState::new();
+    let mut polygon = Vec::new();
+    for range in offsets.windows(2) {
+        polygon.clear();
+        for &index in values[range[0]..range[1]].iter() {
+            if index < 0.0 || index as usize >= vertex.count {
+                return Err(format!("PLY face index is out of range: {}", index).into());
+            }
+            let index = index as u32;
+            polygon.push(Corner {
+                vertex: index,
+                texture: uvs.as_ref().map(|_| index),
+                normal: normals.as_ref().map(|_| index),
+            });
+        }
+        model.add_polygon(&polygon, &state);
+    }
+    Ok(model)
+}
+
+pub fn parse_ply(bytes: &[u8]) -> Result<Model, Box<dyn Error>> {
+    ply_to_model(&parse_ply_data(bytes)?)
+}
+
+pub fn load_ply(path: &str) -> Result<Model, Box<dyn Error>> {
+    parse_ply(&fs::read(path)?)
+}
+
+fn write_value<W: Write>(
+    writer: &mut W,
+    format: Format,
+    ty: ScalarType,
+    value: f64,
+) -> Result<(), Box<dyn Error>> {
+    let bytes = match ty {
+        ScalarType::UChar => vec![value.round().clamp(0.0, 255.0) as u8],
+        ScalarType::Int => (value as i32).to_le_bytes().to_vec(),
+        ScalarType::Float => (value as f32).to_le_bytes().to_vec(),
+        _ => unreachable!("the writer only uses uchar, int and float"),
+    };
+    match format {
+        Format::Ascii => {
+            if ty.is_float() {
+                write!(writer, "{}", value as f32)?;
+            } else {
+                write!(writer, "{}", value)?;
+            }
+        }
+        Format::BinaryLittleEndian => writer.write_all(&bytes)?,
+        Format::BinaryBigEndian => {
+            let mut bytes = bytes;
+            bytes.reverse();
+            writer.write_all(&bytes)?;
+        }
+    }
+    Ok(())
+}
+
+// PLY has one set of attributes per vertex, so OBJ corners which share a position but not
+// the texture coordinate or the normal are written as separate vertices
+pub fn write_ply<W: Write>(
+    writer: &mut W,
+    model: &Model,
+    format: Format,
+) -> Result<(), Box<dyn Error>> {
+    let vertex_num = model.vertex.len() / 3;
+    let has_color = model.vertex_color.len() == vertex_num * 3 && vertex_num > 0;
+    let has_uv = model.corners.iter().any(|c| c.texture.is_some());
+    let has_normal = model.corners.iter().any(|c| c.normal.is_some());
+
+    // unique (vertex, texture, normal) combinations become the PLY vertices
+    let mut keys: Vec<Corner> = Vec::new();
+    let mut key_indices: HashMap<(u32, Option<u32>, Option<u32>), usize> = HashMap::new();
+    let mut faces: Vec<usize> = Vec::with_capacity(model.corners.len());
+    let mut add_key = |corner: Corner| {
+        let key = (corner.vertex, corner.texture, corner.normal);
+        *key_indices.entry(key).or_insert_with(|| {
+            keys.push(corner);
+            keys.len() - 1
+        })
+    };
+    for corner in model.corners.iter() {
+        if corner.vertex as usize >= vertex_num
+            || matches!(corner.texture, Some(t) if t as usize * 2 >= model.vertex_tex.len())
+            || matches!(corner.normal, Some(n) if n as usize * 3 >= model.vertex_normal_tmp.len())
+        {
+            return Err("polygon index is out of range".into());
+        }
+        faces.push(add_key(*corner));
+    }
+    // keep vertices which no polygon uses, such as the points of a point cloud
+    let mut referenced = vec![false; vertex_num];
+    model
+        .corners
+        .iter()
+        .for_each(|c| referenced[c.vertex as usize] = true);
+    for (v, _) in referenced.iter().enumerate().filter(|(_, r)| !**r) {
+        add_key(Corner {
+            vertex: v as u32,
+            texture: None,
+            normal: None,
+        });
+    }
+
+    writeln!(writer, "ply")?;
+    writeln!(writer, "format {} 1.0", format.name())?;
+    writeln!(writer, "comment written by obj_viewer")?;
+    writeln!(writer, "element vertex {}", keys.len())?;
+    let mut vertex_properties: Vec<(&str, ScalarType)> = POSITION_NAMES
+        .iter()
+        .map(|name| (*name, ScalarType::Float))
+        .collect();
+    if has_normal {
+        vertex_properties.extend(NORMAL_NAMES.iter().map(|name| (*name, ScalarType::Float)));
+    }
+    if has_color {
+        vertex_properties.extend(COLOR_NAMES[0].iter().map(|name| (*name, ScalarType::UChar)));
+    }
+    if has_uv {
+        vertex_properties.extend(UV_NAMES[1].iter().map(|name| (*name, ScalarType::Float)));
+    }
+    let extra: Vec<&(String, Vec<f32>)> = model
+        .vertex_properties
+        .iter()
+        .filter(|(_, values)| values.len() == vertex_num)
+        .collect();
+    for (name, ty) in vertex_properties.iter() {
+        writeln!(writer, "property {} {}", ty.name(), name)?;
+    }
+    for (name, _) in extra.iter() {
+        writeln!(writer, "property float {}", name)?;
+    }
+    writeln!(writer, "element face {}", model.polygon_sizes.len())?;
+    writeln!(writer, "property list uchar int vertex_indices")?;
+    writeln!(writer, "end_header")?;
+
+    let separator = |writer: &mut W| -> Result<(), Box<dyn Error>> {
+        if format == Format::Ascii {
+            write!(writer, " ")?;
+        }
+        Ok(())
+    };
+    for key in keys.iter() {
+        let v = key.vertex as usize;
+        let mut values: Vec<(ScalarType, f64)> = Vec::new();
+        values.extend(
+            model.vertex[v * 3..v * 3 + 3]
+                .iter()
+                .map(|x| (ScalarType::Float, *x as f64)),
+        );
+        if has_normal {
+            let normal = match key.normal {
+                Some(n) => &model.vertex_normal_tmp[n as usize * 3..n as usize * 3 + 3],
+                None => &[0.0; 3][..],
+            };
+            values.extend(normal.iter().map(|x| (ScalarType::Float, *x as f64)));
+        }
+        if has_color {
+            let color = &model.vertex_color[v * 3..v * 3 + 3];
+            values.extend(color.iter().map(|x| (ScalarType::UChar, *x as f64 * 255.0)));
+        }
+        if has_uv {
+            let uv = match key.texture {
+                Some(t) => &model.vertex_tex[t as usize * 2..t as usize * 2 + 2],
+                None => &[0.0; 2][..],
+            };
+            values.extend(uv.iter().map(|x| (ScalarType::Float, *x as f64)));
+        }
+        values.extend(extra.iter().map(|(_, p)| (ScalarType::Float, p[v] as f64)));
+        for (i, (ty, value)) in values.iter().enumerate() {
+            if i > 0 {
+                separator(writer)?;
+            }
+            write_value(writer, format, *ty, *value)?;
+        }
+        if format == Format::Ascii {
+            writeln!(writer)?;
+        }
+    }
+
+    let mut start = 0;
+    for &size in model.polygon_sizes.iter() {
+        if size > 255 {
+            return Err("PLY faces are limited to 255 vertices".into());
+        }
+        write_value(writer, format, ScalarType::UChar, size as f64)?;
+        for &index in faces[start..start + size as usize].iter() {
+            separator(writer)?;
+            write_value(writer, format, ScalarType::Int, index as f64)?;
+        }
+        if format == Format::Ascii {
+            writeln!(writer)?;
+        }
+        start += size as usize;
+    }
+    Ok(())
+}
+
+pub fn save_ply(path: &Path, model: &Model, format: Format) -> Result<(), Box<dyn Error>> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    write_ply(&mut writer, model, format)?;
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ASCII: &str = "\
+ply
+format ascii 1.0
+comment colored square
+element vertex 4
+property float x
+property float y
+property float z
+property float nx
+property float ny
+property float nz
+property uchar red
+property uchar green
+property uchar blue
+property float confidence
+element face 1
+property list uchar int vertex_indices
+property uchar flags
+end_header
+0 0 0 0 0 1 255 0 0 0.5
+1 0 0 0 0 1 0 255 0 1
+1 1 0 0 0 1 0 0 255 1
+0 1 0 0 0 1 255 255 255 0.25
+4 0 1 2 3 7
+";
+
+    #[test]
+    fn test_ok_parse_ascii() {
+        let data = parse_ply_data(ASCII.as_bytes()).unwrap();
+        assert_eq!(Format::Ascii, data.format);
+        assert_eq!(vec!["colored square".to_string()], data.comments);
+        assert_eq!(
+            Some(&Column::Scalar(vec![7.0])),
+            data.element("face").unwrap().column("flags")
+        );
+
+        let model = ply_to_model(&data).unwrap();
+        assert_eq!(12, model.vertex.len());
+        assert_eq!(12, model.vertex_normal_tmp.len());
+        assert_eq!(vec![1.0, 0.0, 0.0], model.vertex_color[..3].to_vec());
+        assert_eq!(vec![1.0, 1.0, 1.0], model.vertex_color[9..].to_vec());
+        assert_eq!(
+            vec![("confidence".to_string(), vec![0.5, 1.0, 1.0, 0.25])],
+            model.vertex_properties
+        );
+        assert_eq!(vec![4], model.polygon_sizes);
+        assert_eq!(vec![0, 1, 2, 0, 2, 3], model.indices.vertex_indices);
+        assert_eq!(Some(3), model.corners[3].normal);
+    }
+
+    #[test]
+    fn test_ng_parse() {
+        assert!(parse_ply(b"obj\nend_header\n").is_err());
+        assert!(parse_ply(ASCII.replace("end_header", "end").as_bytes()).is_err());
+        assert!(parse_ply(
+            ASCII
+                .replace("property float x", "property half x")
+                .as_bytes()
+        )
+        .is_err());
+        assert!(parse_ply(ASCII.replace("4 0 1 2 3 7", "4 0 1 2 3").as_bytes()).is_err());
+        assert!(parse_ply(ASCII.replace("4 0 1 2 3 7", "3 0 1 4 7").as_bytes()).is_err());
+        assert!(parse_ply(ASCII.replace("255 0 0 0.5", "255 0 0 x").as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_ng_parse_huge_count() {
+        // the count is not reserved up front, and the missing rows are an error
+        for format in ["ascii", "binary_little_endian"].iter() {
+            let ply = format!(
+                "ply\nformat {} 1.0\nelement vertex 4000000000000\n\
+                 property float x\nproperty float y\nproperty float z\nend_header\n",
+                format
+            );
+            assert!(parse_ply_data(ply.as_bytes()).is_err());
+        }
+    }
+
+    #[test]
+    fn test_ok_binary_round_trip() {
+        let model = parse_ply(ASCII.as_bytes()).unwrap();
+        let formats = [
+            Format::Ascii,
+            Format::BinaryLittleEndian,
+            Format::BinaryBigEndian,
+        ];
+        for format in formats.iter() {
+            let mut buf = Vec::new();
+            write_ply(&mut buf, &model, *format).unwrap();
+            let loaded = parse_ply(&buf).unwrap();
+            assert_eq!(model.vertex, loaded.vertex);
+            assert_eq!(model.vertex_normal_tmp, loaded.vertex_normal_tmp);
+            assert_eq!(model.vertex_color, loaded.vertex_color);
+            assert_eq!(model.vertex_properties, loaded.vertex_properties);
+            assert_eq!(model.corners, loaded.corners);
+            assert_eq!(model.polygon_sizes, loaded.polygon_sizes);
+        }
+    }
+
+    #[test]
+    fn test_ok_parse_big_endian() {
+        let mut buf = b"ply\nformat binary_big_endian 1.0\nelement vertex 3\n\
+property short x\nproperty short y\nproperty short z\nelement face 1\n\
+property list uchar ushort vertex_index\nend_header\n"
+            .to_vec();
+        for value in [0i16, 0, 0, 2, 0, 0, 0, -3, 0].iter() {
+            buf.extend_from_slice(&value.to_be_bytes());
+        }
+        buf.push(3);
+        for index in [0u16, 1, 2].iter() {
+            buf.extend_from_slice(&index.to_be_bytes());
+        }
+        let model = parse_ply(&buf).unwrap();
+        assert_eq!(
+            vec![0.0, 0.0, 0.0, 2.0, 0.0, 0.0, 0.0, -3.0, 0.0],
+            model.vertex
+        );
+        assert_eq!(vec![0, 1, 2], model.indices.vertex_indices);
+        assert!(parse_ply(&buf[..buf.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_ok_write_split_corners() {
+        // one position used with two different texture coordinates
+        let mut model = Model::new();
+        model.vertex = vec![0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0];
+        model.vertex_tex = vec![0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.5, 0.5];
+        let corner = |vertex, texture| Corner {
+            vertex,
+            texture: Some(texture),
+            normal: None,
+        };
+        let state = GroupState::new();
+        model.add_polygon(&[corner(0, 0), corner(1, 1), corner(2, 2)], &state);
+        model.add_polygon(&[corner(0, 3), corner(2, 2), corner(1, 1)], &state);
+
+        let mut buf = Vec::new();
+        write_ply(&mut buf, &model, Format::Ascii).unwrap();
+        let loaded = parse_ply(&buf).unwrap();
+        assert_eq!(12, loaded.vertex.len());
+        assert_eq!(vec![0, 1, 2, 3, 2, 1], loaded.indices.vertex_indices);
+        assert_eq!(vec![0.5, 0.5], loaded.vertex_tex[6..].to_vec());
+    }
+}
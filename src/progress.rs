@@ -0,0 +1,127 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+pub const CANCELLED: &str = "loading was cancelled";
+
+// bytes read at once between checks for cancellation
+const READ_BLOCK: usize = 1 << 20;
+
+// progress of a load, shared between the loading thread and the one showing it
+pub struct Progress {
+    bytes_read: AtomicU64,
+    total_bytes: AtomicU64,
+    faces: AtomicU64,
+    cancelled: AtomicBool,
+}
+
+impl Progress {
+    pub fn new() -> Progress {
+        Progress {
+            bytes_read: AtomicU64::new(0),
+            total_bytes: AtomicU64::new(0),
+            faces: AtomicU64::new(0),
+            cancelled: AtomicBool::new(false),
+        }
+    }
+
+    pub fn set_total_bytes(&self, bytes: u64) {
+        self.total_bytes.store(bytes, Ordering::Relaxed);
+    }
+
+    pub fn add_bytes(&self, bytes: u64) {
+        self.bytes_read.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub fn add_faces(&self, faces: u64) {
+        self.faces.fetch_add(faces, Ordering::Relaxed);
+    }
+
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes_read.load(Ordering::Relaxed)
+    }
+
+    // 0 while the size is not known
+    pub fn total_bytes(&self) -> u64 {
+        self.total_bytes.load(Ordering::Relaxed)
+    }
+
+    pub fn faces(&self) -> u64 {
+        self.faces.load(Ordering::Relaxed)
+    }
+
+    // share of the bytes read in 0..1, 0 while the size is not known
+    pub fn fraction(&self) -> f32 {
+        match self.total_bytes() {
+            0 => 0.0,
+            total => (self.bytes_read() as f64 / total as f64).min(1.0) as f32,
+        }
+    }
+
+    // loaders stop at their next check and return the CANCELLED error
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    pub fn check_cancelled(&self) -> Result<(), Box<dyn Error>> {
+        if self.is_cancelled() {
+            return Err(CANCELLED.into());
+        }
+        Ok(())
+    }
+}
+
+impl Default for Progress {
+    fn default() -> Progress {
+        Progress::new()
+    }
+}
+
+// read a whole file in blocks, counting the bytes read and stopping when cancelled
+pub fn read_file(path: &Path, progress: &Progress) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut file = File::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let len = file.metadata()?.len();
+    progress.set_total_bytes(len);
+    let mut data = Vec::with_capacity(len as usize);
+    let mut block = vec![0; READ_BLOCK];
+    loop {
+        progress.check_cancelled()?;
+        let n = file.read(&mut block)?;
+        if n == 0 {
+            break;
+        }
+        data.extend_from_slice(&block[..n]);
+        progress.add_bytes(n as u64);
+    }
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_ok_read_file() {
+        let path = std::env::temp_dir().join("obj_viewer_test_read_file.obj");
+        let data = vec![b'v'; READ_BLOCK + 10];
+        fs::write(&path, &data).unwrap();
+
+        let progress = Progress::new();
+        assert_eq!(0.0, progress.fraction());
+        assert_eq!(data, read_file(&path, &progress).unwrap());
+        assert_eq!(data.len() as u64, progress.bytes_read());
+        assert_eq!(1.0, progress.fraction());
+
+        progress.cancel();
+        let error = read_file(&path, &progress).err().unwrap();
+        assert_eq!(CANCELLED, error.to_string());
+        fs::remove_file(&path).unwrap();
+    }
+}
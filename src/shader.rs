@@ -1,165 +1,611 @@
-use cgmath::Array;
-use cgmath::Matrix;
 use gl;
 use gl::types::*;
 
-use std::ffi::{CStr, CString};
-use std::fs::File;
-use std::io::Read;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::error::Error;
+use std::ffi::CString;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::ptr;
 use std::str;
 
+use crate::glsl::{self, Source, UniformDeclaration};
+use crate::model::Model;
+use crate::uniform::{self, ActiveVariable, EditableValue, UniformValue};
+
 #[allow(dead_code)]
 type Vector3 = cgmath::Vector3<f32>;
 #[allow(dead_code)]
 type Matrix4 = cgmath::Matrix4<f32>;
 
+// one line of a GLSL info log. the line is None for messages without a location
+#[derive(Debug, PartialEq)]
+pub struct LogMessage {
+    pub file: String,
+    pub line: Option<u32>,
+    pub message: String,
+}
+
+impl fmt::Display for LogMessage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}:{}: {}", self.file, line, self.message),
+            None => write!(f, "{}: {}", self.file, self.message),
+        }
+    }
+}
+
+// a shader which failed to read, compile or link
+#[derive(Debug)]
+pub struct ShaderError {
+    pub messages: Vec<LogMessage>,
+}
+
+impl ShaderError {
+    pub fn new(file: &str, message: &str) -> ShaderError {
+        ShaderError {
+            messages: vec![LogMessage {
+                file: file.to_string(),
+                line: None,
+                message: message.to_string(),
+            }],
+        }
+    }
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, message) in self.messages.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", message)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for ShaderError {}
+
+// "0:12(5): error: ..." (mesa), "0:12: ..." (amd) or "0(12) : error C0000: ..." (nvidia).
+// returns the line and the message
+fn parse_location(text: &str) -> Option<(u32, &str)> {
+    let digits = |s: &str| s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let n = digits(text);
+    if n == 0 {
+        return None;
+    }
+    let rest = &text[n..];
+    if let Some(rest) = rest.strip_prefix(':') {
+        let n = digits(rest);
+        let line = rest[..n].parse().ok()?;
+        let mut rest = &rest[n..];
+        if rest.starts_with('(') {
+            rest = &rest[rest.find(')')? + 1..];
+        }
+        Some((line, rest.trim_start().strip_prefix(':')?.trim()))
+    } else if let Some(rest) = rest.strip_prefix('(') {
+        let end = rest.find(')')?;
+        let line = rest[..end].parse().ok()?;
+        Some((line, rest[end + 1..].trim_start().strip_prefix(':')?.trim()))
+    } else {
+        None
+    }
+}
+
+// split an info log of the driver into messages of `file`
+pub fn parse_info_log(file: &str, log: &str) -> Vec<LogMessage> {
+    let mut messages = Vec::new();
+    for text in log
+        .lines()
+        .map(|text| text.trim_matches(|c: char| c.is_whitespace() || c == '\0'))
+    {
+        if text.is_empty() {
+            continue;
+        }
+        // amd puts the severity in front of the location
+        let (severity, located) = match text.find(": ") {
+            Some(i)
+                if text[..i].eq_ignore_ascii_case("error")
+                    || text[..i].eq_ignore_ascii_case("warning") =>
+            {
+                (&text[..i + 2], &text[i + 2..])
+            }
+            _ => ("", text),
+        };
+        let (line, message) = match parse_location(located) {
+            Some((line, message)) => (Some(line), format!("{}{}", severity, message)),
+            None => (None, text.to_string()),
+        };
+        messages.push(LogMessage {
+            file: file.to_string(),
+            line,
+            message,
+        });
+    }
+    messages
+}
+
 pub struct Shader {
     pub id: u32,
+    vertex_path: String,
+    fragment_path: String,
+    defines: Vec<String>,
+    // the files with the ones they include, as of the last build which read them all
+    files: Vec<PathBuf>,
+    // active variables of the program, queried once it is linked
+    uniforms: HashMap<String, ActiveVariable>,
+    attributes: HashMap<String, ActiveVariable>,
+    blocks: HashMap<String, GLuint>,
+    // uniforms as declared in the code, with their annotations
+    declarations: Vec<UniformDeclaration>,
+    // problems with setting uniforms, each reported once per program
+    reported: RefCell<Vec<String>>,
 }
 
-// #[allow(dead_code)]
+// the unsafe functions need a current OpenGL context
+#[allow(clippy::missing_safety_doc)]
 impl Shader {
     pub fn new() -> Shader {
-        Shader { id: 0 }
-    }
-
-    pub fn setup(&mut self, vertex_path: &str, fragment_path: &str) {
-        // read input files
-        let mut vertex_file = File::open(vertex_path)
-            .unwrap_or_else(|_| panic!("failed to open file: {}", vertex_path));
-        let mut vertex_code = String::new();
-
-        let mut fragment_file = File::open(fragment_path)
-            .unwrap_or_else(|_| panic!("failed to open file:{}", fragment_path));
-        let mut fragment_code = String::new();
-
-        // create cstring
-        // read read shader.fs and shader.vs as CString
-        vertex_file
-            .read_to_string(&mut vertex_code)
-            .expect("failed to read vertex shader file");
-        let cstr_vertex_code = CString::new(vertex_code.as_bytes()).unwrap();
-        fragment_file
-            .read_to_string(&mut fragment_code)
-            .expect("failed to read fragment shader");
-        let cstr_fragment_code = CString::new(fragment_code.as_bytes()).unwrap();
+        Shader {
+            id: 0,
+            vertex_path: String::new(),
+            fragment_path: String::new(),
+            defines: Vec::new(),
+            files: Vec::new(),
+            uniforms: HashMap::new(),
+            attributes: HashMap::new(),
+            blocks: HashMap::new(),
+            declarations: Vec::new(),
+            reported: RefCell::new(Vec::new()),
+        }
+    }
+
+    pub fn setup(&mut self, vertex_path: &str, fragment_path: &str) -> Result<(), ShaderError> {
+        self.setup_variant(vertex_path, fragment_path, &[])
+    }
 
+    // build the program from the two files with a #define for each of `defines`. the paths
+    // are kept for reload() also when it fails
+    pub fn setup_variant(
+        &mut self,
+        vertex_path: &str,
+        fragment_path: &str,
+        defines: &[String],
+    ) -> Result<(), ShaderError> {
+        self.vertex_path = vertex_path.to_string();
+        self.fragment_path = fragment_path.to_string();
+        self.defines = defines.to_vec();
+        self.files = vec![PathBuf::from(vertex_path), PathBuf::from(fragment_path)];
+        self.reload()
+    }
+
+    // build the program again from its files. the program in use stays when this fails
+    pub fn reload(&mut self) -> Result<(), ShaderError> {
+        let mut read = |path: &Path| fs::read_to_string(path);
+        let vertex_source =
+            glsl::preprocess(Path::new(&self.vertex_path), &self.defines, &mut read)?;
+        let fragment_source =
+            glsl::preprocess(Path::new(&self.fragment_path), &self.defines, &mut read)?;
+        self.files = vertex_source.files.clone();
+        self.files.extend(fragment_source.files.iter().cloned());
         unsafe {
-            //vertex shader
-            let vertex = gl::CreateShader(gl::VERTEX_SHADER);
-            gl::ShaderSource(vertex, 1, &cstr_vertex_code.as_ptr(), ptr::null());
-            gl::CompileShader(vertex);
-            self.check_compile_errors(vertex, "VERTEX");
-
-            // fragment shader
-            let fragment = gl::CreateShader(gl::FRAGMENT_SHADER);
-            gl::ShaderSource(fragment, 1, &cstr_fragment_code.as_ptr(), ptr::null());
-            gl::CompileShader(fragment);
-            self.check_compile_errors(fragment, "FRAGMENT");
+            let vertex = compile(gl::VERTEX_SHADER, &vertex_source)?;
+            let fragment = match compile(gl::FRAGMENT_SHADER, &fragment_source) {
+                Ok(fragment) => fragment,
+                Err(e) => {
+                    gl::DeleteShader(vertex);
+                    return Err(e);
+                }
+            };
 
             // shader program
-            self.id = gl::CreateProgram();
-            gl::AttachShader(self.id, vertex);
-            gl::AttachShader(self.id, fragment);
-            gl::LinkProgram(self.id);
-            self.check_compile_errors(self.id, "PROGRAM");
+            let id = gl::CreateProgram();
+            gl::AttachShader(id, vertex);
+            gl::AttachShader(id, fragment);
+            // the inputs take the attributes of the vertex buffer in the order they are
+            // declared, also when a variant does not use some of them
+            for (i, name) in glsl::vertex_inputs(&vertex_source.code).iter().enumerate() {
+                if let Ok(name) = CString::new(name.as_str()) {
+                    gl::BindAttribLocation(id, i as GLuint, name.as_ptr());
+                }
+            }
+            gl::LinkProgram(id);
 
             // delete
             gl::DeleteShader(vertex);
             gl::DeleteShader(fragment);
+
+            //doc : http://docs.gl/gl3/glGetProgram
+            // リンクが成功していれば、第３引数にgl::TRUEが入る
+            let mut success = gl::FALSE as GLint;
+            gl::GetProgramiv(id, gl::LINK_STATUS, &mut success);
+            if success == gl::FALSE as GLint {
+                let log = info_log(id, true);
+                gl::DeleteProgram(id);
+                return Err(log_error("program", &log, "failed to link"));
+            }
+            if self.id != 0 {
+                gl::DeleteProgram(self.id);
+            }
+            self.id = id;
+            self.uniforms = uniform::active_uniforms(id);
+            self.attributes = uniform::active_attributes(id);
+            self.blocks = uniform::active_blocks(id);
+            self.reported.borrow_mut().clear();
+            self.declarations = glsl::uniform_declarations(&vertex_source.code);
+            for declaration in glsl::uniform_declarations(&fragment_source.code) {
+                if !self.declarations.iter().any(|d| d.name == declaration.name) {
+                    self.declarations.push(declaration);
+                }
+            }
+
+            // uniforms start with their annotated defaults
+            let mut program = 0;
+            gl::GetIntegerv(gl::CURRENT_PROGRAM, &mut program);
+            gl::UseProgram(id);
+            for declaration in self.declarations.iter() {
+                if declaration.annotation.default.is_empty() {
+                    continue;
+                }
+                if let Some(value) = self.editable_value(declaration) {
+                    self.set_editable(&declaration.name, &value);
+                }
+            }
+            gl::UseProgram(program as GLuint);
+        }
+        Ok(())
+    }
+
+    // the source files of the program with the files they include
+    pub fn files(&self) -> Vec<PathBuf> {
+        let mut files: Vec<PathBuf> = Vec::new();
+        for file in self.files.iter() {
+            if !files.contains(file) {
+                files.push(file.clone());
+            }
         }
+        files
     }
 
     pub unsafe fn use_program(&self) {
         gl::UseProgram(self.id)
     }
 
+    // the variables are those of the program in use
+    pub fn uniform(&self, name: &str) -> Option<&ActiveVariable> {
+        self.uniforms.get(name)
+    }
+
+    pub fn attribute(&self, name: &str) -> Option<&ActiveVariable> {
+        self.attributes.get(name)
+    }
+
+    // the default of a declared uniform if it is active with a type which can be edited
+    fn editable_value(&self, declaration: &UniformDeclaration) -> Option<EditableValue> {
+        let value = EditableValue::from_declaration(declaration)?;
+        let uniform = self.uniforms.get(&declaration.name)?;
+        if !uniform::accepts(uniform.type_, value.type_()) {
+            return None;
+        }
+        Some(value)
+    }
+
+    // uniforms for the uniform editor in the order they are declared, with their defaults
+    pub fn editable_uniforms(&self) -> Vec<(&UniformDeclaration, EditableValue)> {
+        self.declarations
+            .iter()
+            .filter(|declaration| !declaration.annotation.hidden)
+            .filter_map(|declaration| Some((declaration, self.editable_value(declaration)?)))
+            .collect()
+    }
+
+    // problems with setting uniforms since the program was built
+    pub fn warnings(&self) -> Vec<String> {
+        self.reported.borrow().clone()
+    }
+
+    fn report(&self, problem: String) {
+        let mut reported = self.reported.borrow_mut();
+        if !reported.contains(&problem) {
+            println!("Shader warning: {}", problem);
+            reported.push(problem);
+        }
+    }
+
+    // the uniform if it is active with a type which takes T. a misspelled name or a wrong
+    // type is reported once
+    fn checked_uniform<T: UniformValue>(&self, name: &str) -> Option<&ActiveVariable> {
+        if self.id == 0 {
+            return None;
+        }
+        let uniform = match self.uniforms.get(name) {
+            Some(uniform) => uniform,
+            None => {
+                self.report(format!(
+                    "uniform {} is not active in {} and {}",
+                    name, self.vertex_path, self.fragment_path
+                ));
+                return None;
+            }
+        };
+        if !uniform::accepts(uniform.type_, T::TYPE) {
+            self.report(format!(
+                "uniform {} is a {}, not a {}",
+                name,
+                uniform::type_name(uniform.type_),
+                uniform::type_name(T::TYPE)
+            ));
+            return None;
+        }
+        Some(uniform)
+    }
+
     // send data to GPU
+    pub unsafe fn set_uniform<T: UniformValue>(&self, name: &str, value: T) {
+        if let Some(uniform) = self.checked_uniform::<T>(name) {
+            T::upload(uniform.location, &[value]);
+        }
+    }
+
+    // set the first values.len() elements of a uniform array
+    pub unsafe fn set_uniform_array<T: UniformValue>(&self, name: &str, values: &[T]) {
+        if values.is_empty() {
+            return;
+        }
+        if let Some(uniform) = self.checked_uniform::<T>(name) {
+            let size = uniform.size.max(0) as usize;
+            if values.len() > size {
+                self.report(format!(
+                    "uniform {} has {} elements, not {}",
+                    name,
+                    size,
+                    values.len()
+                ));
+            }
+            T::upload(uniform.location, &values[..values.len().min(size)]);
+        }
+    }
+
+    // make the uniform block read the buffer at a binding point. the shared blocks are bound
+    // to every program, so a program which does not use the block is left as it is
+    pub unsafe fn bind_block(&self, name: &str, binding: GLuint) {
+        if let Some(&index) = self.blocks.get(name) {
+            gl::UniformBlockBinding(self.id, index, binding);
+        }
+    }
+
+    pub unsafe fn set_editable(&self, name: &str, value: &EditableValue) {
+        match *value {
+            EditableValue::Float(value) => self.set_uniform(name, value),
+            EditableValue::Int(value) => self.set_uniform(name, value),
+            EditableValue::Bool(value) => self.set_uniform(name, value),
+            EditableValue::Color([r, g, b]) => self.set_uniform(name, Vector3::new(r, g, b)),
+        }
+    }
+
     #[allow(dead_code)]
-    pub unsafe fn set_bool(&self, name: &CStr, value: bool) {
-        gl::Uniform1i(gl::GetUniformLocation(self.id, name.as_ptr()), value as i32);
+    pub unsafe fn set_bool(&self, name: &str, value: bool) {
+        self.set_uniform(name, value);
     }
 
     #[allow(dead_code)]
-    pub unsafe fn set_int(&self, name: &CStr, value: i32) {
-        gl::Uniform1i(gl::GetUniformLocation(self.id, name.as_ptr()), value);
+    pub unsafe fn set_int(&self, name: &str, value: i32) {
+        self.set_uniform(name, value);
     }
 
     #[allow(dead_code)]
-    pub unsafe fn set_float(&self, name: &CStr, value: f32) {
-        gl::Uniform1f(gl::GetUniformLocation(self.id, name.as_ptr()), value);
+    pub unsafe fn set_float(&self, name: &str, value: f32) {
+        self.set_uniform(name, value);
     }
 
     #[allow(dead_code)]
-    pub unsafe fn set_vector3(&self, name: &CStr, value: &Vector3) {
-        gl::Uniform3fv(
-            gl::GetUniformLocation(self.id, name.as_ptr()),
-            1,
-            value.as_ptr(),
-        );
+    pub unsafe fn set_vector3(&self, name: &str, value: &Vector3) {
+        self.set_uniform(name, *value);
     }
 
     #[allow(dead_code)]
-    pub unsafe fn set_vec3(&self, name: &CStr, x: f32, y: f32, z: f32) {
-        gl::Uniform3f(gl::GetUniformLocation(self.id, name.as_ptr()), x, y, z);
+    pub unsafe fn set_vec3(&self, name: &str, x: f32, y: f32, z: f32) {
+        self.set_uniform(name, Vector3::new(x, y, z));
     }
 
     #[allow(dead_code)]
-    pub unsafe fn set_mat4(&self, name: &CStr, mat: &Matrix4) {
-        gl::UniformMatrix4fv(
-            gl::GetUniformLocation(self.id, name.as_ptr()),
-            1,
-            gl::FALSE,
-            mat.as_ptr(),
-        );
+    pub unsafe fn set_mat4(&self, name: &str, mat: &Matrix4) {
+        self.set_uniform(name, *mat);
     }
+}
 
-    unsafe fn check_compile_errors(&self, shader: u32, type_: &str) {
-        let mut success = gl::FALSE as GLint;
-        let mut info_log = Vec::with_capacity(1024);
-        info_log.set_len(1024 - 1);
+impl Drop for Shader {
+    fn drop(&mut self) {
+        if self.id != 0 {
+            unsafe {
+                gl::DeleteProgram(self.id);
+            }
+        }
+    }
+}
 
-        if type_ == "PROGRAM" {
-            //doc : http://docs.gl/gl3/glGetShader
-            // リンクが成功していれば、第３引数にgl::TRUEが入る
-            gl::GetProgramiv(shader, gl::LINK_STATUS, &mut success);
-            if success == gl::FALSE as GLint {
-                gl::GetProgramInfoLog(
-                    shader,
-                    1024,
-                    ptr::null_mut(),
-                    info_log.as_mut_ptr() as *mut GLchar,
-                );
-                let info_log_string = match String::from_utf8(info_log) {
-                    Ok(log) => log,
-                    Err(vec) => panic!("failed to convert to link log from buffer: {}", vec),
-                };
-                println!(
-                    "failed to link shader code: type={}, log={}",
-                    type_, info_log_string
-                );
+impl Default for Shader {
+    fn default() -> Shader {
+        Shader::new()
+    }
+}
+
+// defines of the shader variant for the attributes of a model
+pub fn model_defines(model: &Model) -> Vec<&'static str> {
+    let mut defines = Vec::new();
+    if !model.vertex_color.is_empty() {
+        defines.push("HAS_VERTEX_COLOR");
+    }
+    defines
+}
+
+// the variants of a program built from the same files, by their set of defines
+pub struct ShaderVariants {
+    vertex_path: String,
+    fragment_path: String,
+    variants: HashMap<Vec<String>, Shader>,
+}
+
+impl ShaderVariants {
+    pub fn new(vertex_path: &str, fragment_path: &str) -> ShaderVariants {
+        ShaderVariants {
+            vertex_path: vertex_path.to_string(),
+            fragment_path: fragment_path.to_string(),
+            variants: HashMap::new(),
+        }
+    }
+
+    // the same defines in any order are the same variant
+    fn key(defines: &[&str]) -> Vec<String> {
+        let mut key: Vec<String> = defines.iter().map(|define| define.to_string()).collect();
+        key.sort();
+        key.dedup();
+        key
+    }
+
+    // build the variant unless it has been built already. a variant which fails to build is
+    // kept without a program as well, so that it is not built again until reload()
+    pub fn build(&mut self, defines: &[&str]) -> Result<(), ShaderError> {
+        let key = ShaderVariants::key(defines);
+        if self.variants.contains_key(&key) {
+            return Ok(());
+        }
+        let mut shader = Shader::new();
+        let result = shader.setup_variant(&self.vertex_path, &self.fragment_path, &key);
+        self.variants.insert(key, shader);
+        result
+    }
+
+    // the file name of the fragment shader without its extension
+    pub fn name(&self) -> String {
+        Path::new(&self.fragment_path)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    }
+
+    pub fn get(&self, defines: &[&str]) -> Option<&Shader> {
+        self.variants.get(&ShaderVariants::key(defines))
+    }
+
+    // build all the variants again. each one keeps its program when it fails, and the
+    // errors of the first one which fails are returned
+    pub fn reload(&mut self) -> Result<(), ShaderError> {
+        let mut result = Ok(());
+        for shader in self.variants.values_mut() {
+            if let Err(e) = shader.reload() {
+                if result.is_ok() {
+                    result = Err(e);
+                }
             }
-        } else {
-            //doc : http://docs.gl/gl3/glGetShader
-            // コンパイルが成功していれば、第３引数にgl::TRUEが入る
-            gl::GetShaderiv(shader, gl::COMPILE_STATUS, &mut success);
-            if success == gl::FALSE as GLint {
-                gl::GetShaderInfoLog(
-                    shader,
-                    1024,
-                    ptr::null_mut(),
-                    info_log.as_mut_ptr() as *mut GLchar,
-                );
-                let info_log_string = match String::from_utf8(info_log) {
-                    Ok(log) => log,
-                    Err(vec) => panic!("failed to convert to comlilation log from buffer: {}", vec),
-                };
-                println!(
-                    "failed to compile shader code: type={}, log={}",
-                    type_, info_log_string
-                );
+        }
+        result
+    }
+
+    // the files of all the variants
+    pub fn files(&self) -> Vec<PathBuf> {
+        let mut files = vec![
+            PathBuf::from(&self.vertex_path),
+            PathBuf::from(&self.fragment_path),
+        ];
+        for shader in self.variants.values() {
+            for file in shader.files() {
+                if !files.contains(&file) {
+                    files.push(file);
+                }
             }
         }
+        files
+    }
+}
+
+// the messages of a log, or `fallback` when the driver gave none
+fn log_error(file: &str, log: &str, fallback: &str) -> ShaderError {
+    let messages = parse_info_log(file, log);
+    if messages.is_empty() {
+        return ShaderError::new(file, fallback);
+    }
+    ShaderError { messages }
+}
+
+unsafe fn compile(type_: GLenum, source: &Source) -> Result<GLuint, ShaderError> {
+    let file = source.files[0].display().to_string();
+    let code = CString::new(source.code.as_str())
+        .map_err(|_| ShaderError::new(&file, "source contains a NUL byte"))?;
+    let shader = gl::CreateShader(type_);
+    gl::ShaderSource(shader, 1, &code.as_ptr(), ptr::null());
+    gl::CompileShader(shader);
+
+    //doc : http://docs.gl/gl3/glGetShader
+    // コンパイルが成功していれば、第３引数にgl::TRUEが入る
+    let mut success = gl::FALSE as GLint;
+    gl::GetShaderiv(shader, gl::COMPILE_STATUS, &mut success);
+    if success == gl::FALSE as GLint {
+        let log = info_log(shader, false);
+        gl::DeleteShader(shader);
+        let mut error = log_error(&file, &log, "failed to compile");
+        source.map_messages(&mut error.messages);
+        return Err(error);
+    }
+    Ok(shader)
+}
+
+unsafe fn info_log(object: GLuint, is_program: bool) -> String {
+    let mut len = 0;
+    if is_program {
+        gl::GetProgramiv(object, gl::INFO_LOG_LENGTH, &mut len);
+    } else {
+        gl::GetShaderiv(object, gl::INFO_LOG_LENGTH, &mut len);
+    }
+    let mut log = vec![0u8; len.max(1) as usize];
+    let mut written = 0;
+    let buffer = log.as_mut_ptr() as *mut GLchar;
+    if is_program {
+        gl::GetProgramInfoLog(object, log.len() as GLsizei, &mut written, buffer);
+    } else {
+        gl::GetShaderInfoLog(object, log.len() as GLsizei, &mut written, buffer);
+    }
+    log.truncate(written.max(0) as usize);
+    String::from_utf8_lossy(&log).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn located(line: u32, message: &str) -> LogMessage {
+        LogMessage {
+            file: "a.fs".to_string(),
+            line: Some(line),
+            message: message.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_ok_parse_info_log() {
+        let log = "0:12(5): error: `foo' undeclared\n\
+                   0(3) : error C0000: syntax error\n\
+                   ERROR: 0:7: 'bar' : undeclared identifier\n\
+                   \n\
+                   link failed\0";
+        let messages = parse_info_log("a.fs", log);
+        assert_eq!(
+            vec![
+                located(12, "error: `foo' undeclared"),
+                located(3, "error C0000: syntax error"),
+                located(7, "ERROR: 'bar' : undeclared identifier"),
+                LogMessage {
+                    file: "a.fs".to_string(),
+                    line: None,
+                    message: "link failed".to_string(),
+                },
+            ],
+            messages
+        );
+        assert_eq!("a.fs:12: error: `foo' undeclared", messages[0].to_string());
+        assert_eq!("a.fs: link failed", messages[3].to_string());
     }
 }
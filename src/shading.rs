@@ -0,0 +1,236 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::model::Model;
+
+// directory of the images the matcap mode can use
+pub const MATCAP_DIR: &str = "rsc/matcap";
+// floats per corner of the corner buffer: position, normal, color, texcoord and group
+pub const CORNER_FLOATS: usize = 12;
+
+// how the faces of the model are colored. each mode is a program of its own
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ShadingMode {
+    FlatLit,
+    #[default]
+    SmoothLit,
+    Normals,
+    WorldPosition,
+    ViewPosition,
+    Depth,
+    UvChecker,
+    Texcoord,
+    GroupColor,
+    Matcap,
+}
+
+impl ShadingMode {
+    pub const ALL: [ShadingMode; 10] = [
+        ShadingMode::FlatLit,
+        ShadingMode::SmoothLit,
+        ShadingMode::Normals,
+        ShadingMode::WorldPosition,
+        ShadingMode::ViewPosition,
+        ShadingMode::Depth,
+        ShadingMode::UvChecker,
+        ShadingMode::Texcoord,
+        ShadingMode::GroupColor,
+        ShadingMode::Matcap,
+    ];
+
+    // position in ALL
+    pub fn index(self) -> usize {
+        ShadingMode::ALL
+            .iter()
+            .position(|&mode| mode == self)
+            .unwrap()
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ShadingMode::FlatLit => "Flat Lit",
+            ShadingMode::SmoothLit => "Smooth Lit",
+            ShadingMode::Normals => "Normals",
+            ShadingMode::WorldPosition => "World Position",
+            ShadingMode::ViewPosition => "View Position",
+            ShadingMode::Depth => "Linear Depth",
+            ShadingMode::UvChecker => "UV Checker",
+            ShadingMode::Texcoord => "Texcoords",
+            ShadingMode::GroupColor => "Group Colors",
+            ShadingMode::Matcap => "Matcap",
+        }
+    }
+
+    // vertex and fragment shader of the program
+    pub fn shader_files(self) -> (&'static str, &'static str) {
+        let fragment = match self {
+            ShadingMode::FlatLit => "rsc/shader/flat_shader.fs",
+            ShadingMode::SmoothLit => "rsc/shader/mono_shader.fs",
+            ShadingMode::Normals => "rsc/shader/normal_shader.fs",
+            ShadingMode::WorldPosition => "rsc/shader/world_position_shader.fs",
+            ShadingMode::ViewPosition => "rsc/shader/view_position_shader.fs",
+            ShadingMode::Depth => "rsc/shader/depth_shader.fs",
+            ShadingMode::UvChecker => "rsc/shader/uv_checker_shader.fs",
+            ShadingMode::Texcoord => "rsc/shader/texcoord_shader.fs",
+            ShadingMode::GroupColor => "rsc/shader/group_color_shader.fs",
+            ShadingMode::Matcap => "rsc/shader/matcap_shader.fs",
+        };
+        let vertex = match self {
+            ShadingMode::FlatLit | ShadingMode::SmoothLit => "rsc/shader/mono_shader.vs",
+            _ => "rsc/shader/debug_shader.vs",
+        };
+        (vertex, fragment)
+    }
+
+    // texcoords and groups belong to the corners of the faces rather than to the vertices,
+    // so these modes draw the corner buffer
+    pub fn needs_corners(self) -> bool {
+        matches!(
+            self,
+            ShadingMode::UvChecker | ShadingMode::Texcoord | ShadingMode::GroupColor
+        )
+    }
+}
+
+// the triangles with a vertex per corner, CORNER_FLOATS floats each. the texcoord is 0 when
+// not every corner has one, and the group is its index in model.groups
+pub fn corner_buffer(model: &Model) -> Vec<f32> {
+    let indices = &model.indices;
+    let has_texcoord = indices.texture_indices.len() == indices.vertex_indices.len();
+    let has_color = model.vertex_color.len() == model.vertex.len();
+    let mut groups = vec![0.0; indices.vertex_indices.len()];
+    for (i, group) in model.groups.iter().enumerate() {
+        let end = (group.index_start + group.index_count).min(groups.len());
+        for group_index in groups[group.index_start.min(end)..end].iter_mut() {
+            *group_index = i as f32;
+        }
+    }
+    let mut buffer = Vec::with_capacity(indices.vertex_indices.len() * CORNER_FLOATS);
+    for (i, &v) in indices.vertex_indices.iter().enumerate() {
+        let start = i - i % 3;
+        let triangle = &indices.vertex_indices[start..(start + 3).min(groups.len())];
+        if !model.triangle_in_range(triangle) {
+            buffer.extend_from_slice(&[0.0; CORNER_FLOATS]);
+            continue;
+        }
+        let v = v as usize * 3;
+        buffer.extend_from_slice(&model.vertex[v..v + 3]);
+        match model.vertex_normal.get(v..v + 3) {
+            Some(normal) => buffer.extend_from_slice(normal),
+            None => buffer.extend_from_slice(&[0.0, 0.0, 0.0]),
+        }
+        if has_color {
+            buffer.extend_from_slice(&model.vertex_color[v..v + 3]);
+        } else {
+            buffer.extend_from_slice(&[1.0, 1.0, 1.0]);
+        }
+        let t = indices.texture_indices.get(i).map(|&t| t as usize * 2);
+        match t.and_then(|t| model.vertex_tex.get(t..t + 2)) {
+            Some(texcoord) if has_texcoord => buffer.extend_from_slice(texcoord),
+            _ => buffer.extend_from_slice(&[0.0, 0.0]),
+        }
+        buffer.push(groups[i]);
+    }
+    buffer
+}
+
+// png and jpeg images in a directory, sorted by name
+pub fn matcap_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| {
+                    let extension = path
+                        .extension()
+                        .and_then(|extension| extension.to_str())
+                        .map(|extension| extension.to_lowercase());
+                    matches!(extension.as_deref(), Some("png" | "jpg" | "jpeg"))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    files.sort();
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::glsl::{preprocess, vertex_inputs};
+    use crate::model::Group;
+
+    #[test]
+    fn test_ok_corner_buffer() {
+        let mut model = Model::new();
+        model.vertex = vec![0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 0.0, 0.0, 1.0, 0.0];
+        model.vertex_normal = [0.0, 0.0, 1.0].repeat(4);
+        model.vertex_tex = vec![0.0, 0.0, 1.0, 0.0, 1.0, 1.0];
+        model.indices.vertex_indices = vec![0, 1, 2, 0, 2, 3];
+        model.indices.texture_indices = vec![0, 1, 2, 0, 2, 1];
+        for (i, name) in ["a", "b"].iter().enumerate() {
+            model.groups.push(Group {
+                name: name.to_string(),
+                object: String::new(),
+                material: None,
+                smoothing_group: 0,
+                face_start: i,
+                face_count: 1,
+                index_start: i * 3,
+                index_count: 3,
+            });
+        }
+        let buffer = corner_buffer(&model);
+        assert_eq!(6 * CORNER_FLOATS, buffer.len());
+        let corner = |i: usize| &buffer[i * CORNER_FLOATS..(i + 1) * CORNER_FLOATS];
+        assert_eq!(
+            &[1.0, 1.0, 0.0, 0.0, 0.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 0.0],
+            corner(2)
+        );
+        assert_eq!(
+            &[0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 1.0, 1.0, 1.0, 1.0, 0.0, 1.0],
+            corner(5)
+        );
+
+        // texcoords of only some corners are left out
+        model.indices.texture_indices.truncate(3);
+        let buffer = corner_buffer(&model);
+        assert_eq!(
+            &[0.0, 0.0],
+            &buffer[2 * CORNER_FLOATS + 9..2 * CORNER_FLOATS + 11]
+        );
+    }
+
+    #[test]
+    fn test_ok_shader_files() {
+        let mut read = |path: &Path| fs::read_to_string(path);
+        for mode in ShadingMode::ALL.iter() {
+            let (vertex, fragment) = mode.shader_files();
+            let vertex = preprocess(Path::new(vertex), &[], &mut read).unwrap();
+            preprocess(Path::new(fragment), &[], &mut read).unwrap();
+            // the inputs are bound in the order of the corner buffer
+            let inputs = vertex_inputs(&vertex.code);
+            assert_eq!(vec!["iPosition", "iNormal", "iColor"], inputs[..3].to_vec());
+        }
+        let (vertex, _) = ShadingMode::Texcoord.shader_files();
+        let code = fs::read_to_string(vertex).unwrap();
+        assert_eq!(
+            vec!["iPosition", "iNormal", "iColor", "iTexcoord", "iGroup"],
+            vertex_inputs(&code)
+        );
+    }
+
+    #[test]
+    fn test_ok_matcap_files() {
+        let files = matcap_files(Path::new(MATCAP_DIR));
+        assert_eq!(
+            vec![
+                PathBuf::from("rsc/matcap/clay.png"),
+                PathBuf::from("rsc/matcap/jade.png"),
+                PathBuf::from("rsc/matcap/red_wax.png"),
+            ],
+            files
+        );
+        assert!(matcap_files(Path::new("rsc/missing")).is_empty());
+    }
+}
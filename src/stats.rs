@@ -0,0 +1,435 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+
+use serde_json::json;
+
+use crate::cli::ReportOptions;
+use crate::loader::LoaderRegistry;
+use crate::model::Model;
+
+// area below which a polygon is reported as degenerate
+const DEGENERATE_AREA: f64 = 1.0e-12;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Check {
+    UnreferencedVertices,
+    DegenerateFaces,
+    OutOfRangeIndices,
+    MissingNormals,
+}
+
+impl Check {
+    pub const ALL: [Check; 4] = [
+        Check::UnreferencedVertices,
+        Check::DegenerateFaces,
+        Check::OutOfRangeIndices,
+        Check::MissingNormals,
+    ];
+
+    // checks that fail `validate` unless --fail-on is given
+    pub const DEFAULT: [Check; 2] = [Check::DegenerateFaces, Check::OutOfRangeIndices];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Check::UnreferencedVertices => "unreferenced-vertices",
+            Check::DegenerateFaces => "degenerate-faces",
+            Check::OutOfRangeIndices => "out-of-range-indices",
+            Check::MissingNormals => "missing-normals",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Check> {
+        Check::ALL
+            .iter()
+            .copied()
+            .find(|check| check.name() == name)
+    }
+}
+
+pub struct ModelStats {
+    pub vertex_count: usize,
+    pub texcoord_count: usize,
+    pub normal_count: usize,
+    pub face_count: usize,
+    pub triangle_count: usize,
+    // polygon size -> number of polygons
+    pub polygon_histogram: BTreeMap<u32, usize>,
+    pub bounding_box: Option<([f32; 3], [f32; 3])>,
+    pub surface_area: f64,
+    // absolute value of the signed volume, only meaningful for closed meshes
+    pub volume: f64,
+    pub group_count: usize,
+    pub material_count: usize,
+    pub material_lib_count: usize,
+    pub unreferenced_vertices: usize,
+    pub degenerate_faces: usize,
+    pub out_of_range_indices: usize,
+    pub faces_without_normals: usize,
+}
+
+fn cross(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+impl ModelStats {
+    pub fn new(model: &Model) -> ModelStats {
+        let vertex_count = model.vertex.len() / 3;
+        let texcoord_count = model.vertex_tex.len() / 2;
+        let normal_count = model.vertex_normal_tmp.len() / 3;
+        let position = |i: u32| {
+            let i = i as usize * 3;
+            [
+                model.vertex[i] as f64,
+                model.vertex[i + 1] as f64,
+                model.vertex[i + 2] as f64,
+            ]
+        };
+
+        let mut stats = ModelStats {
+            vertex_count,
+            texcoord_count,
+            normal_count,
+            face_count: model.polygon_sizes.len(),
+            triangle_count: model.indices.vertex_indices.len() / 3,
+            polygon_histogram: BTreeMap::new(),
+            bounding_box: model.bounding_box(),
+            surface_area: 0.0,
+            volume: 0.0,
+            group_count: 0,
+            material_count: 0,
+            material_lib_count: model.material_libs.len(),
+            unreferenced_vertices: 0,
+            degenerate_faces: 0,
+            out_of_range_indices: 0,
+            faces_without_normals: 0,
+        };
+
+        let mut group_names: Vec<&str> = model.groups.iter().map(|g| g.name.as_str()).collect();
+        group_names.sort_unstable();
+        group_names.dedup();
+        stats.group_count = group_names.len();
+        let mut materials: Vec<&str> = model
+            .groups
+            .iter()
+            .filter_map(|g| g.material.as_deref())
+            .collect();
+        materials.sort_unstable();
+        materials.dedup();
+        stats.material_count = materials.len();
+
+        let mut referenced = vec![false; vertex_count];
+        let mut signed_volume = 0.0;
+        let mut start = 0;
+        for &size in model.polygon_sizes.iter() {
+            let polygon = &model.corners[start..start + size as usize];
+            start += size as usize;
+            *stats.polygon_histogram.entry(size).or_insert(0) += 1;
+
+            let mut in_range = true;
+            for corner in polygon.iter() {
+                if (corner.vertex as usize) < vertex_count {
+                    referenced[corner.vertex as usize] = true;
+                } else {
+                    stats.out_of_range_indices += 1;
+                    in_range = false;
+                }
+                if matches!(corner.texture, Some(t) if t as usize >= texcoord_count) {
+                    stats.out_of_range_indices += 1;
+                }
+                if matches!(corner.normal, Some(n) if n as usize >= normal_count) {
+                    stats.out_of_range_indices += 1;
+                }
+            }
+            if polygon.iter().any(|corner| corner.normal.is_none()) {
+                stats.faces_without_normals += 1;
+            }
+            if size < 3 {
+                stats.degenerate_faces += 1;
+                continue;
+            }
+            if !in_range {
+                continue;
+            }
+
+            // Newell's method gives the area of planar polygons, convex or not
+            let mut normal = [0.0; 3];
+            for i in 0..polygon.len() {
+                let a = position(polygon[i].vertex);
+                let b = position(polygon[(i + 1) % polygon.len()].vertex);
+                let c = cross(a, b);
+                normal = [normal[0] + c[0], normal[1] + c[1], normal[2] + c[2]];
+            }
+            let area = 0.5 * dot(normal, normal).sqrt();
+            let mut repeated = false;
+            for i in 0..polygon.len() {
+                if polygon[i + 1..]
+                    .iter()
+                    .any(|c| c.vertex == polygon[i].vertex)
+                {
+                    repeated = true;
+                }
+            }
+            if repeated || area <= DEGENERATE_AREA {
+                stats.degenerate_faces += 1;
+            }
+            stats.surface_area += area;
+
+            let p0 = position(polygon[0].vertex);
+            for i in 1..polygon.len() - 1 {
+                let (p1, p2) = (position(polygon[i].vertex), position(polygon[i + 1].vertex));
+                signed_volume += dot(p0, cross(p1, p2)) / 6.0;
+            }
+        }
+        stats.volume = signed_volume.abs();
+        stats.unreferenced_vertices = referenced.iter().filter(|r| !**r).count();
+        stats
+    }
+
+    // number of offending elements of the check
+    pub fn count(&self, check: Check) -> usize {
+        match check {
+            Check::UnreferencedVertices => self.unreferenced_vertices,
+            Check::DegenerateFaces => self.degenerate_faces,
+            Check::OutOfRangeIndices => self.out_of_range_indices,
+            Check::MissingNormals => self.faces_without_normals,
+        }
+    }
+
+    // checks of the list that found problems
+    pub fn validate(&self, checks: &[Check]) -> Vec<Check> {
+        checks
+            .iter()
+            .copied()
+            .filter(|check| self.count(*check) > 0)
+            .collect()
+    }
+
+    pub fn report_lines(&self) -> Vec<String> {
+        let mut lines = vec![
+            format!("Vertices: {}", self.vertex_count),
+            format!("Texture coordinates: {}", self.texcoord_count),
+            format!("Normals: {}", self.normal_count),
+            format!("Faces: {}", self.face_count),
+            format!("Triangles: {}", self.triangle_count),
+            "Polygon sizes:".to_string(),
+        ];
+        for (size, count) in self.polygon_histogram.iter() {
+            lines.push(format!("  {:>3}: {}", size, count));
+        }
+        match self.bounding_box {
+            Some((min, max)) => {
+                lines.push(format!(
+                    "Bounding box min: ({:.4}, {:.4}, {:.4})",
+                    min[0], min[1], min[2]
+                ));
+                lines.push(format!(
+                    "Bounding box max: ({:.4}, {:.4}, {:.4})",
+                    max[0], max[1], max[2]
+                ));
+            }
+            None => lines.push("Bounding box: none".to_string()),
+        }
+        lines.push(format!("Surface area: {:.4}", self.surface_area));
+        lines.push(format!("Volume: {:.4}", self.volume));
+        lines.push(format!("Groups: {}", self.group_count));
+        lines.push(format!("Materials: {}", self.material_count));
+        lines.push(format!("Material libraries: {}", self.material_lib_count));
+        lines.push(format!(
+            "Unreferenced vertices: {}",
+            self.unreferenced_vertices
+        ));
+        lines.push(format!("Degenerate faces: {}", self.degenerate_faces));
+        lines.push(format!(
+            "Out of range indices: {}",
+            self.out_of_range_indices
+        ));
+        lines.push(format!(
+            "Faces without normals: {}",
+            self.faces_without_normals
+        ));
+        lines
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        let histogram: serde_json::Map<String, serde_json::Value> = self
+            .polygon_histogram
+            .iter()
+            .map(|(size, count)| (size.to_string(), json!(count)))
+            .collect();
+        let bounding_box = match self.bounding_box {
+            Some((min, max)) => json!({ "min": min, "max": max }),
+            None => serde_json::Value::Null,
+        };
+        json!({
+            "vertices": self.vertex_count,
+            "texture_coordinates": self.texcoord_count,
+            "normals": self.normal_count,
+            "faces": self.face_count,
+            "triangles": self.triangle_count,
+            "polygon_sizes": histogram,
+            "bounding_box": bounding_box,
+            "surface_area": self.surface_area,
+            "volume": self.volume,
+            "groups": self.group_count,
+            "materials": self.material_count,
+            "material_libraries": self.material_lib_count,
+            "unreferenced_vertices": self.unreferenced_vertices,
+            "degenerate_faces": self.degenerate_faces,
+            "out_of_range_indices": self.out_of_range_indices,
+            "faces_without_normals": self.faces_without_normals,
+        })
+    }
+}
+
+// print the report of the info/validate subcommands; returns false if validation failed
+pub fn run(options: &ReportOptions) -> Result<bool, Box<dyn Error>> {
+    let model = LoaderRegistry::new().load(&options.path)?;
+    let stats = ModelStats::new(&model);
+    let failures = match &options.fail_on {
+        Some(checks) => stats.validate(checks),
+        None => Vec::new(),
+    };
+
+    if options.json {
+        let mut report = stats.to_json();
+        if let Some(checks) = &options.fail_on {
+            let checks: Vec<serde_json::Value> = checks
+                .iter()
+                .map(|check| {
+                    json!({
+                        "check": check.name(),
+                        "count": stats.count(*check),
+                        "passed": !failures.contains(check),
+                    })
+                })
+                .collect();
+            report["validation"] = json!({
+                "passed": failures.is_empty(),
+                "checks": checks,
+            });
+        }
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        println!("{}", options.path.display());
+        for line in stats.report_lines() {
+            println!("{}", line);
+        }
+        if let Some(checks) = &options.fail_on {
+            for check in checks.iter() {
+                let result = if failures.contains(check) {
+                    "FAILED"
+                } else {
+                    "ok"
+                };
+                println!("{}: {} ({})", check.name(), result, stats.count(*check));
+            }
+        }
+    }
+    Ok(failures.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::Corner;
+
+    fn corner(vertex: u32, normal: Option<u32>) -> Corner {
+        Corner {
+            vertex,
+            texture: None,
+            normal,
+        }
+    }
+
+    // unit cube from 8 vertices and 6 quads, plus one unused vertex
+    fn cube() -> Model {
+        let mut model = Model::new();
+        #[rustfmt::skip]
+        let vertex = vec![
+            0.0, 0.0, 0.0,  1.0, 0.0, 0.0,  1.0, 1.0, 0.0,  0.0, 1.0, 0.0,
+            0.0, 0.0, 1.0,  1.0, 0.0, 1.0,  1.0, 1.0, 1.0,  0.0, 1.0, 1.0,
+            5.0, 5.0, 5.0,
+        ];
+        model.vertex = vertex;
+        model.vertex_normal_tmp = vec![0.0, 0.0, 1.0];
+        let quads = [
+            [0, 3, 2, 1],
+            [4, 5, 6, 7],
+            [0, 1, 5, 4],
+            [2, 3, 7, 6],
+            [1, 2, 6, 5],
+            [0, 4, 7, 3],
+        ];
+        for quad in quads.iter() {
+            for &v in quad.iter() {
+                model.corners.push(corner(v, Some(0)));
+            }
+            model.polygon_sizes.push(4);
+            model
+                .indices
+                .vertex_indices
+                .extend_from_slice(&[quad[0], quad[1], quad[2], quad[2], quad[3], quad[0]]);
+        }
+        model
+    }
+
+    #[test]
+    fn test_ok_model_stats() {
+        let stats = ModelStats::new(&cube());
+        assert_eq!(9, stats.vertex_count);
+        assert_eq!(6, stats.face_count);
+        assert_eq!(12, stats.triangle_count);
+        assert_eq!(Some(&6), stats.polygon_histogram.get(&4));
+        assert!((stats.surface_area - 6.0).abs() < 1.0e-9);
+        assert!((stats.volume - 1.0).abs() < 1.0e-9);
+        assert_eq!(1, stats.unreferenced_vertices);
+        assert_eq!(0, stats.degenerate_faces);
+        assert_eq!(0, stats.out_of_range_indices);
+        assert_eq!(0, stats.faces_without_normals);
+        assert_eq!(
+            vec![Check::UnreferencedVertices],
+            stats.validate(&Check::ALL)
+        );
+    }
+
+    #[test]
+    fn test_ng_model_stats() {
+        let mut model = cube();
+        // a line, a face with a repeated vertex, and indices past the end
+        model
+            .corners
+            .extend_from_slice(&[corner(0, None), corner(1, None)]);
+        model
+            .corners
+            .extend_from_slice(&[corner(0, None), corner(1, None), corner(1, None)]);
+        model
+            .corners
+            .extend_from_slice(&[corner(0, Some(3)), corner(1, None), corner(20, None)]);
+        model.polygon_sizes.extend_from_slice(&[2, 3, 3]);
+
+        let stats = ModelStats::new(&model);
+        assert_eq!(2, stats.degenerate_faces);
+        assert_eq!(2, stats.out_of_range_indices);
+        assert_eq!(3, stats.faces_without_normals);
+        assert_eq!(
+            vec![Check::DegenerateFaces, Check::OutOfRangeIndices],
+            stats.validate(&Check::DEFAULT)
+        );
+    }
+
+    #[test]
+    fn test_ok_check_name() {
+        for check in Check::ALL.iter() {
+            assert_eq!(Some(*check), Check::from_name(check.name()));
+        }
+        assert_eq!(None, Check::from_name("unknown"));
+    }
+}
@@ -0,0 +1,311 @@
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::model::{Corner, GroupState, Model};
+
+const HEADER_LEN: usize = 80;
+const TRIANGLE_LEN: usize = 50;
+
+// unit normal and positions of a facet
+type Facet = ([f32; 3], [[f32; 3]; 3]);
+
+// binary files may also start with "solid", so the size is checked first
+pub fn is_binary_stl(data: &[u8]) -> bool {
+    if data.len() >= HEADER_LEN + 4 {
+        let count = u32::from_le_bytes(data[HEADER_LEN..HEADER_LEN + 4].try_into().unwrap());
+        if data.len() as u64 == (HEADER_LEN + 4) as u64 + count as u64 * TRIANGLE_LEN as u64 {
+            return true;
+        }
+    }
+    let start = data
+        .iter()
+        .position(|b| !b.is_ascii_whitespace())
+        .unwrap_or(data.len());
+    !data[start..].starts_with(b"solid")
+}
+
+// merges the corners of neighboring triangles, which STL stores separately
+struct Welder {
+    indices: HashMap<[u32; 3], u32>,
+}
+
+impl Welder {
+    fn new() -> Welder {
+        Welder {
+            indices: HashMap::new(),
+        }
+    }
+
+    fn add(&mut self, model: &mut Model, position: [f32; 3]) -> u32 {
+        // -0.0 and 0.0 are the same position
+        let key = [
+            (position[0] + 0.0).to_bits(),
+            (position[1] + 0.0).to_bits(),
+            (position[2] + 0.0).to_bits(),
+        ];
+        let next = (model.vertex.len() / 3) as u32;
+        *self.indices.entry(key).or_insert_with(|| {
+            model.vertex.extend_from_slice(&position);
+            next
+        })
+    }
+
+    fn add_triangle(&mut self, model: &mut Model, triangle: &[[f32; 3]; 3], state: &GroupState) {
+        let mut polygon = [Corner {
+            vertex: 0,
+            texture: None,
+            normal: None,
+        }; 3];
+        for (corner, position) in polygon.iter_mut().zip(triangle.iter()) {
+            corner.vertex = self.add(model, *position);
+        }
+        model.add_polygon(&polygon, state);
+    }
+}
+
+fn parse_binary(data: &[u8]) -> Result<Model, Box<dyn Error>> {
+    if data.len() < HEADER_LEN + 4 {
+        return Err("STL file is too short".into());
+    }
+    let count = u32::from_le_bytes(data[HEADER_LEN..HEADER_LEN + 4].try_into()?) as usize;
+    let body = &data[HEADER_LEN + 4..];
+    if body.len() < count * TRIANGLE_LEN {
+        return Err(format!("STL file is truncated: expected {} triangles", count).into());
+    }
+
+    let mut model = Model::new();
+    let mut welder = Welder::new();
+    let state = GroupState::new();
+    for record in body.chunks_exact(TRIANGLE_LEN).take(count) {
+        let value = |i: usize| f32::from_le_bytes(record[i * 4..i * 4 + 4].try_into().unwrap());
+        // the facet normal (values 0..3) is computed from the winding instead
+        let triangle = [
+            [value(3), value(4), value(5)],
+            [value(6), value(7), value(8)],
+            [value(9), value(10), value(11)],
+        ];
+        welder.add_triangle(&mut model, &triangle, &state);
+    }
+    Ok(model)
+}
+
+fn parse_ascii(data: &[u8]) -> Result<Model, Box<dyn Error>> {
+    let text = std::str::from_utf8(data)?;
+    let mut model = Model::new();
+    let mut welder = Welder::new();
+    let mut state = GroupState::new();
+    let mut triangle = Vec::with_capacity(3);
+
+    for (i, line) in text.lines().enumerate() {
+        let counter = i + 1;
+        let ss: Vec<&str> = line.split_whitespace().collect();
+        match ss.first().copied() {
+            Some("solid") if ss.len() > 1 => state.name = ss[1..].join(" "),
+            Some("outer") => triangle.clear(),
+            Some("vertex") => {
+                let invalid = || format!("STL vertex is invalid. Line number:{}", counter);
+                if ss.len() != 4 {
+                    return Err(invalid().into());
+                }
+                let mut position = [0.0; 3];
+                for (value, str_) in position.iter_mut().zip(ss[1..].iter()) {
+                    *value = str_.parse().map_err(|_| invalid())?;
+                }
+                triangle.push(position);
+            }
+            Some("endloop") => {
+                if triangle.len() != 3 {
+                    let error_text =
+                        format!("STL facet is not a triangle. Line number:{}", counter);
+                    return Err(error_text.into());
+                }
+                let positions = [triangle[0], triangle[1], triangle[2]];
+                welder.add_triangle(&mut model, &positions, &state);
+            }
+            _ => {}
+        }
+    }
+    Ok(model)
+}
+
+// read ASCII or binary STL, whichever the data is
+pub fn parse_stl(data: &[u8]) -> Result<Model, Box<dyn Error>> {
+    if is_binary_stl(data) {
+        parse_binary(data)
+    } else {
+        parse_ascii(data)
+    }
+}
+
+pub fn load_stl(path: &str) -> Result<Model, Box<dyn Error>> {
+    parse_stl(&fs::read(path)?)
+}
+
+// triangles of the model with their unit normals
+fn triangles(model: &Model) -> Result<Vec<Facet>, Box<dyn Error>> {
+    let vertex_num = model.vertex.len() / 3;
+    let mut triangles = Vec::with_capacity(model.indices.vertex_indices.len() / 3);
+    for triangle in model.indices.vertex_indices.chunks_exact(3) {
+        let mut positions = [[0.0; 3]; 3];
+        for (position, &index) in positions.iter_mut().zip(triangle.iter()) {
+            if index as usize >= vertex_num {
+                return Err("polygon index is out of range".into());
+            }
+            let i = index as usize * 3;
+            position.copy_from_slice(&model.vertex[i..i + 3]);
+        }
+        let [a, b, c] = positions;
+        let e1 = [b[0] - a[0], b[1] - a[1], b[2] - a[2]];
+        let e2 = [c[0] - a[0], c[1] - a[1], c[2] - a[2]];
+        let mut normal = [
+            e1[1] * e2[2] - e1[2] * e2[1],
+            e1[2] * e2[0] - e1[0] * e2[2],
+            e1[0] * e2[1] - e1[1] * e2[0],
+        ];
+        let len = (normal[0] * normal[0] + normal[1] * normal[1] + normal[2] * normal[2]).sqrt();
+        if len > 0.0 {
+            normal.iter_mut().for_each(|x| *x /= len);
+        }
+        triangles.push((normal, positions));
+    }
+    Ok(triangles)
+}
+
+pub fn write_stl_ascii<W: Write>(
+    writer: &mut W,
+    model: &Model,
+    name: &str,
+) -> Result<(), Box<dyn Error>> {
+    writeln!(writer, "solid {}", name)?;
+    for (n, positions) in triangles(model)? {
+        writeln!(writer, "  facet normal {:e} {:e} {:e}", n[0], n[1], n[2])?;
+        writeln!(writer, "    outer loop")?;
+        for p in positions.iter() {
+            writeln!(writer, "      vertex {:e} {:e} {:e}", p[0], p[1], p[2])?;
+        }
+        writeln!(writer, "    endloop")?;
+        writeln!(writer, "  endfacet")?;
+    }
+    writeln!(writer, "endsolid {}", name)?;
+    Ok(())
+}
+
+pub fn write_stl_binary<W: Write>(writer: &mut W, model: &Model) -> Result<(), Box<dyn Error>> {
+    let triangles = triangles(model)?;
+    let mut header = [0u8; HEADER_LEN];
+    let text = b"binary STL written by obj_viewer";
+    header[..text.len()].copy_from_slice(text);
+    writer.write_all(&header)?;
+    writer.write_all(&(triangles.len() as u32).to_le_bytes())?;
+    for (normal, positions) in triangles.iter() {
+        for value in normal.iter().chain(positions.iter().flatten()) {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+        // attribute byte count
+        writer.write_all(&0u16.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+pub fn save_stl(path: &Path, model: &Model, ascii: bool) -> Result<(), Box<dyn Error>> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    if ascii {
+        let name = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+        write_stl_ascii(&mut writer, model, &name)?;
+    } else {
+        write_stl_binary(&mut writer, model)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ASCII: &str = "\
+solid square
+  facet normal 0 0 1
+    outer loop
+      vertex 0 0 0
+      vertex 1 0 0
+      vertex 1 1 0
+    endloop
+  endfacet
+  facet normal 0 0 1
+    outer loop
+      vertex 1 1 0
+      vertex 0 1 0
+      vertex -0 0 0
+    endloop
+  endfacet
+endsolid square
+";
+
+    #[test]
+    fn test_ok_parse_ascii() {
+        let model = parse_stl(ASCII.as_bytes()).unwrap();
+        assert_eq!(12, model.vertex.len());
+        assert_eq!(vec![0, 1, 2, 2, 3, 0], model.indices.vertex_indices);
+        assert_eq!(vec![3, 3], model.polygon_sizes);
+        assert_eq!("square", model.groups[0].name);
+    }
+
+    #[test]
+    fn test_ng_parse_ascii() {
+        let invalid = ASCII.replace("vertex 1 0 0", "vertex 1 0");
+        assert!(parse_stl(invalid.as_bytes()).is_err());
+        let invalid = ASCII.replace("vertex 1 0 0", "vertex 1 x 0");
+        assert!(parse_stl(invalid.as_bytes()).is_err());
+        let invalid = ASCII.replace("      vertex 1 0 0\n", "");
+        assert!(parse_stl(invalid.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_ok_binary_round_trip() {
+        let model = parse_stl(ASCII.as_bytes()).unwrap();
+        let mut buf = Vec::new();
+        write_stl_binary(&mut buf, &model).unwrap();
+        assert_eq!(HEADER_LEN + 4 + 2 * TRIANGLE_LEN, buf.len());
+        assert!(is_binary_stl(&buf));
+
+        // a header starting with "solid" must not be taken for ASCII
+        buf[..5].copy_from_slice(b"solid");
+        let loaded = parse_stl(&buf).unwrap();
+        assert_eq!(model.vertex, loaded.vertex);
+        assert_eq!(model.indices.vertex_indices, loaded.indices.vertex_indices);
+
+        // the normal is computed from the winding
+        let normal: Vec<f32> = (0..3)
+            .map(|i| f32::from_le_bytes(buf[84 + i * 4..88 + i * 4].try_into().unwrap()))
+            .collect();
+        assert_eq!(vec![0.0, 0.0, 1.0], normal);
+    }
+
+    #[test]
+    fn test_ng_parse_binary() {
+        let model = parse_stl(ASCII.as_bytes()).unwrap();
+        let mut buf = Vec::new();
+        write_stl_binary(&mut buf, &model).unwrap();
+        buf.truncate(buf.len() - 1);
+        assert!(parse_stl(&buf).is_err());
+    }
+
+    #[test]
+    fn test_ok_ascii_round_trip() {
+        let model = parse_stl(ASCII.as_bytes()).unwrap();
+        let mut buf = Vec::new();
+        write_stl_ascii(&mut buf, &model, "square").unwrap();
+        assert!(!is_binary_stl(&buf));
+        let loaded = parse_stl(&buf).unwrap();
+        assert_eq!(model.vertex, loaded.vertex);
+        assert_eq!(model.indices.vertex_indices, loaded.indices.vertex_indices);
+    }
+}
@@ -0,0 +1,116 @@
+use std::error::Error;
+use std::os::raw::c_void;
+use std::path::Path;
+
+use gl::types::{GLint, GLsizei, GLuint};
+
+// pixels of an image as rgba rows from the top, the way they are uploaded
+pub struct Pixels {
+    pub width: u32,
+    pub height: u32,
+    pub rgba: Vec<u8>,
+}
+
+impl Pixels {
+    pub fn open(path: &Path) -> Result<Pixels, Box<dyn Error>> {
+        let image = image::open(path)
+            .map_err(|e| format!("{}: {}", path.display(), e))?
+            .to_rgba();
+        let (width, height) = image.dimensions();
+        Ok(Pixels {
+            width,
+            height,
+            rgba: image.into_raw(),
+        })
+    }
+}
+
+// a 2D texture, deleted when dropped
+pub struct Texture {
+    id: GLuint,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Texture {
+    // needs a current OpenGL context
+    pub fn new(pixels: &Pixels) -> Texture {
+        let mut id = 0;
+        unsafe {
+            gl::GenTextures(1, &mut id);
+            gl::BindTexture(gl::TEXTURE_2D, id);
+            gl::TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                gl::RGBA8 as GLint,
+                pixels.width as GLsizei,
+                pixels.height as GLsizei,
+                0,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                pixels.rgba.as_ptr() as *const c_void,
+            );
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as GLint);
+            gl::TexParameteri(
+                gl::TEXTURE_2D,
+                gl::TEXTURE_WRAP_S,
+                gl::CLAMP_TO_EDGE as GLint,
+            );
+            gl::TexParameteri(
+                gl::TEXTURE_2D,
+                gl::TEXTURE_WRAP_T,
+                gl::CLAMP_TO_EDGE as GLint,
+            );
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+        Texture {
+            id,
+            width: pixels.width,
+            height: pixels.height,
+        }
+    }
+
+    pub fn open(path: &Path) -> Result<Texture, Box<dyn Error>> {
+        Ok(Texture::new(&Pixels::open(path)?))
+    }
+
+    // bind to a texture unit, which is the value of the sampler uniform reading it
+    pub fn bind(&self, unit: u32) {
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0 + unit);
+            gl::BindTexture(gl::TEXTURE_2D, self.id);
+            gl::ActiveTexture(gl::TEXTURE0);
+        }
+    }
+}
+
+impl Drop for Texture {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteTextures(1, &self.id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ok_pixels_open() {
+        let pixels = Pixels::open(Path::new("rsc/matcap/clay.png")).unwrap();
+        assert_eq!((128, 128), (pixels.width, pixels.height));
+        assert_eq!(128 * 128 * 4, pixels.rgba.len());
+        // the corners are outside of the sphere
+        assert_eq!(255, pixels.rgba[3]);
+    }
+
+    #[test]
+    fn test_ng_pixels_open() {
+        let error = Pixels::open(Path::new("rsc/matcap/missing.png"))
+            .err()
+            .unwrap();
+        assert!(error.to_string().starts_with("rsc/matcap/missing.png: "));
+    }
+}
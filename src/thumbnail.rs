@@ -0,0 +1,254 @@
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use cgmath::prelude::SquareMatrix;
+use indicatif::{ProgressBar, ProgressStyle};
+
+use crate::antialias::{self, Fxaa, SceneTarget};
+use crate::camera::CameraState;
+use crate::cli::ThumbnailOptions;
+use crate::framebuffer::Framebuffer;
+use crate::loader::LoaderRegistry;
+use crate::mesh::Mesh;
+use crate::shader::{self, ShaderVariants};
+use crate::uniform::{self, CameraBlock, LightingBlock, UniformBuffer};
+
+type Matrix4 = cgmath::Matrix4<f32>;
+
+const FLOAT_NUM: usize = 3;
+
+pub struct BatchReport {
+    pub written: usize,
+    pub failed: Vec<(PathBuf, String)>,
+}
+
+// collect every file below dir which a loader of the registry reads, sorted by path
+pub fn find_model_files(
+    registry: &LoaderRegistry,
+    dir: &Path,
+) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut files = Vec::new();
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                dirs.push(path);
+            } else if registry.find_by_extension(&path).is_some() {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+// <name>.png next to the model, or at the same relative position below output_dir
+pub fn thumbnail_path(model_path: &Path, root: &Path, output_dir: Option<&Path>) -> PathBuf {
+    let png_path = model_path.with_extension("png");
+    match output_dir {
+        Some(output_dir) => match png_path.strip_prefix(root) {
+            Ok(relative) => output_dir.join(relative),
+            Err(_) => output_dir.join(png_path.file_name().unwrap_or_default()),
+        },
+        None => png_path,
+    }
+}
+
+fn render_thumbnail(
+    registry: &LoaderRegistry,
+    path: &Path,
+    shaders: &mut ShaderVariants,
+    target: &SceneTarget,
+    // the pass and the framebuffer it draws into when FXAA is enabled
+    fxaa: Option<&(Fxaa, Framebuffer)>,
+    options: &ThumbnailOptions,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut model = registry.load(path)?;
+    let (min, max) = model.bounding_box().ok_or("model has no vertices")?;
+    let index_count = model.indices.vertex_indices.len();
+    if index_count == 0 {
+        return Err("model has no faces".into());
+    }
+    let vertex_num = model.vertex.len() / FLOAT_NUM;
+    if model
+        .indices
+        .vertex_indices
+        .iter()
+        .any(|&i| i as usize >= vertex_num)
+    {
+        return Err("polygon index is out of range".into());
+    }
+    let defines = shader::model_defines(&model);
+    shaders.build(&defines)?;
+    let shader = match shaders.get(&defines) {
+        Some(shader) if shader.id != 0 => shader,
+        _ => return Err("shader failed to build".into()),
+    };
+    model.create_vertex_normal();
+    let vertex_buf = model.create_concat_vertex();
+
+    let mesh = Mesh::from_model(&model, &vertex_buf);
+
+    let mut cam_state = CameraState::new((options.size, options.size));
+    cam_state.frame_bounds(min, max, options.yaw, options.pitch);
+
+    let camera = CameraBlock::new(
+        &cam_state.get_view(),
+        &cam_state.get_perspective(),
+        cam_state.position,
+    );
+    let camera_buffer = UniformBuffer::new(uniform::CAMERA_BINDING, &camera);
+    let lighting_buffer = UniformBuffer::new(uniform::LIGHTING_BINDING, &LightingBlock::new());
+
+    target.bind();
+    unsafe {
+        gl::Enable(gl::DEPTH_TEST);
+        gl::Enable(gl::MULTISAMPLE);
+        gl::Disable(gl::CULL_FACE);
+        gl::PolygonMode(gl::FRONT_AND_BACK, gl::FILL);
+        gl::ClearColor(0.0, 0.0, 0.0, 0.0);
+        gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+
+        shader.use_program();
+        shader.bind_block("Camera", camera_buffer.binding());
+        shader.bind_block("Lighting", lighting_buffer.binding());
+        shader.set_uniform("uModel", Matrix4::identity());
+    }
+    mesh.draw(gl::TRIANGLES);
+    let scene = target.resolve();
+    let pixels = match fxaa {
+        Some((fxaa, output)) => {
+            output.bind();
+            unsafe {
+                fxaa.apply(scene, true);
+            }
+            output.read_pixels()
+        }
+        None => scene.read_pixels(),
+    };
+    scene.unbind();
+    Ok(pixels)
+}
+
+// render a thumbnail of every model file below options.root
+pub fn run(options: &ThumbnailOptions) -> Result<BatchReport, Box<dyn Error>> {
+    let registry = LoaderRegistry::new();
+    let files = find_model_files(&registry, &options.root)?;
+
+    // a hidden window is only needed to own the OpenGL context
+    let sdl_context = sdl2::init()?;
+    let video_subsystem = sdl_context.video()?;
+    {
+        let gl_attr = video_subsystem.gl_attr();
+        gl_attr.set_context_profile(sdl2::video::GLProfile::Core);
+        gl_attr.set_context_version(3, 1);
+    }
+    let window = video_subsystem
+        .window("thumbnail", options.size, options.size)
+        .opengl()
+        .hidden()
+        .build()?;
+    let _gl_context = window.gl_create_context()?;
+    gl::load_with(|s| video_subsystem.gl_get_proc_address(s) as _);
+
+    let mut shaders = ShaderVariants::new("rsc/shader/mono_shader.vs", "rsc/shader/mono_shader.fs");
+    shaders.build(&[])?;
+    let samples = antialias::supported_samples(options.samples, antialias::max_samples());
+    let target = SceneTarget::new(options.size, options.size, samples)?;
+    let fxaa = if options.fxaa {
+        let mut fxaa = Fxaa::new();
+        fxaa.shaders.build(&[])?;
+        Some((fxaa, Framebuffer::new(options.size, options.size)?))
+    } else {
+        None
+    };
+
+    let progress_bar = ProgressBar::new(files.len() as u64);
+    progress_bar.set_style(
+        ProgressStyle::default_bar()
+            .template("{bar:40} {pos}/{len} [{elapsed_precise}] {wide_msg}")
+            .progress_chars("##-"),
+    );
+
+    let mut report = BatchReport {
+        written: 0,
+        failed: Vec::new(),
+    };
+    for path in files.iter() {
+        progress_bar.set_message(path.display().to_string());
+        let output_path = thumbnail_path(path, &options.root, options.output_dir.as_deref());
+        let result = render_thumbnail(
+            &registry,
+            path,
+            &mut shaders,
+            &target,
+            fxaa.as_ref(),
+            options,
+        )
+        .and_then(|pixels| {
+            if let Some(parent) = output_path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            image::save_buffer(
+                &output_path,
+                &pixels,
+                options.size,
+                options.size,
+                image::RGBA(8),
+            )?;
+            Ok(())
+        });
+        match result {
+            Ok(()) => report.written += 1,
+            Err(e) => {
+                progress_bar.println(format!("failed: {}: {}", path.display(), e));
+                report.failed.push((path.clone(), e.to_string()));
+            }
+        }
+        progress_bar.inc(1);
+    }
+    progress_bar.finish_with_message(format!(
+        "{} thumbnails written, {} failed",
+        report.written,
+        report.failed.len()
+    ));
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ok_thumbnail_path() {
+        let root = Path::new("assets");
+        let model_path = Path::new("assets/cars/audi.obj");
+
+        let actual = thumbnail_path(model_path, root, None);
+        assert_eq!(PathBuf::from("assets/cars/audi.png"), actual);
+
+        let actual = thumbnail_path(model_path, root, Some(Path::new("thumbs")));
+        assert_eq!(PathBuf::from("thumbs/cars/audi.png"), actual);
+    }
+
+    #[test]
+    fn test_ok_find_model_files() {
+        let root = std::env::temp_dir().join("obj_viewer_test_find_model_files");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("sub")).unwrap();
+        for name in ["a.obj", "b.txt", "sub/c.OBJ", "sub/d.stl", "sub/d.png"].iter() {
+            fs::write(root.join(name), "").unwrap();
+        }
+
+        let actual = find_model_files(&LoaderRegistry::new(), &root).unwrap();
+        let expected = vec![
+            root.join("a.obj"),
+            root.join("sub/c.OBJ"),
+            root.join("sub/d.stl"),
+        ];
+        assert_eq!(expected, actual);
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
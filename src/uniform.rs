@@ -0,0 +1,425 @@
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::marker::PhantomData;
+use std::mem;
+use std::os::raw::c_void;
+
+use bytemuck::{Pod, Zeroable};
+use cgmath::{Array, Matrix};
+use gl::types::{GLchar, GLenum, GLint, GLsizei, GLsizeiptr, GLuint};
+
+use crate::glsl::UniformDeclaration;
+
+type Vector2 = cgmath::Vector2<f32>;
+type Vector3 = cgmath::Vector3<f32>;
+type Vector4 = cgmath::Vector4<f32>;
+type Matrix3 = cgmath::Matrix3<f32>;
+type Matrix4 = cgmath::Matrix4<f32>;
+
+// binding points of the uniform blocks which the shaders share
+pub const CAMERA_BINDING: GLuint = 0;
+pub const LIGHTING_BINDING: GLuint = 1;
+
+// an active uniform or vertex input of a linked program
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ActiveVariable {
+    pub location: GLint,
+    pub type_: GLenum,
+    // number of elements of an array, 1 otherwise
+    pub size: GLint,
+}
+
+// a value which can be uploaded to uniforms of the GLSL type TYPE. upload needs a current
+// OpenGL context with the program in use
+#[allow(clippy::missing_safety_doc)]
+pub trait UniformValue: Sized {
+    const TYPE: GLenum;
+
+    // upload values.len() elements starting at location
+    unsafe fn upload(location: GLint, values: &[Self]);
+}
+
+impl UniformValue for f32 {
+    const TYPE: GLenum = gl::FLOAT;
+    unsafe fn upload(location: GLint, values: &[f32]) {
+        gl::Uniform1fv(location, values.len() as GLsizei, values.as_ptr());
+    }
+}
+
+impl UniformValue for i32 {
+    const TYPE: GLenum = gl::INT;
+    unsafe fn upload(location: GLint, values: &[i32]) {
+        gl::Uniform1iv(location, values.len() as GLsizei, values.as_ptr());
+    }
+}
+
+impl UniformValue for bool {
+    const TYPE: GLenum = gl::BOOL;
+    unsafe fn upload(location: GLint, values: &[bool]) {
+        let values: Vec<i32> = values.iter().map(|&value| value as i32).collect();
+        gl::Uniform1iv(location, values.len() as GLsizei, values.as_ptr());
+    }
+}
+
+impl UniformValue for Vector2 {
+    const TYPE: GLenum = gl::FLOAT_VEC2;
+    unsafe fn upload(location: GLint, values: &[Vector2]) {
+        gl::Uniform2fv(location, values.len() as GLsizei, values[0].as_ptr());
+    }
+}
+
+impl UniformValue for Vector3 {
+    const TYPE: GLenum = gl::FLOAT_VEC3;
+    unsafe fn upload(location: GLint, values: &[Vector3]) {
+        gl::Uniform3fv(location, values.len() as GLsizei, values[0].as_ptr());
+    }
+}
+
+impl UniformValue for Vector4 {
+    const TYPE: GLenum = gl::FLOAT_VEC4;
+    unsafe fn upload(location: GLint, values: &[Vector4]) {
+        gl::Uniform4fv(location, values.len() as GLsizei, values[0].as_ptr());
+    }
+}
+
+impl UniformValue for Matrix3 {
+    const TYPE: GLenum = gl::FLOAT_MAT3;
+    unsafe fn upload(location: GLint, values: &[Matrix3]) {
+        gl::UniformMatrix3fv(
+            location,
+            values.len() as GLsizei,
+            gl::FALSE,
+            values[0].as_ptr(),
+        );
+    }
+}
+
+impl UniformValue for Matrix4 {
+    const TYPE: GLenum = gl::FLOAT_MAT4;
+    unsafe fn upload(location: GLint, values: &[Matrix4]) {
+        gl::UniformMatrix4fv(
+            location,
+            values.len() as GLsizei,
+            gl::FALSE,
+            values[0].as_ptr(),
+        );
+    }
+}
+
+// a value of a uniform which the uniform editor changes
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EditableValue {
+    Float(f32),
+    Int(i32),
+    Bool(bool),
+    Color([f32; 3]),
+}
+
+impl EditableValue {
+    // the annotated default of a uniform, zero without one. None for types which are not edited
+    pub fn from_declaration(declaration: &UniformDeclaration) -> Option<EditableValue> {
+        let default = &declaration.annotation.default;
+        let at = |i: usize| default.get(i).copied().unwrap_or(0.0);
+        let value = match declaration.type_.as_str() {
+            "float" => EditableValue::Float(at(0)),
+            "int" => EditableValue::Int(at(0) as i32),
+            "bool" => EditableValue::Bool(at(0) != 0.0),
+            "vec3" => EditableValue::Color([at(0), at(1), at(2)]),
+            _ => return None,
+        };
+        Some(value)
+    }
+
+    pub fn type_(&self) -> GLenum {
+        match self {
+            EditableValue::Float(_) => gl::FLOAT,
+            EditableValue::Int(_) => gl::INT,
+            EditableValue::Bool(_) => gl::BOOL,
+            EditableValue::Color(_) => gl::FLOAT_VEC3,
+        }
+    }
+}
+
+pub fn type_name(type_: GLenum) -> String {
+    let name = match type_ {
+        gl::FLOAT => "float",
+        gl::FLOAT_VEC2 => "vec2",
+        gl::FLOAT_VEC3 => "vec3",
+        gl::FLOAT_VEC4 => "vec4",
+        gl::INT => "int",
+        gl::BOOL => "bool",
+        gl::FLOAT_MAT3 => "mat3",
+        gl::FLOAT_MAT4 => "mat4",
+        gl::SAMPLER_2D => "sampler2D",
+        gl::SAMPLER_CUBE => "samplerCube",
+        _ => return format!("type 0x{:x}", type_),
+    };
+    name.to_string()
+}
+
+fn is_sampler(type_: GLenum) -> bool {
+    matches!(
+        type_,
+        gl::SAMPLER_1D
+            | gl::SAMPLER_2D
+            | gl::SAMPLER_3D
+            | gl::SAMPLER_CUBE
+            | gl::SAMPLER_2D_SHADOW
+            | gl::SAMPLER_2D_ARRAY
+            | gl::SAMPLER_2D_MULTISAMPLE
+            | gl::SAMPLER_BUFFER
+    )
+}
+
+// true if a value of value_type can be uploaded to a uniform of uniform_type. booleans and
+// samplers are set with integers
+pub fn accepts(uniform_type: GLenum, value_type: GLenum) -> bool {
+    uniform_type == value_type
+        || (value_type == gl::INT && (uniform_type == gl::BOOL || is_sampler(uniform_type)))
+}
+
+// active arrays are named after their first element. "uWeights[0]" is kept as "uWeights"
+pub fn base_name(name: &str) -> &str {
+    name.strip_suffix("[0]").unwrap_or(name)
+}
+
+// name of an active variable, read by `get` into a buffer of max_len bytes
+unsafe fn active_name(
+    max_len: GLint,
+    mut get: impl FnMut(GLsizei, *mut GLsizei, *mut GLchar),
+) -> String {
+    let mut name = vec![0u8; max_len.max(1) as usize];
+    let mut len = 0;
+    get(
+        name.len() as GLsizei,
+        &mut len,
+        name.as_mut_ptr() as *mut GLchar,
+    );
+    name.truncate(len.max(0) as usize);
+    String::from_utf8_lossy(&name).into_owned()
+}
+
+// active uniforms of a linked program. members of uniform blocks have no location and are
+// set through the buffer of their block instead
+pub fn active_uniforms(program: GLuint) -> HashMap<String, ActiveVariable> {
+    unsafe {
+        let mut count = 0;
+        let mut max_len = 0;
+        gl::GetProgramiv(program, gl::ACTIVE_UNIFORMS, &mut count);
+        gl::GetProgramiv(program, gl::ACTIVE_UNIFORM_MAX_LENGTH, &mut max_len);
+        let mut uniforms = HashMap::new();
+        for i in 0..count.max(0) as GLuint {
+            let mut size = 0;
+            let mut type_ = 0;
+            let name = active_name(max_len, |buf_size, len, name| {
+                gl::GetActiveUniform(program, i, buf_size, len, &mut size, &mut type_, name)
+            });
+            let c_name = match CString::new(name.as_str()) {
+                Ok(c_name) => c_name,
+                Err(_) => continue,
+            };
+            let location = gl::GetUniformLocation(program, c_name.as_ptr());
+            if location < 0 {
+                continue;
+            }
+            let variable = ActiveVariable {
+                location,
+                type_,
+                size,
+            };
+            uniforms.insert(base_name(&name).to_string(), variable);
+        }
+        uniforms
+    }
+}
+
+pub fn active_attributes(program: GLuint) -> HashMap<String, ActiveVariable> {
+    unsafe {
+        let mut count = 0;
+        let mut max_len = 0;
+        gl::GetProgramiv(program, gl::ACTIVE_ATTRIBUTES, &mut count);
+        gl::GetProgramiv(program, gl::ACTIVE_ATTRIBUTE_MAX_LENGTH, &mut max_len);
+        let mut attributes = HashMap::new();
+        for i in 0..count.max(0) as GLuint {
+            let mut size = 0;
+            let mut type_ = 0;
+            let name = active_name(max_len, |buf_size, len, name| {
+                gl::GetActiveAttrib(program, i, buf_size, len, &mut size, &mut type_, name)
+            });
+            let c_name = match CString::new(name.as_str()) {
+                Ok(c_name) => c_name,
+                Err(_) => continue,
+            };
+            let location = gl::GetAttribLocation(program, c_name.as_ptr());
+            let variable = ActiveVariable {
+                location,
+                type_,
+                size,
+            };
+            attributes.insert(name, variable);
+        }
+        attributes
+    }
+}
+
+// indices of the active uniform blocks by name
+pub fn active_blocks(program: GLuint) -> HashMap<String, GLuint> {
+    unsafe {
+        let mut count = 0;
+        let mut max_len = 0;
+        gl::GetProgramiv(program, gl::ACTIVE_UNIFORM_BLOCKS, &mut count);
+        gl::GetProgramiv(
+            program,
+            gl::ACTIVE_UNIFORM_BLOCK_MAX_NAME_LENGTH,
+            &mut max_len,
+        );
+        let mut blocks = HashMap::new();
+        for i in 0..count.max(0) as GLuint {
+            let name = active_name(max_len, |buf_size, len, name| {
+                gl::GetActiveUniformBlockName(program, i, buf_size, len, name)
+            });
+            blocks.insert(name, i);
+        }
+        blocks
+    }
+}
+
+// per-frame camera data in the std140 layout of the Camera block
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct CameraBlock {
+    pub view: [[f32; 4]; 4],
+    pub projection: [[f32; 4]; 4],
+    // w is unused
+    pub view_position: [f32; 4],
+}
+
+// only f32 fields without padding, so any bytes are a valid block
+unsafe impl Zeroable for CameraBlock {}
+unsafe impl Pod for CameraBlock {}
+
+impl CameraBlock {
+    pub fn new(view: &Matrix4, projection: &Matrix4, position: (f32, f32, f32)) -> CameraBlock {
+        CameraBlock {
+            view: (*view).into(),
+            projection: (*projection).into(),
+            view_position: [position.0, position.1, position.2, 1.0],
+        }
+    }
+}
+
+// per-frame light data in the std140 layout of the Lighting block
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct LightingBlock {
+    // position of a light far away, w is unused
+    pub light_position: [f32; 4],
+    // rgb of the light, a is the share of ambient light
+    pub light_color: [f32; 4],
+}
+
+unsafe impl Zeroable for LightingBlock {}
+unsafe impl Pod for LightingBlock {}
+
+impl LightingBlock {
+    pub fn new() -> LightingBlock {
+        LightingBlock {
+            light_position: [5.0, 5.0, 5.0, 1.0],
+            light_color: [1.0, 1.0, 1.0, 0.3],
+        }
+    }
+}
+
+impl Default for LightingBlock {
+    fn default() -> LightingBlock {
+        LightingBlock::new()
+    }
+}
+
+// a uniform buffer object holding one T, bound to a binding point which uniform blocks
+// of that layout are bound to as well
+pub struct UniformBuffer<T: Pod> {
+    ubo: GLuint,
+    binding: GLuint,
+    data: PhantomData<T>,
+}
+
+impl<T: Pod> UniformBuffer<T> {
+    pub fn new(binding: GLuint, data: &T) -> UniformBuffer<T> {
+        let mut ubo = 0;
+        unsafe {
+            gl::GenBuffers(1, &mut ubo);
+            gl::BindBuffer(gl::UNIFORM_BUFFER, ubo);
+            gl::BufferData(
+                gl::UNIFORM_BUFFER,
+                mem::size_of::<T>() as GLsizeiptr,
+                bytemuck::bytes_of(data).as_ptr() as *const c_void,
+                gl::DYNAMIC_DRAW,
+            );
+            gl::BindBuffer(gl::UNIFORM_BUFFER, 0);
+            gl::BindBufferBase(gl::UNIFORM_BUFFER, binding, ubo);
+        }
+        UniformBuffer {
+            ubo,
+            binding,
+            data: PhantomData,
+        }
+    }
+
+    pub fn binding(&self) -> GLuint {
+        self.binding
+    }
+
+    pub fn update(&self, data: &T) {
+        unsafe {
+            gl::BindBuffer(gl::UNIFORM_BUFFER, self.ubo);
+            gl::BufferSubData(
+                gl::UNIFORM_BUFFER,
+                0,
+                mem::size_of::<T>() as GLsizeiptr,
+                bytemuck::bytes_of(data).as_ptr() as *const c_void,
+            );
+            gl::BindBuffer(gl::UNIFORM_BUFFER, 0);
+            // another buffer may have been bound to the binding point since
+            gl::BindBufferBase(gl::UNIFORM_BUFFER, self.binding, self.ubo);
+        }
+    }
+}
+
+impl<T: Pod> Drop for UniformBuffer<T> {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteBuffers(1, &self.ubo);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ok_accepts() {
+        assert!(accepts(gl::FLOAT_MAT4, Matrix4::TYPE));
+        assert!(accepts(gl::BOOL, bool::TYPE));
+        assert!(accepts(gl::BOOL, i32::TYPE));
+        assert!(accepts(gl::SAMPLER_2D, i32::TYPE));
+        assert!(!accepts(gl::FLOAT_VEC3, f32::TYPE));
+        assert!(!accepts(gl::SAMPLER_2D, f32::TYPE));
+        assert_eq!("vec3", type_name(gl::FLOAT_VEC3));
+        assert_eq!("type 0x1", type_name(1));
+    }
+
+    #[test]
+    fn test_ok_base_name() {
+        assert_eq!("uWeights", base_name("uWeights[0]"));
+        assert_eq!("uLight.direction", base_name("uLight.direction"));
+    }
+
+    #[test]
+    fn test_ok_std140_size() {
+        // mat4 is 4 vec4 columns and vec4 is 16 bytes in std140
+        assert_eq!(2 * 64 + 16, mem::size_of::<CameraBlock>());
+        assert_eq!(32, mem::size_of::<LightingBlock>());
+    }
+}
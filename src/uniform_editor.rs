@@ -0,0 +1,224 @@
+use std::collections::HashMap;
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use imgui::{im_str, ImString, Ui};
+use serde_json::{json, Map, Value};
+
+use crate::shader::Shader;
+use crate::uniform::EditableValue;
+
+// $XDG_CONFIG_HOME/obj_viewer, ~/.config/obj_viewer, or a directory in the temp directory
+pub fn default_config_dir() -> PathBuf {
+    let base = match (env::var_os("XDG_CONFIG_HOME"), env::var_os("HOME")) {
+        (Some(dir), _) => PathBuf::from(dir),
+        (None, Some(home)) => Path::new(&home).join(".config"),
+        (None, None) => env::temp_dir(),
+    };
+    base.join("obj_viewer")
+}
+
+fn to_json(value: &EditableValue) -> Value {
+    match *value {
+        EditableValue::Float(value) => json!(value),
+        EditableValue::Int(value) => json!(value),
+        EditableValue::Bool(value) => json!(value),
+        EditableValue::Color(value) => json!(value),
+    }
+}
+
+// a stored value of the same type as `like`, None if the type has changed since
+fn from_json(json: &Value, like: &EditableValue) -> Option<EditableValue> {
+    let value = match like {
+        EditableValue::Float(_) => EditableValue::Float(json.as_f64()? as f32),
+        EditableValue::Int(_) => EditableValue::Int(json.as_i64()? as i32),
+        EditableValue::Bool(_) => EditableValue::Bool(json.as_bool()?),
+        EditableValue::Color(_) => {
+            let array = json.as_array().filter(|array| array.len() == 3)?;
+            let mut color = [0.0; 3];
+            for (c, json) in color.iter_mut().zip(array.iter()) {
+                *c = json.as_f64()? as f32;
+            }
+            EditableValue::Color(color)
+        }
+    };
+    Some(value)
+}
+
+// values of the uniforms of a shader edited in the viewer, saved to a file per shader
+pub struct UniformEditor {
+    path: PathBuf,
+    // values edited since the file was read. untouched uniforms are not kept, so that their
+    // defaults follow the shader
+    values: HashMap<String, EditableValue>,
+    // the file as it was read, which keeps values of uniforms the shader does not have now
+    stored: Map<String, Value>,
+    changed: bool,
+}
+
+impl UniformEditor {
+    // the values saved for the shader, none if they cannot be read
+    pub fn load(shader_name: &str) -> UniformEditor {
+        let path = default_config_dir()
+            .join("uniforms")
+            .join(format!("{}.json", shader_name));
+        UniformEditor::open(&path).unwrap_or_else(|e| {
+            println!("Uniform editor error: {}: {}", path.display(), e);
+            UniformEditor::new(&path)
+        })
+    }
+
+    pub fn new(path: &Path) -> UniformEditor {
+        UniformEditor {
+            path: path.to_path_buf(),
+            values: HashMap::new(),
+            stored: Map::new(),
+            changed: false,
+        }
+    }
+
+    // a missing file has no values
+    pub fn open(path: &Path) -> Result<UniformEditor, Box<dyn Error>> {
+        let mut editor = UniformEditor::new(path);
+        match fs::read(path) {
+            Ok(data) => match serde_json::from_slice(&data)? {
+                Value::Object(stored) => editor.stored = stored,
+                _ => return Err("uniform values are invalid".into()),
+            },
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+        Ok(editor)
+    }
+
+    // the value of each editable uniform of the shader: the edited one, else the stored one,
+    // else the default
+    fn value(&self, name: &str, default: EditableValue) -> EditableValue {
+        // the shader may have changed the type since
+        if let Some(value) = self.values.get(name) {
+            if value.type_() == default.type_() {
+                return *value;
+            }
+        }
+        self.stored
+            .get(name)
+            .and_then(|json| from_json(json, &default))
+            .unwrap_or(default)
+    }
+
+    // set the values of the editable uniforms. needs a current OpenGL context with the
+    // shader in use
+    #[allow(clippy::missing_safety_doc)]
+    pub unsafe fn apply(&self, shader: &Shader) {
+        for (declaration, default) in shader.editable_uniforms() {
+            let value = self.value(&declaration.name, default);
+            shader.set_editable(&declaration.name, &value);
+        }
+    }
+
+    // an editor for each editable uniform, with the range of its annotation
+    pub fn build_ui(&mut self, ui: &Ui, shader: &Shader) {
+        let uniforms = shader.editable_uniforms();
+        if uniforms.is_empty() {
+            ui.text("No editable uniforms");
+        }
+        for (declaration, default) in uniforms {
+            let mut value = self.value(&declaration.name, default);
+            let label = ImString::new(declaration.name.as_str());
+            let range = declaration.annotation.range;
+            let changed = match &mut value {
+                EditableValue::Float(value) => {
+                    let (min, max) = range.unwrap_or((0.0, 1.0));
+                    imgui::Slider::new(&label).range(min..=max).build(ui, value)
+                }
+                EditableValue::Int(value) => {
+                    let drag = imgui::Drag::new(&label);
+                    match range {
+                        Some((min, max)) => drag.range(min as i32..=max as i32).build(ui, value),
+                        None => drag.build(ui, value),
+                    }
+                }
+                EditableValue::Bool(value) => ui.checkbox(&label, value),
+                EditableValue::Color(value) => imgui::ColorEdit::new(&label, value).build(ui),
+            };
+            if changed {
+                self.values.insert(declaration.name.clone(), value);
+                self.changed = true;
+            }
+        }
+        if ui.button(im_str!("Reset to defaults"), [0.0, 0.0]) {
+            // forgetting the values makes the uniforms follow the defaults of the shader
+            for (declaration, _) in shader.editable_uniforms() {
+                self.values.remove(&declaration.name);
+                self.stored.remove(&declaration.name);
+            }
+            self.changed = true;
+        }
+        // saved once a slider is let go rather than on every step of it
+        if self.changed && !ui.is_any_item_active() {
+            if let Err(e) = self.save() {
+                println!("Uniform editor error: {}: {}", self.path.display(), e);
+            }
+        }
+    }
+
+    // write the stored values with the edited ones over them
+    pub fn save(&mut self) -> Result<(), Box<dyn Error>> {
+        for (name, value) in self.values.iter() {
+            self.stored.insert(name.clone(), to_json(value));
+        }
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let json = Value::Object(self.stored.clone());
+        fs::write(&self.path, serde_json::to_vec_pretty(&json)?)?;
+        self.changed = false;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ok_save_and_open() {
+        let path = env::temp_dir().join("obj_viewer_test_uniforms/mono_shader.json");
+        let _ = fs::remove_file(&path);
+        let mut editor = UniformEditor::open(&path).unwrap();
+        let tint = EditableValue::Color([1.0, 1.0, 1.0]);
+        assert_eq!(tint, editor.value("uTint", tint));
+
+        let unlit = EditableValue::Bool(false);
+        assert_eq!(unlit, editor.value("uUnlit", unlit));
+        assert!(editor.values.is_empty());
+
+        let edited = EditableValue::Color([0.5, 0.25, 1.0]);
+        editor.values.insert("uTint".to_string(), edited);
+        editor.save().unwrap();
+
+        let editor = UniformEditor::open(&path).unwrap();
+        assert_eq!(edited, editor.value("uTint", tint));
+        // an untouched uniform is not saved, so it follows a later default
+        assert!(!editor.stored.contains_key("uUnlit"));
+        assert_eq!(
+            EditableValue::Bool(true),
+            editor.value("uUnlit", EditableValue::Bool(true))
+        );
+        // a uniform whose type has changed starts with its default
+        let brightness = EditableValue::Float(1.0);
+        assert_eq!(brightness, editor.value("uTint", brightness));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_ng_open() {
+        let path = env::temp_dir().join("obj_viewer_test_uniforms_invalid.json");
+        fs::write(&path, "[1, 2]").unwrap();
+        assert!(UniformEditor::open(&path).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+}
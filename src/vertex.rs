@@ -1,143 +1,299 @@
-use gl::types::{GLenum, GLfloat, GLint, GLsizei, GLsizeiptr};
 use std::mem;
 use std::os::raw::c_void;
 
+use bytemuck::Pod;
+use gl::types::{GLenum, GLint, GLsizei, GLsizeiptr, GLuint};
+
+// size in bytes of a component type of an attribute
+fn type_size(type_: GLenum) -> usize {
+    match type_ {
+        gl::BYTE | gl::UNSIGNED_BYTE => 1,
+        gl::SHORT | gl::UNSIGNED_SHORT | gl::HALF_FLOAT => 2,
+        gl::DOUBLE => 8,
+        _ => 4,
+    }
+}
+
+// one attribute of a vertex, such as a position of 3 floats
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Attribute {
+    pub components: GLint,
+    pub type_: GLenum,
+    // integers are read as floats from 0 to 1
+    pub normalized: bool,
+}
+
+impl Attribute {
+    pub fn floats(components: GLint) -> Attribute {
+        Attribute {
+            components,
+            type_: gl::FLOAT,
+            normalized: false,
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.components as usize * type_size(self.type_)
+    }
+}
+
+// the attributes of a vertex in the order they are stored. they are bound to locations
+// 0, 1, ... in that order, which is the order the shaders declare their inputs in
+#[derive(Clone, Debug, PartialEq)]
+pub struct VertexLayout {
+    pub attributes: Vec<Attribute>,
+}
+
+impl VertexLayout {
+    pub fn new(attributes: Vec<Attribute>) -> VertexLayout {
+        VertexLayout { attributes }
+    }
+
+    // attributes of floats only, with the number of components of each
+    pub fn floats(components: &[GLint]) -> VertexLayout {
+        VertexLayout::new(components.iter().map(|&n| Attribute::floats(n)).collect())
+    }
+
+    // bytes from one vertex to the next
+    pub fn stride(&self) -> usize {
+        self.attributes
+            .iter()
+            .map(|attribute| attribute.size())
+            .sum()
+    }
+
+    // byte offset of each attribute in a vertex
+    pub fn offsets(&self) -> Vec<usize> {
+        self.attributes
+            .iter()
+            .scan(0, |offset, attribute| {
+                let start = *offset;
+                *offset += attribute.size();
+                Some(start)
+            })
+            .collect()
+    }
+}
+
+// the types an index buffer can hold
+pub trait Index: Pod {
+    const TYPE: GLenum;
+}
+
+impl Index for u16 {
+    const TYPE: GLenum = gl::UNSIGNED_SHORT;
+}
+
+impl Index for u32 {
+    const TYPE: GLenum = gl::UNSIGNED_INT;
+}
+
+// byte offsets of the first indices of the ranges in an index buffer of the type
+pub fn index_offsets(index_type: GLenum, ranges: &[(usize, usize)]) -> Vec<usize> {
+    ranges
+        .iter()
+        .map(|&(first, _)| first * type_size(index_type))
+        .collect()
+}
+
+// a vertex array with its vertex buffer and an optional index buffer. the buffers are
+// deleted when it is dropped, which needs the OpenGL context it was made in
 pub struct Vertex {
-    vao: u32,
-    _vbo: u32,
-    ibo: u32,
+    vao: GLuint,
+    vbo: GLuint,
+    ibo: GLuint,
     vertex_num: i32,
+    index_num: i32,
+    index_type: GLenum,
 }
 
 impl Vertex {
-    pub fn new(
-        size: GLsizeiptr,
-        vertices: *const c_void,
-        usage: GLenum,
-        attribute_type_vec: std::vec::Vec<GLenum>,
-        attribute_size_vec: std::vec::Vec<GLint>,
-        stride: GLsizei,
-        vertex_num: i32,
-    ) -> Vertex {
+    // upload vertices laid out as `layout`. the data is any plain type, such as floats
+    // or structs of them
+    pub fn from_slice<T: Pod>(data: &[T], layout: &VertexLayout, usage: GLenum) -> Vertex {
+        let bytes: &[u8] = bytemuck::cast_slice(data);
+        let stride = layout.stride();
         let mut vao = 0;
         let mut vbo = 0;
         unsafe {
-            // create vertex array object and vertex buffer object
             gl::GenVertexArrays(1, &mut vao);
             gl::GenBuffers(1, &mut vbo);
-
-            // bind buffer
             gl::BindVertexArray(vao);
             gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
-            gl::BufferData(gl::ARRAY_BUFFER, size, vertices, usage);
-
-            let mut offset = 0;
-            for i in 0..attribute_type_vec.len() {
-                // attribute属性を有効にする
-                gl::EnableVertexAttribArray(i as u32);
-                // attribute属性を登録
+            gl::BufferData(
+                gl::ARRAY_BUFFER,
+                bytes.len() as GLsizeiptr,
+                bytes.as_ptr() as *const c_void,
+                usage,
+            );
+            for (i, (attribute, offset)) in
+                layout.attributes.iter().zip(layout.offsets()).enumerate()
+            {
+                gl::EnableVertexAttribArray(i as GLuint);
                 gl::VertexAttribPointer(
-                    i as u32,
-                    attribute_size_vec[i],
-                    attribute_type_vec[i],
-                    gl::FALSE,
-                    stride,
-                    (offset * mem::size_of::<GLfloat>()) as *const c_void,
+                    i as GLuint,
+                    attribute.components,
+                    attribute.type_,
+                    if attribute.normalized {
+                        gl::TRUE
+                    } else {
+                        gl::FALSE
+                    },
+                    stride as GLsizei,
+                    offset as *const c_void,
                 );
-                offset += attribute_size_vec[i] as usize;
             }
-
-            // unbind
-            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
             gl::BindVertexArray(0);
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
         }
         Vertex {
-            vao: vao,
-            _vbo: vbo,
-            ibo: 0u32,
-            vertex_num: vertex_num,
+            vao,
+            vbo,
+            ibo: 0,
+            vertex_num: bytes.len().checked_div(stride).unwrap_or(0) as i32,
+            index_num: 0,
+            index_type: gl::UNSIGNED_INT,
         }
     }
 
-    pub fn setup_ibo(&mut self, indices_size: GLsizeiptr, indices: *const c_void) {
+    // upload the index buffer, replacing the one before. it is part of the vertex array
+    pub fn set_indices<I: Index>(&mut self, indices: &[I]) {
         unsafe {
-            gl::GenBuffers(1, &mut self.ibo);
+            if self.ibo == 0 {
+                gl::GenBuffers(1, &mut self.ibo);
+            }
+            gl::BindVertexArray(self.vao);
             gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.ibo);
             gl::BufferData(
                 gl::ELEMENT_ARRAY_BUFFER,
-                indices_size,
-                indices,
+                mem::size_of_val(indices) as GLsizeiptr,
+                indices.as_ptr() as *const c_void,
                 gl::STATIC_DRAW,
             );
+            gl::BindVertexArray(0);
             gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0);
         }
+        self.index_num = indices.len() as i32;
+        self.index_type = I::TYPE;
+    }
+
+    // upload the indices as u16 when every one of them fits, which halves the buffer
+    pub fn set_compact_indices(&mut self, indices: &[u32]) {
+        if indices.iter().all(|&index| index <= u16::MAX as u32) {
+            let indices: Vec<u16> = indices.iter().map(|&index| index as u16).collect();
+            self.set_indices(&indices);
+        } else {
+            self.set_indices(indices);
+        }
+    }
+
+    pub fn vertex_num(&self) -> i32 {
+        self.vertex_num
+    }
+
+    pub fn index_num(&self) -> i32 {
+        self.index_num
     }
 
-    #[allow(dead_code)]
+    // gl::UNSIGNED_SHORT or gl::UNSIGNED_INT
+    pub fn index_type(&self) -> GLenum {
+        self.index_type
+    }
+
+    // draw every vertex as a point. the size comes from gl::PointSize
     pub fn draw(&self) {
+        self.draw_arrays(gl::POINTS);
+    }
+
+    // draw the vertices in order, without the index buffer
+    pub fn draw_arrays(&self, mode: GLenum) {
         unsafe {
             gl::BindVertexArray(self.vao);
-            // gl::DrawArrays(gl::TRIANGLES, 0, self.vertex_num);
-            gl::DrawArrays(gl::POINTS, 0, self.vertex_num);
-            gl::PointSize(10.0);
+            gl::DrawArrays(mode, 0, self.vertex_num);
             gl::BindVertexArray(0);
         }
     }
 
-    pub fn draw_elements(
-        &self,
-        mode: gl::types::GLenum,
-        indices_size: GLsizei,
-        indices: *const c_void,
-    ) {
+    // draw all the indices of the index buffer
+    pub fn draw_elements(&self, mode: GLenum) {
+        if self.ibo == 0 {
+            return;
+        }
+        self.draw_ranges(mode, &[(0, self.index_num as usize)]);
+    }
+
+    // draw parts of the index buffer given as (first index, count), or parts of the vertices
+    // when there is no index buffer. the parts are drawn with a single call
+    pub fn draw_ranges(&self, mode: GLenum, ranges: &[(usize, usize)]) {
+        if ranges.is_empty() {
+            return;
+        }
+        let counts: Vec<GLsizei> = ranges.iter().map(|&(_, count)| count as GLsizei).collect();
         unsafe {
-            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.ibo);
-            gl::BindVertexArray(self.vao); //add
-            gl::DrawElements(mode, indices_size, gl::UNSIGNED_INT, indices);
-            gl::BindVertexArray(0); // add
-            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0);
+            gl::BindVertexArray(self.vao);
+            if self.ibo == 0 {
+                let firsts: Vec<GLint> = ranges.iter().map(|&(first, _)| first as GLint).collect();
+                gl::MultiDrawArrays(
+                    mode,
+                    firsts.as_ptr(),
+                    counts.as_ptr(),
+                    ranges.len() as GLsizei,
+                );
+            } else {
+                // the offsets into the bound index buffer are in bytes
+                let offsets: Vec<*const c_void> = index_offsets(self.index_type, ranges)
+                    .into_iter()
+                    .map(|offset| offset as *const c_void)
+                    .collect();
+                gl::MultiDrawElements(
+                    mode,
+                    counts.as_ptr(),
+                    self.index_type,
+                    offsets.as_ptr(),
+                    ranges.len() as GLsizei,
+                );
+            }
+            gl::BindVertexArray(0);
+        }
+    }
+}
+
+impl Drop for Vertex {
+    fn drop(&mut self) {
+        unsafe {
+            if self.ibo != 0 {
+                gl::DeleteBuffers(1, &self.ibo);
+            }
+            gl::DeleteBuffers(1, &self.vbo);
+            gl::DeleteVertexArrays(1, &self.vao);
         }
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    // pub fn draw_elements2(&self, indices: &Indices) {
-    //     unsafe {
-    //         gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.ibo);
-    //         gl::BindVertexArray(self.vao); //add
-    //         let mut counter = 0;
-    //         for i in 0..indices.polygon_count.len() {
-    //             match indices.polygon_count[i] {
-    //                 2 => {
-    //                     gl::DrawElements(
-    //                         gl::LINES,
-    //                         indices.polygon_count[i] as GLsizei,
-    //                         gl::UNSIGNED_INT,
-    //                         indices.vertex_indices[counter..].as_ptr() as *const c_void,
-    //                     );
-    //                     counter += 2;
-    //                 }
-    //                 3 => {
-    //                     gl::DrawElements(
-    //                         gl::TRIANGLES,
-    //                         indices.polygon_count[i] as GLsizei,
-    //                         gl::UNSIGNED_INT,
-    //                         indices.vertex_indices[counter..].as_ptr() as *const c_void,
-    //                     );
-    //                     counter += 3;
-    //                 }
-    //                 4 => {
-    //                     gl::DrawElements(
-    //                         gl::QUADS,
-    //                         indices.polygon_count[i] as GLsizei,
-    //                         gl::UNSIGNED_INT,
-    //                         indices.vertex_indices[counter..].as_ptr() as *const c_void,
-    //                     );
-    //                     counter += 4;
-    //                 }
-    //                 _ => (),
-    //             }
-    //         }
-    //         gl::BindVertexArray(0); // add
-    //         gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0);
-    //     }
-    // }
+    #[test]
+    fn test_ok_vertex_layout() {
+        let layout = VertexLayout::floats(&[3, 3, 3, 2, 1]);
+        assert_eq!(48, layout.stride());
+        assert_eq!(vec![0, 12, 24, 36, 44], layout.offsets());
+
+        let color = Attribute {
+            components: 4,
+            type_: gl::UNSIGNED_BYTE,
+            normalized: true,
+        };
+        let layout = VertexLayout::new(vec![Attribute::floats(3), color]);
+        assert_eq!(16, layout.stride());
+        assert_eq!(vec![0, 12], layout.offsets());
+    }
+
+    #[test]
+    fn test_ok_index_offsets() {
+        let ranges = [(0, 3), (6, 3), (30, 6)];
+        assert_eq!(vec![0, 12, 60], index_offsets(u16::TYPE, &ranges));
+        assert_eq!(vec![0, 24, 120], index_offsets(u32::TYPE, &ranges));
+    }
 }
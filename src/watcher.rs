@@ -0,0 +1,152 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::model::Model;
+
+// time between two looks at the files
+pub const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+// modification time and size of a file, None while it does not exist
+type Stamp = Option<(SystemTime, u64)>;
+
+fn stamp(path: &Path) -> Stamp {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+// the model file with the material libraries and textures it refers to, which are
+// looked up relative to the directory of the model like the loaders do
+pub fn model_files(path: &Path, model: &Model) -> Vec<PathBuf> {
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    let mut files = vec![path.to_path_buf()];
+    let maps = model.materials.iter().flat_map(|material| material.maps());
+    let names = model
+        .material_libs
+        .iter()
+        .chain(maps.filter_map(|map| map.as_ref()));
+    for name in names {
+        // embedded textures are read from the model file itself
+        if model
+            .embedded_textures
+            .iter()
+            .any(|(embedded, _)| embedded == name)
+        {
+            continue;
+        }
+        let file = dir.join(name);
+        if !files.contains(&file) {
+            files.push(file);
+        }
+    }
+    files
+}
+
+// polls files for changes. a change is reported once the files have stayed the same for
+// a whole interval, so that a file still being written is not read half way
+pub struct FileWatcher {
+    files: Vec<(PathBuf, Stamp)>,
+    last_poll: Instant,
+    changed: bool,
+}
+
+impl FileWatcher {
+    pub fn new(files: Vec<PathBuf>) -> FileWatcher {
+        let files = files
+            .into_iter()
+            .map(|file| {
+                let stamp = stamp(&file);
+                (file, stamp)
+            })
+            .collect();
+        FileWatcher {
+            files,
+            last_poll: Instant::now(),
+            changed: false,
+        }
+    }
+
+    // watch a model file with the files it refers to
+    pub fn for_model(path: &Path, model: &Model) -> FileWatcher {
+        FileWatcher::new(model_files(path, model))
+    }
+
+    // the first file, which is the model file of a watcher for a model
+    pub fn path(&self) -> &Path {
+        &self.files[0].0
+    }
+
+    pub fn files(&self) -> impl Iterator<Item = &Path> {
+        self.files.iter().map(|(file, _)| file.as_path())
+    }
+
+    // true once when the files have changed and settled since the last change was reported
+    pub fn poll(&mut self, now: Instant) -> bool {
+        if now.duration_since(self.last_poll) < POLL_INTERVAL {
+            return false;
+        }
+        self.last_poll = now;
+        let mut changed = false;
+        for (file, old) in self.files.iter_mut() {
+            let new = stamp(file);
+            if new != *old {
+                *old = new;
+                changed = true;
+            }
+        }
+        if changed {
+            self.changed = true;
+            return false;
+        }
+        let settled = self.changed;
+        self.changed = false;
+        settled
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Material;
+
+    #[test]
+    fn test_ok_model_files() {
+        let mut model = Model::new();
+        model.material_libs = vec!["a.mtl".to_string(), "a.mtl".to_string()];
+        let mut material = Material::new("red");
+        material.diffuse_map = Some("tex/red.png".to_string());
+        material.bump_map = Some("embedded.png".to_string());
+        model.materials.push(material);
+        model.embedded_textures = vec![("embedded.png".to_string(), Vec::new())];
+        assert_eq!(
+            vec![
+                PathBuf::from("dir/a.obj"),
+                PathBuf::from("dir/a.mtl"),
+                PathBuf::from("dir/tex/red.png"),
+            ],
+            model_files(Path::new("dir/a.obj"), &model)
+        );
+    }
+
+    #[test]
+    fn test_ok_poll() {
+        let path = std::env::temp_dir().join("obj_viewer_test_watcher.obj");
+        fs::write(&path, "v 0 0 0\n").unwrap();
+        let mut watcher = FileWatcher::new(vec![path.clone()]);
+        let start = Instant::now();
+        assert!(!watcher.poll(start));
+        assert!(!watcher.poll(start + POLL_INTERVAL));
+
+        // the change is reported after the file has stayed the same for an interval
+        fs::write(&path, "v 0 0 0\nv 1 0 0\n").unwrap();
+        assert!(!watcher.poll(start + POLL_INTERVAL));
+        assert!(!watcher.poll(start + POLL_INTERVAL * 2));
+        assert!(watcher.poll(start + POLL_INTERVAL * 3));
+        assert!(!watcher.poll(start + POLL_INTERVAL * 4));
+
+        // removing the file is a change as well
+        fs::remove_file(&path).unwrap();
+        assert!(!watcher.poll(start + POLL_INTERVAL * 5));
+        assert!(watcher.poll(start + POLL_INTERVAL * 6));
+    }
+}
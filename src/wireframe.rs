@@ -0,0 +1,233 @@
+use cgmath::prelude::SquareMatrix;
+use gl::types::GLuint;
+
+use crate::display::DisplayState;
+use crate::mesh::{self, Mesh};
+use crate::model::Model;
+use crate::shader::ShaderVariants;
+use crate::vertex::{Vertex, VertexLayout};
+
+type Matrix4 = cgmath::Matrix4<f32>;
+type Vector3 = cgmath::Vector3<f32>;
+
+// floats per corner of the wire buffer: position and barycentric coordinates
+pub const WIRE_FLOATS: usize = 6;
+
+// how the edges of the faces are shown
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WireframeMode {
+    #[default]
+    Off,
+    // the faces as lines only, with gl::PolygonMode
+    Lines,
+    // smooth lines over the shaded faces
+    OnShaded,
+    // the lines which are not behind faces
+    HiddenLine,
+}
+
+#[allow(clippy::missing_safety_doc)]
+impl WireframeMode {
+    pub const ALL: [WireframeMode; 4] = [
+        WireframeMode::Off,
+        WireframeMode::Lines,
+        WireframeMode::OnShaded,
+        WireframeMode::HiddenLine,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            WireframeMode::Off => "Off",
+            WireframeMode::Lines => "Lines",
+            WireframeMode::OnShaded => "On Shaded",
+            WireframeMode::HiddenLine => "Hidden Line",
+        }
+    }
+
+    // modes which draw the wire buffer after the faces
+    pub fn draws_wire(self) -> bool {
+        matches!(self, WireframeMode::OnShaded | WireframeMode::HiddenLine)
+    }
+
+    // state for drawing the faces under the wire: the faces are pushed back a little so that
+    // the lines on them pass the depth test, and hidden-line draws their depth only
+    pub unsafe fn setup_faces(self) {
+        gl::Enable(gl::DEPTH_TEST);
+        gl::Enable(gl::POLYGON_OFFSET_FILL);
+        gl::PolygonOffset(1.0, 1.0);
+        if self == WireframeMode::HiddenLine {
+            gl::ColorMask(gl::FALSE, gl::FALSE, gl::FALSE, gl::FALSE);
+        }
+    }
+}
+
+// the triangles with a vertex per corner, WIRE_FLOATS floats each. an edge is drawn where a
+// barycentric coordinate is 0. with `polygon_edges`, the coordinate of the corner across an
+// edge which only splits a polygon is raised by 1, so that the edge is not drawn
+pub fn wire_buffer(model: &Model, polygon_edges: bool) -> Vec<f32> {
+    let indices = &model.indices.vertex_indices;
+    let mut buffer = Vec::with_capacity(indices.len() * WIRE_FLOATS);
+    let mut triangles = indices.chunks_exact(3);
+    // polygons are split into fans of triangles (0, i, i + 1) by Model::add_polygon
+    let fans = model
+        .polygon_sizes
+        .iter()
+        .flat_map(|&size| (1..(size as usize).saturating_sub(1)).map(move |i| (i, size as usize)));
+    for ((i, size), triangle) in fans.zip(&mut triangles) {
+        let mut hidden = [0.0; 3];
+        if polygon_edges {
+            // the edge (0, i) is across corner i + 1, and (i + 1, 0) is across corner i
+            if i > 1 {
+                hidden[2] = 1.0;
+            }
+            if i + 2 < size {
+                hidden[1] = 1.0;
+            }
+        }
+        push_triangle(&mut buffer, model, triangle, hidden);
+    }
+    // triangles of loaders which do not record the polygons show all their edges
+    for triangle in triangles {
+        push_triangle(&mut buffer, model, triangle, [0.0; 3]);
+    }
+    buffer
+}
+
+fn push_triangle(buffer: &mut Vec<f32>, model: &Model, triangle: &[u32], hidden: [f32; 3]) {
+    if !model.triangle_in_range(triangle) {
+        buffer.resize(buffer.len() + triangle.len() * WIRE_FLOATS, 0.0);
+        return;
+    }
+    for (corner, &v) in triangle.iter().enumerate() {
+        let v = v as usize * 3;
+        buffer.extend_from_slice(&model.vertex[v..v + 3]);
+        let mut barycentric = hidden;
+        barycentric[corner] += 1.0;
+        buffer.extend_from_slice(&barycentric);
+    }
+}
+
+// the edges of the faces drawn over them
+pub struct Wireframe {
+    pub shaders: ShaderVariants,
+    // made when first shown, for the setting of polygon_edges
+    mesh: Option<(bool, Mesh)>,
+}
+
+#[allow(clippy::missing_safety_doc)]
+impl Wireframe {
+    pub fn new() -> Wireframe {
+        Wireframe {
+            shaders: ShaderVariants::new("rsc/shader/wire_shader.vs", "rsc/shader/wire_shader.fs"),
+            mesh: None,
+        }
+    }
+
+    // forget the wire buffer of the model before
+    pub fn set_model(&mut self) {
+        self.mesh = None;
+    }
+
+    // draw the wire of the visible sub-meshes over the faces, with the camera block at the
+    // binding. the state of the display is set again afterwards
+    pub unsafe fn draw(
+        &mut self,
+        model: &Model,
+        state: &DisplayState,
+        camera_binding: GLuint,
+        sub_meshes: impl Iterator<Item = usize>,
+    ) {
+        gl::Disable(gl::POLYGON_OFFSET_FILL);
+        gl::ColorMask(gl::TRUE, gl::TRUE, gl::TRUE, gl::TRUE);
+        if let Some(shader) = self.shaders.get(&[]).filter(|shader| shader.id != 0) {
+            let polygon_edges = state.is_enabled_polygon_edges;
+            if self
+                .mesh
+                .as_ref()
+                .is_none_or(|(edges, _)| *edges != polygon_edges)
+            {
+                let buffer = wire_buffer(model, polygon_edges);
+                let layout = VertexLayout::floats(&[3, 3]);
+                let vertex = Vertex::from_slice(&buffer, &layout, gl::STATIC_DRAW);
+                self.mesh = Some((polygon_edges, Mesh::new(vertex, mesh::sub_meshes(model))));
+            }
+            // the smoothed edges are blended, and do not hide each other
+            gl::Enable(gl::BLEND);
+            gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+            gl::DepthMask(gl::FALSE);
+            shader.use_program();
+            shader.bind_block("Camera", camera_binding);
+            shader.set_uniform("uModel", Matrix4::identity());
+            let [r, g, b] = state.wire_color;
+            shader.set_uniform("uWireColor", Vector3::new(r, g, b));
+            shader.set_uniform("uWireWidth", state.wire_width);
+            if let Some((_, mesh)) = self.mesh.as_ref() {
+                mesh.draw_sub_meshes(gl::TRIANGLES, sub_meshes);
+            }
+            gl::DepthMask(gl::TRUE);
+        }
+        state.setup();
+    }
+}
+
+impl Default for Wireframe {
+    fn default() -> Wireframe {
+        Wireframe::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{Corner, GroupState};
+
+    // the barycentric coordinates of each corner
+    fn barycentrics(buffer: &[f32]) -> Vec<[f32; 3]> {
+        buffer
+            .chunks_exact(WIRE_FLOATS)
+            .map(|corner| [corner[3], corner[4], corner[5]])
+            .collect()
+    }
+
+    fn polygon_model(sizes: &[u32]) -> Model {
+        let mut model = Model::new();
+        model.vertex = vec![0.0; 3 * 6];
+        for &size in sizes {
+            let polygon: Vec<Corner> = (0..size)
+                .map(|vertex| Corner {
+                    vertex,
+                    texture: None,
+                    normal: None,
+                })
+                .collect();
+            model.add_polygon(&polygon, &GroupState::new());
+        }
+        model
+    }
+
+    #[test]
+    fn test_ok_wire_buffer() {
+        let model = polygon_model(&[3, 4]);
+        let all = barycentrics(&wire_buffer(&model, false));
+        assert_eq!(9, all.len());
+        assert_eq!([0.0, 1.0, 0.0], all[4]);
+
+        // the diagonal (0, 2) of the quad is across corner 1 of its first triangle and
+        // corner 2 of its second one
+        let edges = barycentrics(&wire_buffer(&model, true));
+        assert_eq!(all[..3].to_vec(), edges[..3].to_vec());
+        assert_eq!([1.0, 1.0, 0.0], edges[3]);
+        assert_eq!([0.0, 2.0, 0.0], edges[4]);
+        assert_eq!([0.0, 1.0, 1.0], edges[7]);
+        assert_eq!([0.0, 0.0, 2.0], edges[8]);
+    }
+
+    #[test]
+    fn test_ok_wire_buffer_pentagon() {
+        let model = polygon_model(&[5]);
+        let edges = barycentrics(&wire_buffer(&model, true));
+        // the middle triangle of the fan has both of its diagonals hidden
+        assert_eq!([1.0, 1.0, 1.0], edges[3]);
+        assert_eq!([0.0, 2.0, 1.0], edges[4]);
+    }
+}
@@ -0,0 +1,73 @@
+use std::error::Error;
+use std::fs;
+
+use crate::model::{unit_colors, Model};
+
+// point cloud with one point per line, "x y z" or "x y z r g b". values may also be
+// separated by commas, and lines starting with # or // are comments
+pub fn parse_xyz(text: &str) -> Result<Model, Box<dyn Error>> {
+    let mut model = Model::new();
+    let mut colors = Vec::new();
+    let mut columns = None;
+    for (i, line) in text.lines().enumerate() {
+        let counter = i + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with("//") {
+            continue;
+        }
+        let values: Vec<f32> = line
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|s| !s.is_empty())
+            .map(|s| s.parse::<f32>())
+            .collect::<Result<_, _>>()
+            .map_err(|_| format!("XYZ value is invalid. Line number:{}", counter))?;
+        if values.len() < 3 {
+            let error_text = format!("XYZ point needs x, y and z. Line number:{}", counter);
+            return Err(error_text.into());
+        }
+        // with 6 or more values, the ones after the position are the color
+        let has_color = values.len() >= 6;
+        match columns {
+            None => columns = Some(has_color),
+            Some(c) if c != has_color => {
+                let error_text =
+                    format!("XYZ line differs from the first. Line number:{}", counter);
+                return Err(error_text.into());
+            }
+            _ => {}
+        }
+        model.vertex.extend_from_slice(&values[..3]);
+        if has_color {
+            colors.extend_from_slice(&values[3..6]);
+        }
+    }
+    model.vertex_color = unit_colors(&colors);
+    Ok(model)
+}
+
+pub fn load_xyz(path: &str) -> Result<Model, Box<dyn Error>> {
+    parse_xyz(&fs::read_to_string(path)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ok_parse_xyz() {
+        let model = parse_xyz("# scan\n0 0 0\n\n1.5 2 -3\n").unwrap();
+        assert_eq!(vec![0.0, 0.0, 0.0, 1.5, 2.0, -3.0], model.vertex);
+        assert!(model.vertex_color.is_empty());
+        assert!(model.polygon_sizes.is_empty());
+
+        let model = parse_xyz("0,0,0,255,0,0\n1,1,1,0,0,255\n").unwrap();
+        assert_eq!(vec![1.0, 0.0, 0.0, 0.0, 0.0, 1.0], model.vertex_color);
+    }
+
+    #[test]
+    fn test_ng_parse_xyz() {
+        assert!(parse_xyz("0 0\n").is_err());
+        assert!(parse_xyz("0 0 x\n").is_err());
+        assert!(parse_xyz("0 0 0 1 1 1\n1 1 1\n").is_err());
+    }
+}